@@ -0,0 +1,556 @@
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Vector2, Vector3, Zero};
+
+/// Axis-aligned bounding box. An empty box has `min > max` and contains nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+	pub min: Vector3<f32>,
+	pub max: Vector3<f32>,
+}
+
+impl BoundingBox {
+	pub fn empty() -> Self {
+		Self {
+			min: Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+			max: Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+		}
+	}
+
+	pub fn from_points<I: IntoIterator<Item = Vector3<f32>>>(points: I) -> Self {
+		let mut bounds = Self::empty();
+		for p in points {
+			bounds.extend(p);
+		}
+		bounds
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+	}
+
+	pub fn extend(&mut self, p: Vector3<f32>) {
+		self.min = Vector3::new(self.min.x.min(p.x), self.min.y.min(p.y), self.min.z.min(p.z));
+		self.max = Vector3::new(self.max.x.max(p.x), self.max.y.max(p.y), self.max.z.max(p.z));
+	}
+
+	pub fn union(&self, other: &BoundingBox) -> Self {
+		let mut bounds = *self;
+		if !other.is_empty() {
+			bounds.extend(other.min);
+			bounds.extend(other.max);
+		}
+		bounds
+	}
+
+	pub fn center(&self) -> Vector3<f32> {
+		if self.is_empty() {
+			return Vector3::zero();
+		}
+		(self.min + self.max) / 2.0
+	}
+
+	pub fn size(&self) -> Vector3<f32> {
+		if self.is_empty() {
+			return Vector3::zero();
+		}
+		self.max - self.min
+	}
+
+	pub fn diag(&self) -> f32 {
+		self.size().magnitude()
+	}
+
+	pub fn corners(&self) -> [Vector3<f32>; 8] {
+		let (a, b) = (self.min, self.max);
+		[
+			Vector3::new(a.x, a.y, a.z),
+			Vector3::new(b.x, a.y, a.z),
+			Vector3::new(a.x, b.y, a.z),
+			Vector3::new(b.x, b.y, a.z),
+			Vector3::new(a.x, a.y, b.z),
+			Vector3::new(b.x, a.y, b.z),
+			Vector3::new(a.x, b.y, b.z),
+			Vector3::new(b.x, b.y, b.z),
+		]
+	}
+
+	/// Box enclosing this box after transformation by `m` (Arvo's method).
+	pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+		if self.is_empty() {
+			return *self;
+		}
+		let mut min = m.w.truncate();
+		let mut max = min;
+		for i in 0..3 {
+			for j in 0..3 {
+				let a = m[j][i] * self.min[j];
+				let b = m[j][i] * self.max[j];
+				min[i] += a.min(b);
+				max[i] += a.max(b);
+			}
+		}
+		Self { min, max }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+	pub center: Vector3<f32>,
+	pub radius: f32,
+}
+
+impl BoundingSphere {
+	/// The tighter of Ritter's approximate sphere and the sphere around the points' box center.
+	pub fn from_points(points: &[Vector3<f32>]) -> Self {
+		let ritter = Self::ritter(points);
+		let center = BoundingBox::from_points(points.iter().copied()).center();
+		let radius = points.iter().fold(0.0_f32, |r, p| r.max((p - center).magnitude()));
+		if radius < ritter.radius {
+			Self { center, radius }
+		} else {
+			ritter
+		}
+	}
+
+	/// Ritter's approximate bounding sphere, grown until it contains every point.
+	fn ritter(points: &[Vector3<f32>]) -> Self {
+		let first = match points.first() {
+			Some(p) => *p,
+			None => return Self { center: Vector3::zero(), radius: 0.0 },
+		};
+		let farthest = |from: Vector3<f32>| {
+			points.iter()
+				.copied()
+				.fold(from, |best, p| if (p - from).magnitude2() > (best - from).magnitude2() { p } else { best })
+		};
+
+		let a = farthest(first);
+		let b = farthest(a);
+		let mut center = (a + b) / 2.0;
+		let mut radius = (b - a).magnitude() / 2.0;
+
+		for p in points {
+			let d = (p - center).magnitude();
+			if d > radius {
+				let new_radius = (radius + d) / 2.0;
+				center += (p - center) * ((new_radius - radius) / d);
+				radius = new_radius;
+			}
+		}
+
+		Self { center, radius }
+	}
+
+	/// Sphere enclosing this sphere after transformation by `m`, scaled by its largest axis scale.
+	pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+		let scale = m.x.truncate().magnitude()
+			.max(m.y.truncate().magnitude())
+			.max(m.z.truncate().magnitude());
+		Self {
+			center: (m * self.center.extend(1.0)).truncate(),
+			radius: self.radius * scale,
+		}
+	}
+}
+
+/// Oriented bounding box with orthonormal `axes` and the box half size along each of them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedBoundingBox {
+	pub center: Vector3<f32>,
+	pub axes: [Vector3<f32>; 3],
+	pub half_extents: Vector3<f32>,
+}
+
+impl OrientedBoundingBox {
+	/// Approximates the minimal-volume box. Each principal axis and each world axis is tried as a
+	/// fixed box axis, and the minimal-area rectangle of the points projected onto the plane
+	/// perpendicular to it is found with rotating calipers. The smallest resulting box wins.
+	pub fn from_points(points: &[Vector3<f32>]) -> Self {
+		let aabb = BoundingBox::from_points(points.iter().copied());
+		let mut best = Self::from_aabb(&aabb);
+		if points.len() < 2 {
+			return best;
+		}
+
+		let principal = principal_axes(points);
+		let candidates = principal.into_iter().chain([Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]);
+		for axis in candidates {
+			let obb = Self::fit_around_axis(points, axis);
+			if obb.volume() < best.volume() {
+				best = obb;
+			}
+		}
+
+		best
+	}
+
+	pub fn from_aabb(aabb: &BoundingBox) -> Self {
+		Self {
+			center: aabb.center(),
+			axes: [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+			half_extents: aabb.size() / 2.0,
+		}
+	}
+
+	pub fn volume(&self) -> f32 {
+		8.0 * self.half_extents.x * self.half_extents.y * self.half_extents.z
+	}
+
+	pub fn corners(&self) -> [Vector3<f32>; 8] {
+		let [x, y, z] = [
+			self.axes[0] * self.half_extents.x,
+			self.axes[1] * self.half_extents.y,
+			self.axes[2] * self.half_extents.z,
+		];
+		let c = self.center;
+		[
+			c - x - y - z,
+			c + x - y - z,
+			c - x + y - z,
+			c + x + y - z,
+			c - x - y + z,
+			c + x - y + z,
+			c - x + y + z,
+			c + x + y + z,
+		]
+	}
+
+	/// Box after transformation by `m`. Non-uniform scale is folded into the half extents; shear is not supported.
+	pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+		let mut axes = self.axes;
+		let mut half_extents = self.half_extents;
+		for i in 0..3 {
+			let v = (m * axes[i].extend(0.0)).truncate();
+			let len = v.magnitude();
+			half_extents[i] *= len;
+			axes[i] = if len > 0.0 { v / len } else { axes[i] };
+		}
+		Self {
+			center: (m * self.center.extend(1.0)).truncate(),
+			axes,
+			half_extents,
+		}
+	}
+
+	fn fit_around_axis(points: &[Vector3<f32>], axis: Vector3<f32>) -> Self {
+		let axis = axis.normalize();
+		let helper = if axis.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+		let u = axis.cross(helper).normalize();
+		let v = axis.cross(u);
+
+		let projected: Vec<Vector2<f32>> = points.iter().map(|p| Vector2::new(p.dot(u), p.dot(v))).collect();
+		let hull = convex_hull_2d(projected);
+		let (dir, rect_min, rect_max) = min_area_rect(&hull);
+		let perp = Vector2::new(-dir.y, dir.x);
+
+		let axis_u = u * dir.x + v * dir.y;
+		let axis_v = u * perp.x + v * perp.y;
+		let (mut axis_min, mut axis_max) = (f32::INFINITY, f32::NEG_INFINITY);
+		for p in points {
+			let d = p.dot(axis);
+			axis_min = axis_min.min(d);
+			axis_max = axis_max.max(d);
+		}
+
+		let mid = (rect_min + rect_max) / 2.0;
+		Self {
+			center: axis_u * mid.x + axis_v * mid.y + axis * ((axis_min + axis_max) / 2.0),
+			axes: [axis_u, axis_v, axis],
+			half_extents: Vector3::new(
+				(rect_max.x - rect_min.x) / 2.0,
+				(rect_max.y - rect_min.y) / 2.0,
+				(axis_max - axis_min) / 2.0,
+			),
+		}
+	}
+}
+
+/// Every bounding volume kept for a mesh or submesh.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+	pub aabb: BoundingBox,
+	pub sphere: BoundingSphere,
+	pub obb: OrientedBoundingBox,
+}
+
+impl Bounds {
+	pub fn from_points(points: &[Vector3<f32>]) -> Self {
+		Self {
+			aabb: BoundingBox::from_points(points.iter().copied()),
+			sphere: BoundingSphere::from_points(points),
+			obb: OrientedBoundingBox::from_points(points),
+		}
+	}
+
+	pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+		Self {
+			aabb: self.aabb.transform(m),
+			sphere: self.sphere.transform(m),
+			obb: self.obb.transform(m),
+		}
+	}
+}
+
+/// Eigenvectors of the point covariance matrix, found with Jacobi rotations.
+fn principal_axes(points: &[Vector3<f32>]) -> [Vector3<f32>; 3] {
+	let n = points.len() as f32;
+	let mean = points.iter().fold(Vector3::zero(), |acc, p| acc + p) / n;
+	let mut a = [[0.0_f32; 3]; 3];
+	for p in points {
+		let d = p - mean;
+		for i in 0..3 {
+			for j in 0..3 {
+				a[i][j] += d[i] * d[j] / n;
+			}
+		}
+	}
+
+	let mut v = [[1.0_f32, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+	for _ in 0..32 {
+		let (p, q) = [(0, 1), (0, 2), (1, 2)].into_iter()
+			.max_by(|&(i, j), &(k, l)| a[i][j].abs().total_cmp(&a[k][l].abs()))
+			.unwrap();
+		if a[p][q].abs() < 1e-12 {
+			break;
+		}
+
+		let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+		let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+		let c = 1.0 / (t * t + 1.0).sqrt();
+		let s = t * c;
+		for row in a.iter_mut() {
+			let (kp, kq) = (row[p], row[q]);
+			row[p] = c * kp - s * kq;
+			row[q] = s * kp + c * kq;
+		}
+		let (row_p, row_q) = (a[p], a[q]);
+		a[p] = [0, 1, 2].map(|k| c * row_p[k] - s * row_q[k]);
+		a[q] = [0, 1, 2].map(|k| s * row_p[k] + c * row_q[k]);
+		for row in v.iter_mut() {
+			let (kp, kq) = (row[p], row[q]);
+			row[p] = c * kp - s * kq;
+			row[q] = s * kp + c * kq;
+		}
+	}
+
+	let m = Matrix3::from(v).transpose();
+	[m.x.normalize(), m.y.normalize(), m.z.normalize()]
+}
+
+/// Andrew's monotone chain, returned counter-clockwise without the closing point.
+fn convex_hull_2d(mut points: Vec<Vector2<f32>>) -> Vec<Vector2<f32>> {
+	points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+	points.dedup();
+	if points.len() < 3 {
+		return points;
+	}
+
+	let cross = |o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+	let mut hull: Vec<Vector2<f32>> = Vec::with_capacity(points.len() + 1);
+	let push = |hull: &mut Vec<Vector2<f32>>, start: usize, p: Vector2<f32>| {
+		while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0 {
+			hull.pop();
+		}
+		hull.push(p);
+	};
+	for &p in points.iter() {
+		push(&mut hull, 0, p);
+	}
+	hull.pop();
+	let start = hull.len();
+	for &p in points.iter().rev() {
+		push(&mut hull, start, p);
+	}
+	hull.pop();
+	hull
+}
+
+/// Minimal-area enclosing rectangle of a convex hull. Returns the rectangle's first axis and its
+/// extents along that axis and its perpendicular.
+fn min_area_rect(hull: &[Vector2<f32>]) -> (Vector2<f32>, Vector2<f32>, Vector2<f32>) {
+	let extents = |dir: Vector2<f32>| {
+		let perp = Vector2::new(-dir.y, dir.x);
+		let mut min = Vector2::new(f32::INFINITY, f32::INFINITY);
+		let mut max = Vector2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+		for p in hull {
+			let q = Vector2::new(p.dot(dir), p.dot(perp));
+			min = Vector2::new(min.x.min(q.x), min.y.min(q.y));
+			max = Vector2::new(max.x.max(q.x), max.y.max(q.y));
+		}
+		(min, max)
+	};
+
+	// the optimal rectangle is flush with a hull edge; edges are bucketed by angle (mod 90 degrees)
+	// so large hulls don't test thousands of nearly identical directions
+	let mut angles: Vec<i32> = (0..hull.len())
+		.filter_map(|i| {
+			let e = hull[(i + 1) % hull.len()] - hull[i];
+			if e.magnitude2() == 0.0 {
+				return None;
+			}
+			let deg = e.y.atan2(e.x).to_degrees().rem_euclid(90.0);
+			Some((deg * 16.0).round() as i32 % (90 * 16))
+		})
+		.collect();
+	angles.sort_unstable();
+	angles.dedup();
+	if angles.is_empty() {
+		angles.push(0);
+	}
+
+	let mut best = (Vector2::unit_x(), Vector2::zero(), Vector2::zero());
+	let mut best_area = f32::INFINITY;
+	for a in angles {
+		let rad = (a as f32 / 16.0).to_radians();
+		let dir = Vector2::new(rad.cos(), rad.sin());
+		let (min, max) = extents(dir);
+		let area = (max.x - min.x) * (max.y - min.y);
+		if area < best_area {
+			best_area = area;
+			best = (dir, min, max);
+		}
+	}
+	best
+}
+
+/// Line-list vertices (pairs of endpoints) outlining the given corners, in the order returned by `corners()`.
+pub fn box_edges(corners: &[Vector3<f32>; 8]) -> Vec<Vector3<f32>> {
+	const EDGES: [(usize, usize); 12] = [
+		(0, 1), (2, 3), (4, 5), (6, 7),
+		(0, 2), (1, 3), (4, 6), (5, 7),
+		(0, 4), (1, 5), (2, 6), (3, 7),
+	];
+	EDGES.iter().flat_map(|&(a, b)| [corners[a], corners[b]]).collect()
+}
+
+/// Line-list vertices tracing three great circles of the sphere.
+pub fn sphere_edges(sphere: &BoundingSphere, segments: usize) -> Vec<Vector3<f32>> {
+	let circle = |a: Vector3<f32>, b: Vector3<f32>| {
+		(0..segments).flat_map(move |i| {
+			let t0 = i as f32 / segments as f32 * std::f32::consts::TAU;
+			let t1 = (i + 1) as f32 / segments as f32 * std::f32::consts::TAU;
+			[
+				sphere.center + (a * t0.cos() + b * t0.sin()) * sphere.radius,
+				sphere.center + (a * t1.cos() + b * t1.sin()) * sphere.radius,
+			]
+		})
+	};
+	circle(Vector3::unit_x(), Vector3::unit_y())
+		.chain(circle(Vector3::unit_y(), Vector3::unit_z()))
+		.chain(circle(Vector3::unit_z(), Vector3::unit_x()))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cgmath::{Deg, Rad};
+
+	fn unit_cube() -> Vec<Vector3<f32>> {
+		BoundingBox { min: Vector3::zero(), max: Vector3::new(1.0, 1.0, 1.0) }.corners().to_vec()
+	}
+
+	fn close(a: Vector3<f32>, b: Vector3<f32>) -> bool {
+		(a - b).magnitude() < 1e-4
+	}
+
+	#[test]
+	fn unit_cube_bounds() {
+		let bounds = Bounds::from_points(&unit_cube());
+		assert_eq!(bounds.aabb.min, Vector3::zero());
+		assert_eq!(bounds.aabb.max, Vector3::new(1.0, 1.0, 1.0));
+		assert!(close(bounds.sphere.center, Vector3::new(0.5, 0.5, 0.5)));
+		assert!((bounds.sphere.radius - 3.0_f32.sqrt() / 2.0).abs() < 1e-4);
+		assert!(close(bounds.obb.center, Vector3::new(0.5, 0.5, 0.5)));
+		assert!((bounds.obb.volume() - 1.0).abs() < 1e-3);
+	}
+
+	fn rotated(points: &[Vector3<f32>], m: Matrix4<f32>) -> Vec<Vector3<f32>> {
+		points.iter().map(|p| (m * p.extend(1.0)).truncate()).collect()
+	}
+
+	fn aabb_volume(aabb: &BoundingBox) -> f32 {
+		aabb.size().x * aabb.size().y * aabb.size().z
+	}
+
+	#[test]
+	fn rotated_cube_obb_beats_aabb() {
+		// a cube's covariance is the same in every direction, so its principal axes say nothing
+		// about how it's turned; the calipers still find a box well inside the AABB, if not the
+		// cube itself
+		let points = rotated(&unit_cube(), Matrix4::from_angle_y(Deg(40.0)) * Matrix4::from_angle_x(Deg(25.0)));
+		let bounds = Bounds::from_points(&points);
+		assert!(aabb_volume(&bounds.aabb) > 3.0);
+		assert!(bounds.obb.volume() < aabb_volume(&bounds.aabb) * 0.6);
+		for p in &points {
+			assert!((p - bounds.sphere.center).magnitude() <= bounds.sphere.radius + 1e-4);
+		}
+
+		// turned about a world axis, the calipers find it exactly
+		let points = rotated(&unit_cube(), Matrix4::from_angle_y(Deg(40.0)));
+		let bounds = Bounds::from_points(&points);
+		assert!(aabb_volume(&bounds.aabb) > 1.5);
+		assert!((bounds.obb.volume() - 1.0).abs() < 1e-2);
+	}
+
+	#[test]
+	fn rotated_cuboid_obb_is_tight() {
+		let cuboid = BoundingBox { min: Vector3::zero(), max: Vector3::new(1.0, 2.0, 4.0) }.corners();
+		let points = rotated(&cuboid, Matrix4::from_angle_y(Deg(40.0)) * Matrix4::from_angle_x(Deg(25.0)));
+		let bounds = Bounds::from_points(&points);
+		assert!((bounds.obb.volume() - 8.0).abs() < 0.1);
+		assert!(aabb_volume(&bounds.aabb) > 16.0);
+	}
+
+	#[test]
+	fn sphere_is_the_tighter_one() {
+		// whichever of Ritter's sphere and the box-centered one is smaller, it holds every point
+		let mut points = unit_cube();
+		points.push(Vector3::new(0.5, 0.5, 1.2));
+		let sphere = BoundingSphere::from_points(&points);
+		let ritter = BoundingSphere::ritter(&points);
+		assert!(sphere.radius <= ritter.radius);
+		for p in &points {
+			assert!((p - sphere.center).magnitude() <= sphere.radius + 1e-4);
+		}
+	}
+
+	#[test]
+	fn union_with_empty_is_identity() {
+		let aabb = BoundingBox::from_points(unit_cube());
+		assert_eq!(aabb.union(&BoundingBox::empty()), aabb);
+		assert_eq!(BoundingBox::empty().union(&aabb), aabb);
+		assert!(BoundingBox::empty().union(&BoundingBox::empty()).is_empty());
+	}
+
+	#[test]
+	fn transform_empty_stays_empty() {
+		let m = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0)) * Matrix4::from_scale(2.0);
+		assert!(BoundingBox::empty().transform(&m).is_empty());
+	}
+
+	#[test]
+	fn transform_encloses_transformed_corners() {
+		let aabb = BoundingBox { min: Vector3::new(0.0, 0.0, 0.0), max: Vector3::new(2.0, 1.0, 1.0) };
+		let m = Matrix4::from_translation(Vector3::new(1.0, 0.0, 0.0)) * Matrix4::from_angle_z(Rad(std::f32::consts::FRAC_PI_2));
+		let transformed = aabb.transform(&m);
+		let expected = BoundingBox::from_points(aabb.corners().map(|p| (m * p.extend(1.0)).truncate()));
+		assert!(close(transformed.min, expected.min));
+		assert!(close(transformed.max, expected.max));
+		assert!(close(transformed.min, Vector3::new(0.0, 0.0, 0.0)));
+		assert!(close(transformed.max, Vector3::new(1.0, 2.0, 1.0)));
+	}
+
+	#[test]
+	fn principal_axes_follow_the_spread() {
+		let direction = Vector3::new(1.0, 1.0, 0.0).normalize();
+		let points: Vec<Vector3<f32>> = (0..20)
+			.map(|i| direction * (i as f32 - 10.0) + Vector3::new(0.0, 0.0, (i % 3) as f32 * 0.1))
+			.collect();
+		let axes = principal_axes(&points);
+		assert!(axes.iter().any(|axis| axis.dot(direction).abs() > 0.999));
+		for i in 0..3 {
+			assert!((axes[i].magnitude() - 1.0).abs() < 1e-4);
+			assert!(axes[i].dot(axes[(i + 1) % 3]).abs() < 1e-4);
+		}
+	}
+}
@@ -3,14 +3,19 @@ use winit::{dpi::PhysicalPosition, event::*};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct MatrixUniform {
-    data: [[f32; 4]; 4],
+pub struct CameraUniform {
+    view_proj: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    /// World-space eye position, w = 1.
+    position: [f32; 4],
 }
 
-impl MatrixUniform {
-    pub fn from_matrix4(mat: cgmath::Matrix4<f32>) -> Self {
+impl CameraUniform {
+    pub fn from_camera(camera: &Camera) -> Self {
         Self {
-            data: mat.into()
+            view_proj: camera.view_proj().into(),
+            view: camera.view().into(),
+            position: camera.transform.w.into(),
         }
     }
 }
@@ -45,11 +50,11 @@ impl Camera {
 
 	pub fn new(transform: cgmath::Matrix4<f32>, aspect: f32, fovy: f32, znear: f32, zfar: f32) -> Self {
 		Camera {
-			transform: transform,
-			aspect: aspect,
-			fovy: fovy,
-			znear: znear,
-			zfar: zfar,
+			transform,
+			aspect,
+			fovy,
+			znear,
+			zfar,
 			speed: 0.05,
 			zoom: 2.0,
 
@@ -68,15 +73,20 @@ impl Camera {
 	}
 
 	pub fn view(&self) -> cgmath::Matrix4<f32> {
-        return self.transform.invert().unwrap();
+        self.transform.invert().unwrap()
     }
 
     pub fn proj(&self) -> cgmath::Matrix4<f32> {
-        return cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+        cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar)
+    }
+
+    /// Vertical focal length of the projection, `proj()[1][1]`.
+    pub fn focal(&self) -> f32 {
+        1.0 / (self.fovy.to_radians() / 2.0).tan()
     }
 
     pub fn view_proj(&self) -> cgmath::Matrix4<f32> {
-        return self.proj() * self.view();
+        self.proj() * self.view()
     }
 
 	pub fn input(&mut self, event: &WindowEvent) {
@@ -99,16 +109,11 @@ impl Camera {
 					}
 				}
 			},
-			WindowEvent::MouseInput { state, button, .. } => {
-				match button {
-					MouseButton::Left => {
-						self.dragging = match state {
-							ElementState::Pressed => true,
-							ElementState::Released => false,
-						};
-					},
-					_ => {}
-				}
+			WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+				self.dragging = match state {
+					ElementState::Pressed => true,
+					ElementState::Released => false,
+				};
 			},
 			WindowEvent::MouseWheel { delta, .. } => {
 				match delta {
@@ -149,10 +154,10 @@ impl Camera {
 		let pos = self.transform.w;
 		self.transform.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
 		
-		let yaw = Matrix4::from_angle_y(Deg { 0: -self.delta[0] * Self::LOOK_SENSITIVITY });
+		let yaw = Matrix4::from_angle_y(Deg(-self.delta[0] * Self::LOOK_SENSITIVITY));
 		self.transform = yaw * self.transform;
 		
-		let tilt = Matrix4::from_angle_x(Deg { 0: -self.delta[1] * Self::LOOK_SENSITIVITY });
+		let tilt = Matrix4::from_angle_x(Deg(-self.delta[1] * Self::LOOK_SENSITIVITY));
 		self.transform = self.transform * tilt;
 		
 		self.transform = self.transform * Matrix4::from_translation((0.0, 0.0, self.zoom * self.zoom_factor).into());
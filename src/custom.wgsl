@@ -0,0 +1,34 @@
+// Shading for the custom render mode, and the template for your own: `--fragment <file.wgsl>`
+// replaces this file with one defining the same function. It is compiled in front of
+// shader.wgsl, so everything declared there can be used, and nothing declared here may share a
+// name with it. What's there:
+//
+// - `in: VertexOutput`, the interpolated surface: `world_position`, `normal` (world space, not
+//   normalized), `tex_coords`, `color` (the instance's color) and `clip_position`, whose xy is
+//   the pixel
+// - `camera`: `view_proj`, `view` and `position`, the eye in xyz
+// - `render_state`: `viewport` in pixels and `depth_range`, the scene's nearest and farthest
+//   view depth
+// - `lights`: `ambient`, `count` and the `lights` themselves, each with its `color` in rgb and
+//   intensity in a; `incident_light(i, position, normal)` gives the direction towards light i
+//   in xyz and how much of it arrives, shadows included, in w
+// - `material`: `diffuse` (dissolve in a), `specular` (shininess in w), `emissive` and the rest
+//   of the Material struct, with its textures sampled by `material_sampler`
+// - helpers: `blinn_phong(in)` and `cook_torrance(in)` as the solid and PBR modes light,
+//   `ambient_occlusion(in.clip_position.xy)`, `emission(in)` and `surface_alpha(in)`
+//
+// Section planes, caps, cutouts and back faces are dealt with before `custom` is called. The
+// alpha it returns is blended like a material's dissolve while transparency is on.
+
+// half-Lambert: diffuse light wrapped around to the far side, so nothing goes flat black
+fn custom(in: VertexOutput) -> vec4<f32> {
+	let n = normalize(in.normal);
+	var light = lights.ambient.rgb;
+	for (var i = 0u; i < lights.count; i++) {
+		let incident = incident_light(i, in.world_position, n);
+		let wrapped = dot(n, incident.xyz) * 0.5 + 0.5;
+		light += lights.lights[i].color.rgb * lights.lights[i].color.a * wrapped * wrapped * incident.w;
+	}
+	let base = material.diffuse.rgb * in.color.rgb;
+	return vec4<f32>(base * light * ambient_occlusion(in.clip_position.xy) + emission(in), surface_alpha(in));
+}
@@ -0,0 +1,211 @@
+use wgpu::util::DeviceExt;
+use winit::event::VirtualKeyCode;
+
+use crate::hdr::Hdr;
+use crate::settings::{self, Setting};
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EdlUniform {
+	strength: f32,
+	radius: f32,
+	znear: f32,
+	zfar: f32,
+}
+
+/// Eye-dome lighting post pass. The scene is drawn into `target`, then shaded from the depth
+/// buffer into the HDR target.
+pub struct EyeDomeLighting {
+	pipeline: wgpu::RenderPipeline,
+	bind_group_layout: wgpu::BindGroupLayout,
+	bind_group: wgpu::BindGroup,
+	uniform_buffer: wgpu::Buffer,
+	pub target: Texture,
+	pub enabled: bool,
+	pub strength: f32,
+	pub radius: f32,
+}
+
+impl EyeDomeLighting {
+	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth_texture: &Texture) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("EDL Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("edl.wgsl").into()),
+		});
+
+		let uniform_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("EDL Buffer"),
+				contents: bytemuck::cast_slice(&[EdlUniform { strength: 1.0, radius: 1.0, znear: 0.1, zfar: 100.0 }]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+			],
+			label: Some("edl_bind_group_layout"),
+		});
+
+		let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("EDL Pipeline Layout"),
+			bind_group_layouts: &[&bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("EDL Pipeline"),
+			layout: Some(&pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[Some(wgpu::ColorTargetState {
+					format: Hdr::FORMAT,
+					blend: Some(wgpu::BlendState::REPLACE),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+
+		let target = Texture::new_render_target(device, config, Hdr::FORMAT, "edl_target");
+		let bind_group = Self::create_bind_group(device, &bind_group_layout, &uniform_buffer, &target, depth_texture);
+
+		Self {
+			pipeline,
+			bind_group_layout,
+			bind_group,
+			uniform_buffer,
+			target,
+			enabled: true,
+			strength: 1.0,
+			radius: 1.0,
+		}
+	}
+
+	pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth_texture: &Texture) {
+		self.target = Texture::new_render_target(device, config, Hdr::FORMAT, "edl_target");
+		self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniform_buffer, &self.target, depth_texture);
+	}
+
+	/// K turns eye-dome lighting on and off.
+	pub fn input(&mut self, keycode: VirtualKeyCode) -> Option<String> {
+		if keycode != VirtualKeyCode::K {
+			return None;
+		}
+		self.enabled = !self.enabled;
+		Some(settings::toggled("eye-dome lighting", self.enabled))
+	}
+
+	pub fn settings(&self) -> &'static [Setting] {
+		if self.enabled { &[Setting::EdlStrength, Setting::EdlRadius] } else { &[] }
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::EdlStrength => self.strength = (self.strength * 1.25_f32.powf(steps)).max(0.01),
+			Setting::EdlRadius => self.radius = (self.radius + steps).clamp(1.0, 8.0),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::EdlStrength => Some(format!("{}", self.strength)),
+			Setting::EdlRadius => Some(format!("{} px", self.radius)),
+			_ => None,
+		}
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue, znear: f32, zfar: f32) {
+		let uniform = EdlUniform {
+			strength: self.strength,
+			radius: self.radius,
+			znear,
+			zfar,
+		};
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+	}
+
+	pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("EDL Pass"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: wgpu::StoreOp::Store,
+				},
+			})],
+			depth_stencil_attachment: None,
+			occlusion_query_set: None,
+			timestamp_writes: None,
+		});
+
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_bind_group(0, &self.bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+
+	fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, target: &Texture, depth_texture: &Texture) -> wgpu::BindGroup {
+		device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: uniform_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&target.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(&depth_texture.view),
+				},
+			],
+			label: Some("edl_bind_group"),
+		})
+	}
+}
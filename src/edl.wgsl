@@ -0,0 +1,66 @@
+// Eye-dome lighting: darkens each pixel by how far it sits behind its neighbors in log depth
+struct EdlUniform {
+	strength: f32,
+	radius: f32,
+	znear: f32,
+	zfar: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> edl: EdlUniform;
+
+@group(0) @binding(1)
+var color_texture: texture_2d<f32>;
+
+// bound as unfilterable float so it can be loaded like a color texture on every backend
+@group(0) @binding(2)
+var depth_texture: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // one triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+fn log_depth(coords: vec2<i32>) -> f32 {
+	let size = vec2<i32>(textureDimensions(depth_texture));
+	let d = textureLoad(depth_texture, clamp(coords, vec2<i32>(0), size - 1), 0).r;
+	if d >= 1.0 {
+		return -1.0; // background
+	}
+	// the camera's projection is GL-style, so the stored depth is NDC z in [-1, 1], of which only the
+	// far half survives clipping
+	let linear = 2.0 * edl.znear * edl.zfar / (edl.zfar + edl.znear - d * (edl.zfar - edl.znear));
+	return log2(linear);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let coords = vec2<i32>(in.clip_position.xy);
+	let color = textureLoad(color_texture, coords, 0);
+	let depth = log_depth(coords);
+	if depth < 0.0 {
+		return color;
+	}
+
+	var response = 0.0;
+	for (var i = 0; i < 8; i++) {
+		let angle = f32(i) * 0.785398;
+		let offset = vec2<i32>(round(vec2<f32>(cos(angle), sin(angle)) * edl.radius));
+		let neighbor = log_depth(coords + offset);
+		if neighbor >= 0.0 {
+			response += max(0.0, depth - neighbor);
+		}
+	}
+	let shade = exp(-response / 8.0 * 300.0 * edl.strength);
+
+	return vec4<f32>(color.rgb * shade, color.a);
+}
@@ -0,0 +1,476 @@
+use std::error::Error;
+
+use cgmath::{Deg, Matrix4, Rad, SquareMatrix};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::settings::Setting;
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct EnvironmentUniform {
+	inv_view_proj: [[f32; 4]; 4],
+	rotation: [f32; 2],
+	intensity: f32,
+	specular_mips: f32,
+	enabled: u32,
+	_padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeUniform {
+	face: u32,
+	size: f32,
+	roughness: f32,
+	source_size: f32,
+}
+
+/// Image-based lighting from an equirectangular `.hdr` or `.exr` image. At load the image is
+/// turned into a mipmapped cube, a GGX-prefiltered specular cube with roughness rising
+/// along its mips, and a diffuse irradiance cube. The cube is also drawn as the sky.
+/// Without an image the maps are black and `enabled` stays false.
+pub struct Environment {
+	sky_shader: wgpu::ShaderModule,
+	sky_layout: wgpu::PipelineLayout,
+	sky_pipeline: wgpu::RenderPipeline,
+	sky_bind_group: wgpu::BindGroup,
+	format: wgpu::TextureFormat,
+	pub buffer: wgpu::Buffer,
+	#[allow(dead_code)] // sampled through sky_bind_group
+	cube: Texture,
+	pub specular: Texture,
+	pub irradiance: Texture,
+	pub enabled: bool,
+	/// Turn about the vertical axis.
+	pub rotation: Deg<f32>,
+	/// In stops, applied to the sky and the lighting alike.
+	pub exposure: f32,
+}
+
+impl Environment {
+	const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+	const SPECULAR_SIZE: u32 = 128;
+	const SPECULAR_MIPS: u32 = 6;
+	const IRRADIANCE_SIZE: u32 = 32;
+
+	pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat, sample_count: u32, path: Option<&str>) -> Result<Self, Box<dyn Error>> {
+		let (cube, specular, irradiance) = match path {
+			Some(path) => Self::bake(device, queue, &Self::load(device, path)?),
+			None => (
+				Texture::new_cube(device, 1, 1, Self::FORMAT, "environment_cube"),
+				Texture::new_cube(device, 1, 1, Self::FORMAT, "environment_specular"),
+				Texture::new_cube(device, 1, 1, Self::FORMAT, "environment_irradiance"),
+			),
+		};
+
+		let buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Environment Buffer"),
+				contents: bytemuck::cast_slice(&[EnvironmentUniform {
+					inv_view_proj: Matrix4::identity().into(),
+					rotation: [1.0, 0.0],
+					intensity: 1.0,
+					specular_mips: Self::SPECULAR_MIPS as f32,
+					enabled: 0,
+					_padding: [0; 3],
+				}]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let sky_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Skybox Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("skybox.wgsl").into()),
+		});
+
+		let sky_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::Cube,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				}
+			],
+			label: Some("skybox_bind_group_layout"),
+		});
+
+		let sky_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &sky_bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&cube.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::Sampler(&cube.sampler),
+				}
+			],
+			label: Some("skybox_bind_group"),
+		});
+
+		let sky_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Skybox Pipeline Layout"),
+			bind_group_layouts: &[&sky_bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let sky_pipeline = Self::build_sky(device, &sky_shader, &sky_layout, format, sample_count);
+
+		Ok(Self {
+			sky_shader,
+			sky_layout,
+			sky_pipeline,
+			sky_bind_group,
+			format,
+			buffer,
+			cube,
+			specular,
+			irradiance,
+			enabled: path.is_some(),
+			rotation: Deg(0.0),
+			exposure: 0.0,
+		})
+	}
+
+	pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+		self.sky_pipeline = Self::build_sky(device, &self.sky_shader, &self.sky_layout, self.format, sample_count);
+	}
+
+	/// Rotation and exposure, once there is an image to turn and light with.
+	pub fn settings(&self) -> &'static [Setting] {
+		if self.enabled { &[Setting::EnvironmentRotation, Setting::EnvironmentExposure] } else { &[] }
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::EnvironmentRotation => self.rotation = Deg((self.rotation.0 + steps * 15.0).rem_euclid(360.0)),
+			Setting::EnvironmentExposure => self.exposure = (self.exposure + steps * 0.5).clamp(-10.0, 10.0),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::EnvironmentRotation => Some(format!("{}°", self.rotation.0)),
+			Setting::EnvironmentExposure => Some(format!("{} EV", self.exposure)),
+			_ => None,
+		}
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+		let angle: Rad<f32> = self.rotation.into();
+		let uniform = EnvironmentUniform {
+			inv_view_proj: camera.view_proj().invert().unwrap_or(Matrix4::identity()).into(),
+			rotation: [angle.0.cos(), angle.0.sin()],
+			intensity: 2.0_f32.powf(self.exposure),
+			specular_mips: Self::SPECULAR_MIPS as f32,
+			enabled: self.enabled as u32,
+			_padding: [0; 3],
+		};
+		queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+	}
+
+	/// Draws the sky wherever the depth buffer is still clear.
+	pub fn draw_sky<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		if !self.enabled {
+			return;
+		}
+		render_pass.set_pipeline(&self.sky_pipeline);
+		render_pass.set_bind_group(0, &self.sky_bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+
+	/// Reads the image as 32 bit float RGBA, scaled down if it's wider than the device allows.
+	fn load(device: &wgpu::Device, path: &str) -> Result<image::Rgba32FImage, Box<dyn Error>> {
+		let image = image::open(path)?.into_rgba32f();
+		let max = device.limits().max_texture_dimension_2d;
+		if image.width() <= max && image.height() <= max {
+			return Ok(image);
+		}
+		let scale = max as f32 / image.width().max(image.height()) as f32;
+		let (width, height) = ((image.width() as f32 * scale) as u32, (image.height() as f32 * scale) as u32);
+		Ok(image::imageops::resize(&image, width.max(1), height.max(1), image::imageops::FilterType::Triangle))
+	}
+
+	fn bake(device: &wgpu::Device, queue: &wgpu::Queue, image: &image::Rgba32FImage) -> (Texture, Texture, Texture) {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Environment Bake Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("environment.wgsl").into()),
+		});
+
+		let size = wgpu::Extent3d {
+			width: image.width(),
+			height: image.height(),
+			depth_or_array_layers: 1,
+		};
+		let equirect = device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("environment_equirect"),
+			size,
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: wgpu::TextureFormat::Rgba32Float,
+			usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			view_formats: &[],
+		});
+		queue.write_texture(
+			wgpu::ImageCopyTexture {
+				aspect: wgpu::TextureAspect::All,
+				texture: &equirect,
+				mip_level: 0,
+				origin: wgpu::Origin3d::ZERO,
+			},
+			bytemuck::cast_slice(image.as_raw()),
+			wgpu::ImageDataLayout {
+				offset: 0,
+				bytes_per_row: Some(16 * image.width()),
+				rows_per_image: Some(image.height()),
+			},
+			size,
+		);
+		let equirect_view = equirect.create_view(&wgpu::TextureViewDescriptor::default());
+
+		// a face a quarter of the image's width keeps about its resolution at the equator
+		let cube_size = (image.width() / 4).next_power_of_two().clamp(16, 1024);
+		let cube_mips = cube_size.ilog2() + 1;
+		let cube = Texture::new_cube(device, cube_size, cube_mips, Self::FORMAT, "environment_cube");
+		let specular = Texture::new_cube(device, Self::SPECULAR_SIZE, Self::SPECULAR_MIPS, Self::FORMAT, "environment_specular");
+		let irradiance = Texture::new_cube(device, Self::IRRADIANCE_SIZE, 1, Self::FORMAT, "environment_irradiance");
+
+		let uniform = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Uniform,
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		// the equirect pass loads from a 2D texture, the filtering passes sample the cube
+		let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				uniform(0),
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				}
+			],
+			label: Some("environment_source_bind_group_layout"),
+		});
+		let cube_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				uniform(0),
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::Cube,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				}
+			],
+			label: Some("environment_cube_bind_group_layout"),
+		});
+
+		let pipeline = |layout: &wgpu::BindGroupLayout, entry_point| {
+			let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+				label: Some("Environment Bake Pipeline Layout"),
+				bind_group_layouts: &[layout],
+				push_constant_ranges: &[],
+			});
+			device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+				label: Some("Environment Bake Pipeline"),
+				layout: Some(&pipeline_layout),
+				vertex: wgpu::VertexState {
+					module: &shader,
+					entry_point: "vs_main",
+					buffers: &[],
+				},
+				fragment: Some(wgpu::FragmentState {
+					module: &shader,
+					entry_point,
+					targets: &[Some(Self::FORMAT.into())],
+				}),
+				primitive: wgpu::PrimitiveState::default(),
+				depth_stencil: None,
+				multisample: wgpu::MultisampleState::default(),
+				multiview: None,
+			})
+		};
+		let equirect_pipeline = pipeline(&source_layout, "fs_equirect");
+		let prefilter_pipeline = pipeline(&cube_layout, "fs_prefilter");
+		let irradiance_pipeline = pipeline(&cube_layout, "fs_irradiance");
+
+		let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+			label: Some("Environment Bake Encoder"),
+		});
+
+		// every pass draws one face of one mip, with its own small uniform
+		let mut pass = |target: &Texture, face: u32, mip: u32, roughness: f32, pipeline: &wgpu::RenderPipeline, layout: &wgpu::BindGroupLayout, input: wgpu::BindGroupEntry| {
+			let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: Some("Environment Bake Buffer"),
+				contents: bytemuck::cast_slice(&[BakeUniform {
+					face,
+					size: (target.texture.width() >> mip).max(1) as f32,
+					roughness,
+					source_size: cube_size as f32,
+				}]),
+				usage: wgpu::BufferUsages::UNIFORM,
+			});
+			let mut entries = vec![
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: buffer.as_entire_binding(),
+				},
+				input,
+			];
+			if entries[1].binding == 2 {
+				entries.push(wgpu::BindGroupEntry {
+					binding: 3,
+					resource: wgpu::BindingResource::Sampler(&cube.sampler),
+				});
+			}
+			let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+				layout,
+				entries: &entries,
+				label: Some("environment_bake_bind_group"),
+			});
+
+			let view = Self::face_view(target, face, mip);
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("Environment Bake Pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: &view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+						store: wgpu::StoreOp::Store,
+					},
+				})],
+				depth_stencil_attachment: None,
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+			render_pass.set_pipeline(pipeline);
+			render_pass.set_bind_group(0, &bind_group, &[]);
+			render_pass.draw(0..3, 0..1);
+		};
+
+		for face in 0..6 {
+			for mip in 0..cube_mips {
+				let input = wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&equirect_view),
+				};
+				pass(&cube, face, mip, 0.0, &equirect_pipeline, &source_layout, input);
+			}
+		}
+
+		for face in 0..6 {
+			for mip in 0..Self::SPECULAR_MIPS {
+				let roughness = mip as f32 / (Self::SPECULAR_MIPS - 1) as f32;
+				let input = wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(&cube.view),
+				};
+				pass(&specular, face, mip, roughness, &prefilter_pipeline, &cube_layout, input);
+			}
+			let input = wgpu::BindGroupEntry {
+				binding: 2,
+				resource: wgpu::BindingResource::TextureView(&cube.view),
+			};
+			pass(&irradiance, face, 0, 1.0, &irradiance_pipeline, &cube_layout, input);
+		}
+
+		queue.submit(std::iter::once(encoder.finish()));
+		(cube, specular, irradiance)
+	}
+
+	fn face_view(texture: &Texture, face: u32, mip: u32) -> wgpu::TextureView {
+		texture.texture.create_view(&wgpu::TextureViewDescriptor {
+			dimension: Some(wgpu::TextureViewDimension::D2),
+			base_mip_level: mip,
+			mip_level_count: Some(1),
+			base_array_layer: face,
+			array_layer_count: Some(1),
+			..Default::default()
+		})
+	}
+
+	fn build_sky(device: &wgpu::Device, shader: &wgpu::ShaderModule, layout: &wgpu::PipelineLayout, format: wgpu::TextureFormat, sample_count: u32) -> wgpu::RenderPipeline {
+		device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Skybox Pipeline"),
+			layout: Some(layout),
+			vertex: wgpu::VertexState {
+				module: shader,
+				entry_point: "vs_main",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: shader,
+				entry_point: "fs_main",
+				targets: &[Some(format.into())],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			// drawn on the far plane, so only where nothing else was
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState {
+				count: sample_count,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		})
+	}
+}
@@ -0,0 +1,148 @@
+// Bakes an equirectangular environment into a mipmapped cube, then prefilters it for specular
+// and diffuse image-based lighting. Each pass renders one face of one mip.
+struct Bake {
+	face: u32,
+	// face size of the target mip in pixels
+	size: f32,
+	roughness: f32,
+	// face size of the environment cube's top mip
+	source_size: f32,
+}
+
+const PI: f32 = 3.14159265;
+const SAMPLES: u32 = 64u;
+
+@group(0) @binding(0)
+var<uniform> bake: Bake;
+
+// the equirectangular image
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+@group(0) @binding(2)
+var environment: texture_cube<f32>;
+
+@group(0) @binding(3)
+var environment_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // one triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// direction through pixel `position` of face `bake.face`, following the cube map face layout
+fn face_direction(position: vec2<f32>) -> vec3<f32> {
+	let st = position / bake.size * 2.0 - 1.0;
+	let s = st.x;
+	let t = st.y;
+	switch bake.face {
+		case 0u { return normalize(vec3<f32>(1.0, -t, -s)); }
+		case 1u { return normalize(vec3<f32>(-1.0, -t, s)); }
+		case 2u { return normalize(vec3<f32>(s, 1.0, t)); }
+		case 3u { return normalize(vec3<f32>(s, -1.0, -t)); }
+		case 4u { return normalize(vec3<f32>(s, -t, 1.0)); }
+		default { return normalize(vec3<f32>(-s, -t, -1.0)); }
+	}
+}
+
+// 32 bit float textures can't be filtered, so blend the four nearest texels by hand
+fn load_bilinear(uv: vec2<f32>) -> vec3<f32> {
+	let size = vec2<i32>(textureDimensions(source));
+	let p = uv * vec2<f32>(size) - 0.5;
+	let base = vec2<i32>(floor(p));
+	let f = fract(p);
+	var texels: array<vec3<f32>, 4>;
+	for (var i = 0; i < 4; i++) {
+		let offset = vec2<i32>(i & 1, i >> 1u);
+		// wrap around horizontally, clamp at the poles
+		let x = ((base.x + offset.x) % size.x + size.x) % size.x;
+		let y = clamp(base.y + offset.y, 0, size.y - 1);
+		texels[i] = textureLoad(source, vec2<i32>(x, y), 0).rgb;
+	}
+	return mix(mix(texels[0], texels[1], f.x), mix(texels[2], texels[3], f.x), f.y);
+}
+
+// lower mips average a grid of samples over the texel's footprint at the top mip; downsampling
+// the previous mip instead would need binding a single mip, which the GL backend can't
+@fragment
+fn fs_equirect(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let n = u32(clamp(bake.source_size / bake.size, 1.0, 8.0));
+	var sum = vec3<f32>(0.0);
+	for (var i = 0u; i < n * n; i++) {
+		let offset = (vec2<f32>(f32(i % n), f32(i / n)) + 0.5) / f32(n) - 0.5;
+		let d = face_direction(position.xy + offset);
+		let uv = vec2<f32>(atan2(d.z, d.x) / (2.0 * PI) + 0.5, acos(clamp(d.y, -1.0, 1.0)) / PI);
+		sum += load_bilinear(uv);
+	}
+	return vec4<f32>(sum / f32(n * n), 1.0);
+}
+
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+	return vec2<f32>(f32(i) / f32(n), f32(reverseBits(i)) * 2.3283064e-10);
+}
+
+// orthonormal basis around n, for turning tangent-space samples into world directions
+fn basis(n: vec3<f32>) -> mat3x3<f32> {
+	let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(n.y) < 0.999);
+	let t = normalize(cross(up, n));
+	return mat3x3<f32>(t, cross(n, t), n);
+}
+
+// mip of the environment whose texels cover about the solid angle of one sample with density `pdf`,
+// which keeps bright spots from turning into fireflies with few samples
+fn sample_lod(pdf: f32) -> f32 {
+	let sample_angle = 1.0 / (f32(SAMPLES) * max(pdf, 1e-6));
+	let texel_angle = 4.0 * PI / (6.0 * bake.source_size * bake.source_size);
+	return max(0.5 * log2(sample_angle / texel_angle) + 1.0, 0.0);
+}
+
+// GGX importance sampling with the view direction taken to be the normal
+@fragment
+fn fs_prefilter(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let n = face_direction(position.xy);
+	if bake.roughness <= 0.0 {
+		return vec4<f32>(textureSampleLevel(environment, environment_sampler, n, 0.0).rgb, 1.0);
+	}
+
+	let frame = basis(n);
+	let alpha = bake.roughness * bake.roughness;
+	var sum = vec3<f32>(0.0);
+	var weight = 0.0;
+	for (var i = 0u; i < SAMPLES; i++) {
+		let xi = hammersley(i, SAMPLES);
+		let phi = 2.0 * PI * xi.x;
+		let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+		let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+		let h = frame * vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+		let l = reflect(-n, h);
+		let n_dot_l = dot(n, l);
+		if n_dot_l <= 0.0 {
+			continue;
+		}
+
+		let a2 = alpha * alpha;
+		let d = cos_theta * cos_theta * (a2 - 1.0) + 1.0;
+		let pdf = a2 / (PI * d * d) * 0.25;
+		sum += textureSampleLevel(environment, environment_sampler, l, sample_lod(pdf)).rgb * n_dot_l;
+		weight += n_dot_l;
+	}
+	return vec4<f32>(sum / max(weight, 1e-4), 1.0);
+}
+
+// cosine-weighted average of the radiance over the hemisphere, the irradiance divided by pi
+@fragment
+fn fs_irradiance(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let frame = basis(face_direction(position.xy));
+	var sum = vec3<f32>(0.0);
+	for (var i = 0u; i < SAMPLES; i++) {
+		let xi = hammersley(i, SAMPLES);
+		let phi = 2.0 * PI * xi.x;
+		let cos_theta = sqrt(1.0 - xi.y);
+		let sin_theta = sqrt(xi.y);
+		let l = frame * vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+		sum += textureSampleLevel(environment, environment_sampler, l, sample_lod(cos_theta / PI)).rgb;
+	}
+	return vec4<f32>(sum / f32(SAMPLES), 1.0);
+}
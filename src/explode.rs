@@ -0,0 +1,68 @@
+use crate::settings::Setting;
+
+/// Exploded view setting, as a multiple of each object's distance from the model's center, and
+/// how far the animation towards it has got.
+#[derive(Default)]
+pub struct ExplodedView {
+	amount: f32,
+	shown: f32,
+}
+
+impl ExplodedView {
+	pub fn settings(&self) -> &'static [Setting] {
+		&[Setting::Explode]
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		if setting != Setting::Explode {
+			return false;
+		}
+		self.amount = (self.amount + steps * 0.1).clamp(0.0, 2.0);
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::Explode if self.amount <= 0.0 => Some("off".into()),
+			Setting::Explode => Some(format!("{:.1}", self.amount)),
+			_ => None,
+		}
+	}
+
+	/// Eases what is shown towards the setting, so objects glide out and back in. Returns the
+	/// amount to show while it changes.
+	pub fn animate(&mut self) -> Option<f32> {
+		if self.shown == self.amount {
+			return None;
+		}
+		let step = (self.amount - self.shown) * 0.15;
+		self.shown = if step.abs() < 1e-3 { self.amount } else { self.shown + step };
+		Some(self.shown)
+	}
+
+	/// Whether the animation has reached the setting.
+	pub fn settled(&self) -> bool {
+		self.shown == self.amount
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn glides_to_the_setting_and_stops() {
+		let mut explode = ExplodedView::default();
+		assert_eq!(explode.animate(), None);
+		explode.adjust(Setting::Explode, 5.0);
+		let first = explode.animate().unwrap();
+		assert!(first > 0.0 && first < 0.5);
+		let mut frames = 1;
+		while explode.animate().is_some() {
+			frames += 1;
+		}
+		assert!(explode.settled());
+		assert!(frames < 60);
+		assert_eq!(explode.value(Setting::Explode).as_deref(), Some("0.5"));
+	}
+}
@@ -0,0 +1,421 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+use winit::event::VirtualKeyCode;
+
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::model::{Draw, Mesh, Submesh, Vertex};
+use crate::settings::{self, Setting};
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FeatureUniform {
+	viewport: [f32; 2],
+	width: f32,
+	/// Cosine of the crease angle.
+	crease: f32,
+}
+
+/// Marks an edge with a single triangle, or more than two.
+const NONE: u32 = u32::MAX;
+
+/// CAD-style lines along the mesh's creases, its open boundaries and its silhouette, drawn as
+/// screen-space quads so they can be thicker than a pixel. Which edges are which is worked out
+/// in the vertex shader from the triangles on either side, so silhouettes follow the camera and
+/// lines follow the exploded view's submesh offsets.
+pub struct FeatureLines {
+	shader: wgpu::ShaderModule,
+	layout: wgpu::PipelineLayout,
+	format: wgpu::TextureFormat,
+	pipeline: wgpu::RenderPipeline,
+	bind_group: wgpu::BindGroup,
+	buffer: wgpu::Buffer,
+	/// Each submesh's edges.
+	ranges: Vec<Range<u32>>,
+	pub enabled: bool,
+	/// Dihedral angle in degrees beyond which an edge is a crease.
+	pub crease_angle: f32,
+	/// In pixels.
+	pub width: f32,
+}
+
+impl FeatureLines {
+	pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, camera_layout: &wgpu::BindGroupLayout, geometry_layout: &wgpu::BindGroupLayout, model: &Mesh) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Feature Line Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("feature_lines.wgsl").into()),
+		});
+
+		// written every frame by `update`
+		let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Feature Line Buffer"),
+			size: std::mem::size_of::<FeatureUniform>() as wgpu::BufferAddress,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let (edges, ranges) = edges(&model.vertices, &model.indices, &model.submeshes);
+		let edge_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Feature Edge Buffer"),
+				// storage bindings can't be empty
+				contents: if edges.is_empty() { &[0; 16] } else { bytemuck::cast_slice(&edges) },
+				usage: wgpu::BufferUsages::STORAGE,
+			}
+		);
+
+		let storage = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::VERTEX,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: true },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				storage(1),
+				storage(2),
+			],
+			label: Some("feature_line_bind_group_layout"),
+		});
+
+		let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: model.vertex_buffer().as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: edge_buffer.as_entire_binding(),
+				}
+			],
+			label: Some("feature_line_bind_group"),
+		});
+
+		let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Feature Line Pipeline Layout"),
+			bind_group_layouts: &[camera_layout, &bind_group_layout, geometry_layout],
+			push_constant_ranges: &[],
+		});
+
+		let pipeline = Self::build(device, &shader, &layout, format, sample_count);
+
+		Self {
+			shader,
+			layout,
+			format,
+			pipeline,
+			bind_group,
+			buffer,
+			ranges,
+			enabled: false,
+			crease_angle: 30.0,
+			width: 3.0,
+		}
+	}
+
+	pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+		self.pipeline = Self::build(device, &self.shader, &self.layout, self.format, sample_count);
+	}
+
+	/// Z turns feature lines on and off.
+	pub fn input(&mut self, keycode: VirtualKeyCode) -> Option<String> {
+		if keycode != VirtualKeyCode::Z {
+			return None;
+		}
+		self.enabled = !self.enabled;
+		Some(settings::toggled("feature lines", self.enabled))
+	}
+
+	pub fn settings(&self) -> &'static [Setting] {
+		if self.enabled { &[Setting::CreaseAngle, Setting::FeatureLineWidth] } else { &[] }
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::CreaseAngle => self.crease_angle = (self.crease_angle + steps * 5.0).clamp(5.0, 180.0),
+			Setting::FeatureLineWidth => self.width = (self.width + steps * 0.5).clamp(1.0, 10.0),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::CreaseAngle if self.crease_angle >= 180.0 => Some("off".into()),
+			Setting::CreaseAngle => Some(format!("{}°", self.crease_angle)),
+			Setting::FeatureLineWidth => Some(format!("{} px", self.width)),
+			_ => None,
+		}
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue, viewport: [f32; 2]) {
+		let uniform = FeatureUniform {
+			viewport,
+			width: self.width,
+			crease: self.crease_angle.to_radians().cos(),
+		};
+		queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+	}
+
+	/// Draws the edges of `draws`, six vertices each; the shader collapses the ones that aren't
+	/// features.
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, camera_bind_group: &'a wgpu::BindGroup, model: &'a Mesh, instances: &'a InstanceBuffer, draws: &[Draw]) {
+		if !self.enabled || instances.is_empty() {
+			return;
+		}
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_bind_group(0, camera_bind_group, &[]);
+		render_pass.set_bind_group(1, &self.bind_group, &[]);
+		render_pass.set_vertex_buffer(0, instances.slice());
+		for draw in draws {
+			let edges = &self.ranges[draw.submesh];
+			if !edges.is_empty() {
+				model.bind_submesh(render_pass, 2, draw.submesh);
+				render_pass.draw(edges.start * 6..edges.end * 6, draw.instances.clone());
+			}
+		}
+	}
+
+	fn build(device: &wgpu::Device, shader: &wgpu::ShaderModule, layout: &wgpu::PipelineLayout, format: wgpu::TextureFormat, sample_count: u32) -> wgpu::RenderPipeline {
+		device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Feature Line Pipeline"),
+			layout: Some(layout),
+			vertex: wgpu::VertexState {
+				module: shader,
+				entry_point: "vs_main",
+				buffers: &[InstanceRaw::desc()],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: shader,
+				entry_point: "fs_main",
+				targets: &[Some(wgpu::ColorTargetState {
+					format,
+					blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState {
+				cull_mode: None,
+				..Default::default()
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState {
+				count: sample_count,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+		})
+	}
+}
+
+/// Every edge of the submeshes' triangles once, as `[a, b, c0, c1]`: the edge from `a` to `b` as
+/// the first triangle has it, the corner across from it in that triangle and in the second, or
+/// `NONE` when there isn't exactly one other. Triangles without area are left out. Loading splits vertices wherever their normals or
+/// texture coordinates differ, so they're welded by position within each submesh to find which
+/// triangles share an edge. Each submesh's edges are contiguous, with their range alongside.
+fn edges(vertices: &[Vertex], indices: &[u32], submeshes: &[Submesh]) -> (Vec<[u32; 4]>, Vec<Range<u32>>) {
+	let mut edges: Vec<[u32; 4]> = vec![];
+	let mut ranges = vec![];
+	for submesh in submeshes {
+		let start = edges.len() as u32;
+		let mut first = HashMap::new();
+		let welded: Vec<u32> = (submesh.vertices.start..submesh.vertices.end)
+			// adding zero turns -0 into 0, which compares equal but has other bits
+			.map(|i| *first.entry(vertices[i as usize].position.map(|c| (c + 0.0).to_bits())).or_insert(i))
+			.collect();
+		let weld = |i: u32| welded[(i - submesh.vertices.start) as usize];
+
+		// index into `edges` and how many triangles have been found on it
+		let mut shared: HashMap<(u32, u32), (usize, u32)> = HashMap::new();
+		let triangles = &indices[submesh.indices.start as usize..submesh.indices.end as usize];
+		for triangle in triangles.chunks_exact(3) {
+			// triangles with two corners in one place, like the fans of quads at a sphere's poles,
+			// have no area and would only come between the faces that do
+			let [w0, w1, w2] = [0, 1, 2].map(|k| weld(triangle[k]));
+			if w0 == w1 || w1 == w2 || w2 == w0 {
+				continue;
+			}
+			for k in 0..3 {
+				let (a, b, c) = (triangle[k], triangle[(k + 1) % 3], triangle[(k + 2) % 3]);
+				let (wa, wb) = (weld(a), weld(b));
+				match shared.entry((wa.min(wb), wa.max(wb))) {
+					Entry::Vacant(entry) => {
+						entry.insert((edges.len(), 1));
+						edges.push([a, b, c, NONE]);
+					}
+					Entry::Occupied(mut entry) => {
+						let (edge, triangles) = entry.get_mut();
+						*triangles += 1;
+						edges[*edge][3] = if *triangles == 2 { c } else { NONE };
+					}
+				}
+			}
+		}
+		ranges.push(start..edges.len() as u32);
+	}
+	(edges, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{InnerSpace, Vector3, Zero};
+
+	use super::*;
+	use crate::bounds::Bounds;
+
+	/// Vertices and triangles for `faces`, each polygon with its own copies of its corners the way
+	/// a flat-shaded OBJ loads, fanned into triangles.
+	fn flat(positions: &[[f32; 3]], faces: &[&[usize]]) -> (Vec<Vertex>, Vec<u32>) {
+		let (mut vertices, mut indices) = (vec![], vec![]);
+		for face in faces {
+			let base = vertices.len() as u32;
+			vertices.extend(face.iter().map(|&i| Vertex { position: positions[i], normal: [0.0; 3], tex_coords: [0.0; 2] }));
+			for k in 1..face.len() as u32 - 1 {
+				indices.extend([base, base + k, base + k + 1]);
+			}
+		}
+		(vertices, indices)
+	}
+
+	fn submesh(vertices: Range<u32>, indices: Range<u32>) -> Submesh {
+		Submesh {
+			name: String::new(),
+			indices,
+			bounds: Bounds::from_points(&[]),
+			material: 0,
+			vertices,
+			offset: Vector3::zero(),
+		}
+	}
+
+	fn whole(vertices: &[Vertex], indices: &[u32]) -> Vec<[u32; 4]> {
+		edges(vertices, indices, &[submesh(0..vertices.len() as u32, 0..indices.len() as u32)]).0
+	}
+
+	/// The shader's crease test at `degrees`.
+	fn is_crease(vertices: &[Vertex], edge: &[u32; 4], degrees: f32) -> bool {
+		let p = |i: u32| Vector3::from(vertices[i as usize].position);
+		let (a, b) = (p(edge[0]), p(edge[1]));
+		let n0 = (b - a).cross(p(edge[2]) - a);
+		let n1 = (a - b).cross(p(edge[3]) - b);
+		n0.dot(n1) < degrees.to_radians().cos() * n0.magnitude() * n1.magnitude()
+	}
+
+	fn count(vertices: &[Vertex], edges: &[[u32; 4]]) -> (usize, usize) {
+		let boundaries = edges.iter().filter(|e| e[3] == NONE).count();
+		let creases = edges.iter().filter(|e| e[3] != NONE && is_crease(vertices, e, 30.0)).count();
+		(creases, boundaries)
+	}
+
+	const CUBE: [[f32; 3]; 8] = [
+		[-1.0, -1.0, -1.0], [1.0, -1.0, -1.0], [1.0, 1.0, -1.0], [-1.0, 1.0, -1.0],
+		[-1.0, -1.0, 1.0], [1.0, -1.0, 1.0], [1.0, 1.0, 1.0], [-1.0, 1.0, 1.0],
+	];
+	const CUBE_FACES: [&[usize]; 6] = [&[0, 3, 2, 1], &[4, 5, 6, 7], &[0, 1, 5, 4], &[3, 7, 6, 2], &[0, 4, 7, 3], &[1, 2, 6, 5]];
+
+	#[test]
+	fn cube_has_twelve_creases() {
+		let (vertices, indices) = flat(&CUBE, &CUBE_FACES);
+		let edges = whole(&vertices, &indices);
+		// the twelve sides and a diagonal per face, each found once despite the split corners
+		assert_eq!(edges.len(), 18);
+		assert_eq!(count(&vertices, &edges), (12, 0));
+	}
+
+	#[test]
+	fn open_quad_has_four_boundaries() {
+		let (vertices, indices) = flat(&CUBE[..4], &[&[0, 1, 2, 3]]);
+		let edges = whole(&vertices, &indices);
+		assert_eq!(edges.len(), 5);
+		assert_eq!(count(&vertices, &edges), (0, 4));
+	}
+
+	#[test]
+	fn smooth_sphere_has_no_creases() {
+		let (rings, segments) = (12, 24);
+		let point = |ring: usize, segment: usize| {
+			let (lat, lon) = (std::f32::consts::PI * ring as f32 / rings as f32, std::f32::consts::TAU * (segment % segments) as f32 / segments as f32);
+			match ring {
+				0 => [0.0, 1.0, 0.0],
+				_ if ring == rings => [0.0, -1.0, 0.0],
+				_ => [lat.sin() * lon.cos(), lat.cos(), lat.sin() * lon.sin()],
+			}
+		};
+		let positions: Vec<[f32; 3]> = (0..=rings).flat_map(|r| (0..=segments).map(move |s| point(r, s))).collect();
+		let at = |r: usize, s: usize| r * (segments + 1) + s;
+		let quads: Vec<[usize; 4]> = (0..rings).flat_map(|r| (0..segments).map(move |s| [at(r, s), at(r, s + 1), at(r + 1, s + 1), at(r + 1, s)])).collect();
+		let faces: Vec<&[usize]> = quads.iter().map(|q| &q[..]).collect();
+		let (vertices, indices) = flat(&positions, &faces);
+		let edges = whole(&vertices, &indices);
+		assert_eq!(count(&vertices, &edges), (0, 0));
+		// but a tighter angle picks out the facets
+		assert!(edges.iter().any(|e| e[3] != NONE && is_crease(&vertices, e, 5.0)));
+	}
+
+	#[test]
+	fn welds_by_position() {
+		// two triangles that share an edge only by position, one of its ends written as -0
+		let vertices: Vec<Vertex> = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [-0.0, 0.0, 0.0], [0.0, -1.0, 0.0]]
+			.map(|position| Vertex { position, normal: [0.0; 3], tex_coords: [0.0; 2] })
+			.to_vec();
+		let edges = whole(&vertices, &[0, 1, 2, 3, 4, 5]);
+		assert_eq!(edges.len(), 5);
+		let shared: Vec<&[u32; 4]> = edges.iter().filter(|e| e[3] != NONE).collect();
+		assert_eq!(shared, vec![&[0, 1, 2, 5]]);
+	}
+
+	#[test]
+	fn non_manifold_and_degenerate_edges() {
+		// three triangles on one edge have no pair to compare, and a triangle with a collapsed edge
+		// adds nothing
+		let (vertices, indices) = flat(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0], [0.0, 0.0, 1.0]], &[&[0, 1, 2], &[1, 0, 3], &[0, 1, 4], &[2, 2, 4]]);
+		let edges = whole(&vertices, &indices);
+		let spine: Vec<&[u32; 4]> = edges.iter().filter(|e| [e[0], e[1]] == [0, 1]).collect();
+		assert_eq!(spine, vec![&[0, 1, 2, NONE]]);
+		assert_eq!(edges.len(), 1 + 2 * 3);
+	}
+
+	#[test]
+	fn submeshes_keep_their_own_edges() {
+		// the same quad twice, in two submeshes that aren't welded together
+		let (mut vertices, mut indices) = flat(&CUBE[..4], &[&[0, 1, 2, 3]]);
+		let (more, more_indices) = flat(&CUBE[..4], &[&[0, 1, 2, 3]]);
+		indices.extend(more_indices.iter().map(|i| i + vertices.len() as u32));
+		vertices.extend(more);
+		let submeshes = [submesh(0..4, 0..6), submesh(4..8, 6..12)];
+		let (edges, ranges) = edges(&vertices, &indices, &submeshes);
+		assert_eq!(ranges, vec![0..5, 5..10]);
+		assert_eq!(count(&vertices, &edges), (0, 8));
+		assert!(edges[5..].iter().flat_map(|e| &e[..3]).all(|&i| (4..8).contains(&i)));
+	}
+}
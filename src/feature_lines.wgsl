@@ -0,0 +1,144 @@
+// Creases, open boundaries and silhouettes, each mesh edge drawn as a quad facing the screen
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct Section {
+	planes: array<vec4<f32>, 3>,
+	count: u32,
+}
+
+struct Features {
+	viewport: vec2<f32>,
+	// in pixels
+	width: f32,
+	// cosine of the dihedral angle beyond which an edge is a crease
+	crease: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(0) @binding(1)
+var<uniform> section: Section;
+
+@group(1) @binding(0)
+var<uniform> features: Features;
+
+// 8 per vertex, see model.rs
+@group(1) @binding(1)
+var<storage, read> vertices: array<f32>;
+
+// a and b, the corner across from them in the first triangle, and in the second or NONE
+@group(1) @binding(2)
+var<storage, read> edges: array<vec4<u32>>;
+
+// where the exploded view has moved the submesh being drawn
+struct Submesh {
+	object: u32,
+	material: u32,
+	offset: vec3<f32>,
+}
+
+@group(2) @binding(3)
+var<uniform> submesh: Submesh;
+
+const NONE: u32 = 0xffffffffu;
+const LINE_COLOR: vec3<f32> = vec3<f32>(0.01, 0.01, 0.01);
+// boundaries stand out, since on a mesh that should be closed they are holes
+const BOUNDARY_COLOR: vec3<f32> = vec3<f32>(1.0, 0.3, 0.05);
+// fraction of the distance to the eye lines are pulled in by, so the faces they lie on don't hide them
+const DEPTH_PULL: f32 = 0.005;
+
+struct InstanceIn {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    // distance from the middle of the line in pixels, signed by side
+    @location(2) across: f32,
+}
+
+fn position(model_matrix: mat4x4<f32>, i: u32) -> vec3<f32> {
+	let v = i * 8u;
+	return (model_matrix * vec4<f32>(vec3<f32>(vertices[v], vertices[v + 1u], vertices[v + 2u]) + submesh.offset, 1.0)).xyz;
+}
+
+// each edge is six vertices, two triangles over the corners of its quad
+@vertex
+fn vs_main(@builtin(vertex_index) i: u32, instance: InstanceIn) -> VertexOutput {
+	let model_matrix = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+	let edge = edges[i / 6u];
+	let a = position(model_matrix, edge.x);
+	let b = position(model_matrix, edge.y);
+
+	// edges that aren't features collapse to a point outside the view
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+
+	// the second triangle runs the other way along the edge, so both normals point out
+	var color = BOUNDARY_COLOR;
+	if edge.w != NONE {
+		let n0 = cross(b - a, position(model_matrix, edge.z) - a);
+		let n1 = cross(a - b, position(model_matrix, edge.w) - b);
+		let eye = camera.position.xyz - a;
+		let silhouette = (dot(n0, eye) > 0.0) != (dot(n1, eye) > 0.0);
+		let crease = dot(n0, n1) < features.crease * length(n0) * length(n1);
+		if !silhouette && !crease {
+			return out;
+		}
+		color = LINE_COLOR;
+	}
+
+	var clip_a = camera.view_proj * vec4<f32>(mix(a, camera.position.xyz, DEPTH_PULL), 1.0);
+	var clip_b = camera.view_proj * vec4<f32>(mix(b, camera.position.xyz, DEPTH_PULL), 1.0);
+	// cut at the near plane, past which the projected direction flips
+	let near = 1e-4;
+	if clip_a.w < near && clip_b.w < near {
+		return out;
+	}
+	if clip_a.w < near {
+		clip_a = mix(clip_a, clip_b, (near - clip_a.w) / (clip_b.w - clip_a.w));
+	} else if clip_b.w < near {
+		clip_b = mix(clip_b, clip_a, (near - clip_b.w) / (clip_a.w - clip_b.w));
+	}
+
+	let half_viewport = features.viewport * 0.5;
+	let screen = clip_b.xy / clip_b.w * half_viewport - clip_a.xy / clip_a.w * half_viewport;
+	let along = select(vec2<f32>(1.0, 0.0), normalize(screen), dot(screen, screen) > 1e-12);
+	let side = vec2<f32>(-along.y, along.x);
+
+	// corners 0 and 1 at a, 2 and 3 at b, in the order 0 1 2 2 1 3
+	let corner = (0x312210u >> ((i % 6u) * 4u)) & 3u;
+	let at_b = corner >= 2u;
+	let sign = select(-1.0, 1.0, (corner & 1u) == 1u);
+	// a pixel more for the falloff, and as far past each end so neighbouring lines join up
+	let half_width = features.width * 0.5 + 1.0;
+	let offset = (side * sign + along * select(-1.0, 1.0, at_b)) * half_width;
+	let clip = select(clip_a, clip_b, at_b);
+
+	out.clip_position = vec4<f32>(clip.xy + offset / half_viewport * clip.w, clip.zw);
+	out.world_position = select(a, b, at_b);
+	out.color = color;
+	out.across = sign * half_width;
+	return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	for (var i = 0u; i < section.count; i++) {
+		if dot(section.planes[i].xyz, in.world_position) > section.planes[i].w {
+			discard;
+		}
+	}
+	let coverage = saturate(features.width * 0.5 + 0.5 - abs(in.across));
+	return vec4<f32>(in.color, coverage);
+}
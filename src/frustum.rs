@@ -0,0 +1,153 @@
+use cgmath::{InnerSpace, Matrix, Matrix4, Vector3, Vector4};
+
+use crate::bounds::BoundingBox;
+use crate::instance::Instance;
+use crate::model::{Draw, Submesh};
+
+/// The six planes bounding what a view-projection matrix shows, normals pointing in, taken from
+/// the matrix's rows (Gribb and Hartmann). Clip space runs from -w to w on every axis, as
+/// `cgmath::perspective` sets it up.
+pub struct Frustum {
+	planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+	pub fn from_matrix(m: &Matrix4<f32>) -> Self {
+		let (x, y, z, w) = (m.row(0), m.row(1), m.row(2), m.row(3));
+		Self { planes: [w + x, w - x, w + y, w - y, w + z, w - z] }
+	}
+
+	/// Whether any of `aabb` might be inside. A box can lie outside while reaching past every
+	/// plane near a corner of the frustum, which only costs a draw.
+	pub fn intersects(&self, aabb: &BoundingBox) -> bool {
+		!aabb.is_empty() && self.planes.iter().all(|plane| {
+			// the corner farthest along the plane's normal
+			let corner = Vector3::new(
+				if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+				if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+				if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+			);
+			plane.truncate().dot(corner) + plane.w >= 0.0
+		})
+	}
+}
+
+/// Submesh instances drawn and culled in a frame, and the triangles drawn.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CullStats {
+	pub drawn: u32,
+	pub culled: u32,
+	pub triangles: u64,
+}
+
+/// Narrows `draws` down to the instances whose submesh, as the exploded view has moved it,
+/// reaches into `frustum`. A draw's instance range is split around the instances culled from
+/// it, and draws keep their order, so blended ones stay sorted.
+pub fn cull(submeshes: &[Submesh], instances: &[Instance], draws: &[Draw], frustum: &Frustum, stats: &mut CullStats) -> Vec<Draw> {
+	let mut visible = Vec::with_capacity(draws.len());
+	for draw in draws {
+		let submesh = &submeshes[draw.submesh];
+		let offset = Matrix4::from_translation(submesh.offset);
+		let triangles = submesh.indices.len() as u64 / 3;
+		let mut run: Option<Draw> = None;
+
+		for i in draw.instances.clone() {
+			let aabb = submesh.bounds.aabb.transform(&(instances[i as usize].transform * offset));
+			if !frustum.intersects(&aabb) {
+				stats.culled += 1;
+				visible.extend(run.take());
+				continue;
+			}
+			stats.drawn += 1;
+			stats.triangles += triangles;
+			match &mut run {
+				Some(draw) => draw.instances.end = i + 1,
+				None => run = Some(Draw { submesh: draw.submesh, instances: i..i + 1 }),
+			}
+		}
+		visible.extend(run);
+	}
+	visible
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cgmath::{Deg, Vector3, Zero};
+
+	use crate::bounds::Bounds;
+
+	/// Looking down -z from the origin.
+	fn frustum() -> Frustum {
+		Frustum::from_matrix(&cgmath::perspective(Deg(90.0), 1.0, 0.1, 100.0))
+	}
+
+	fn unit_cube() -> Vec<Vector3<f32>> {
+		BoundingBox { min: Vector3::new(-0.5, -0.5, -0.5), max: Vector3::new(0.5, 0.5, 0.5) }.corners().to_vec()
+	}
+
+	fn submesh(triangles: u32, offset: Vector3<f32>) -> Submesh {
+		Submesh {
+			name: String::new(),
+			indices: 0..triangles * 3,
+			bounds: Bounds::from_points(&unit_cube()),
+			material: 0,
+			vertices: 0..8,
+			offset,
+		}
+	}
+
+	fn at(x: f32, y: f32, z: f32) -> Instance {
+		Instance { transform: Matrix4::from_translation(Vector3::new(x, y, z)), ..Default::default() }
+	}
+
+	#[test]
+	fn intersects() {
+		let frustum = frustum();
+		let aabb = |center: Vector3<f32>| BoundingBox::from_points(unit_cube().into_iter().map(|p| p + center));
+		assert!(frustum.intersects(&aabb(Vector3::new(0.0, 0.0, -5.0))));
+		// straddling the left plane, and the near plane
+		assert!(frustum.intersects(&aabb(Vector3::new(-5.4, 0.0, -5.0))));
+		assert!(frustum.intersects(&aabb(Vector3::zero())));
+		assert!(!frustum.intersects(&aabb(Vector3::new(-7.0, 0.0, -5.0))));
+		assert!(!frustum.intersects(&aabb(Vector3::new(0.0, 0.0, 5.0))));
+		assert!(!frustum.intersects(&aabb(Vector3::new(0.0, 0.0, -101.0))));
+		assert!(!frustum.intersects(&BoundingBox::empty()));
+	}
+
+	#[test]
+	fn cull_splits_instance_ranges() {
+		// in, in, off to the side, in, behind
+		let instances = [at(0.0, 0.0, -5.0), at(1.0, 0.0, -8.0), at(20.0, 0.0, -5.0), at(0.0, -2.0, -5.0), at(0.0, 0.0, 5.0)];
+		let submeshes = [
+			submesh(12, Vector3::zero()),
+			// exploded out of view
+			submesh(4, Vector3::new(0.0, 50.0, 0.0)),
+			submesh(2, Vector3::zero()),
+		];
+		let draws = [
+			Draw { submesh: 0, instances: 0..5 },
+			Draw { submesh: 1, instances: 0..2 },
+			Draw { submesh: 2, instances: 2..5 },
+		];
+
+		let mut stats = CullStats::default();
+		let visible = cull(&submeshes, &instances, &draws, &frustum(), &mut stats);
+		assert_eq!(visible, vec![
+			Draw { submesh: 0, instances: 0..2 },
+			Draw { submesh: 0, instances: 3..4 },
+			Draw { submesh: 2, instances: 3..4 },
+		]);
+		assert_eq!(stats, CullStats { drawn: 4, culled: 6, triangles: 3 * 12 + 2 });
+	}
+
+	#[test]
+	fn cull_keeps_whole_ranges_in_view() {
+		let instances = [at(0.0, 0.0, -5.0), at(0.0, 0.0, -6.0), at(0.0, 0.0, -7.0)];
+		let draws = [Draw { submesh: 0, instances: 0..3 }];
+		let mut stats = CullStats::default();
+		let visible = cull(&[submesh(12, Vector3::zero())], &instances, &draws, &frustum(), &mut stats);
+		assert_eq!(visible, draws);
+		assert_eq!(stats, CullStats { drawn: 3, culled: 0, triangles: 36 });
+	}
+}
@@ -0,0 +1,222 @@
+use cgmath::{Matrix4, SquareMatrix, Vector3, Zero};
+use wgpu::util::DeviceExt;
+use winit::event::VirtualKeyCode;
+
+use crate::camera::Camera;
+use crate::lines::{self, LineVertex, Lines};
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+	view_proj: [[f32; 4]; 4],
+	inv_view_proj: [[f32; 4]; 4],
+	view: [[f32; 4]; 4],
+	position: [f32; 4],
+	viewport: [f32; 2],
+	scale: f32,
+	fade: f32,
+}
+
+/// Infinite ground grid on the file's y = 0 plane, with an axis gizmo in the bottom left corner
+/// and axes marking the origin. Grid lines are a power of ten file units apart, chosen from
+/// the camera's zoom distance.
+pub struct Grid {
+	shader: wgpu::ShaderModule,
+	layout: wgpu::PipelineLayout,
+	format: wgpu::TextureFormat,
+	grid_pipeline: wgpu::RenderPipeline,
+	gizmo_pipeline: wgpu::RenderPipeline,
+	bind_group: wgpu::BindGroup,
+	buffer: wgpu::Buffer,
+	gizmo: Lines,
+	/// Drawn with the line pipeline.
+	pub origin: Lines,
+	pub enabled: bool,
+}
+
+impl Grid {
+	pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, origin_size: f32) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Grid Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("grid.wgsl").into()),
+		});
+
+		let buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Grid Buffer"),
+				contents: bytemuck::cast_slice(&[GridUniform {
+					view_proj: Matrix4::identity().into(),
+					inv_view_proj: Matrix4::identity().into(),
+					view: Matrix4::identity().into(),
+					position: [0.0; 4],
+					viewport: [1.0; 2],
+					scale: 0.0,
+					fade: 1.0,
+				}]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				}
+			],
+			label: Some("grid_bind_group_layout"),
+		});
+
+		let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: buffer.as_entire_binding(),
+				}
+			],
+			label: Some("grid_bind_group"),
+		});
+
+		let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Grid Pipeline Layout"),
+			bind_group_layouts: &[&bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let (grid_pipeline, gizmo_pipeline) = Self::build(device, &shader, &layout, format, sample_count);
+
+		Self {
+			shader,
+			layout,
+			format,
+			grid_pipeline,
+			gizmo_pipeline,
+			bind_group,
+			buffer,
+			gizmo: Lines::new(device, &lines::axes(Vector3::zero(), 1.0), "Gizmo Lines"),
+			origin: Lines::new(device, &lines::axes(Vector3::zero(), origin_size), "Origin Lines"),
+			enabled: true,
+		}
+	}
+
+	pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+		(self.grid_pipeline, self.gizmo_pipeline) = Self::build(device, &self.shader, &self.layout, self.format, sample_count);
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue, camera: &Camera, viewport: [f32; 2]) {
+		let view_proj = camera.view_proj();
+		let zoom = camera.zoom.max(1e-6);
+		let uniform = GridUniform {
+			view_proj: view_proj.into(),
+			inv_view_proj: view_proj.invert().unwrap_or(Matrix4::identity()).into(),
+			view: camera.view().into(),
+			position: camera.transform.w.into(),
+			viewport,
+			scale: zoom.log10(),
+			fade: (zoom * 10.0).min(camera.zfar),
+		};
+		queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+	}
+
+	/// G turns the grid on and off.
+	pub fn input(&mut self, keycode: VirtualKeyCode, camera: &Camera) -> Option<String> {
+		if keycode != VirtualKeyCode::G {
+			return None;
+		}
+		self.enabled = !self.enabled;
+		Some(if self.enabled { format!("grid: {} units", Self::spacing(camera)) } else { "grid: off".into() })
+	}
+
+	/// Current spacing of the major grid lines, in file units.
+	pub fn spacing(camera: &Camera) -> f32 {
+		10.0_f32.powf(camera.zoom.max(1e-6).log10().floor())
+	}
+
+	/// Draws the grid, then the gizmo over everything.
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		if !self.enabled {
+			return;
+		}
+		render_pass.set_bind_group(0, &self.bind_group, &[]);
+		render_pass.set_pipeline(&self.grid_pipeline);
+		render_pass.draw(0..3, 0..1);
+		render_pass.set_pipeline(&self.gizmo_pipeline);
+		self.gizmo.draw(render_pass);
+	}
+
+	fn build(device: &wgpu::Device, shader: &wgpu::ShaderModule, layout: &wgpu::PipelineLayout, format: wgpu::TextureFormat, sample_count: u32) -> (wgpu::RenderPipeline, wgpu::RenderPipeline) {
+		let multisample = wgpu::MultisampleState {
+			count: sample_count,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		};
+
+		let grid = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Grid Pipeline"),
+			layout: Some(layout),
+			vertex: wgpu::VertexState {
+				module: shader,
+				entry_point: "vs_grid",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: shader,
+				entry_point: "fs_grid",
+				targets: &[Some(wgpu::ColorTargetState {
+					format,
+					blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			// the depth of the ground is written by the fragment shader, so the model hides it
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::Less,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample,
+			multiview: None,
+		});
+
+		let gizmo = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Gizmo Pipeline"),
+			layout: Some(layout),
+			vertex: wgpu::VertexState {
+				module: shader,
+				entry_point: "vs_gizmo",
+				buffers: &[LineVertex::desc()],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: shader,
+				entry_point: "fs_gizmo",
+				targets: &[Some(format.into())],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::LineList,
+				..Default::default()
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::Always,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample,
+			multiview: None,
+		});
+
+		(grid, gizmo)
+	}
+}
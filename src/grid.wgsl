@@ -0,0 +1,114 @@
+// Ground grid on the y = 0 plane and the corner axis gizmo
+struct Grid {
+	view_proj: mat4x4<f32>,
+	inv_view_proj: mat4x4<f32>,
+	view: mat4x4<f32>,
+	position: vec4<f32>,
+	viewport: vec2<f32>,
+	// log10 of the camera's zoom distance
+	scale: f32,
+	// distance the grid fades out over
+	fade: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> grid: Grid;
+
+struct GridOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) ndc: vec2<f32>,
+}
+
+@vertex
+fn vs_grid(@builtin(vertex_index) index: u32) -> GridOutput {
+	// one triangle covering the screen
+	let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+	var out: GridOutput;
+	out.ndc = uv * 2.0 - 1.0;
+	out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+	return out;
+}
+
+// coverage of lines `spacing` apart, about a pixel wide
+fn lines(coords: vec2<f32>, spacing: f32) -> f32 {
+	let c = coords / spacing;
+	let d = abs(fract(c - 0.5) - 0.5) / max(fwidth(c), vec2<f32>(1e-6));
+	return 1.0 - min(min(d.x, d.y), 1.0);
+}
+
+struct GridFragment {
+	@location(0) color: vec4<f32>,
+	@builtin(frag_depth) depth: f32,
+}
+
+@fragment
+fn fs_grid(in: GridOutput) -> GridFragment {
+	// where the pixel's ray meets the ground
+	let near = grid.inv_view_proj * vec4<f32>(in.ndc, 0.0, 1.0);
+	let far = grid.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+	let origin = near.xyz / near.w;
+	let ray = far.xyz / far.w - origin;
+	let t = -origin.y / ray.y;
+	let p = origin + ray * t;
+	let clip = grid.view_proj * vec4<f32>(p, 1.0);
+
+	// powers of ten, the finer one fading out as the camera backs away
+	let level = floor(grid.scale) - 1.0;
+	let minor = pow(10.0, level);
+	let blend = fract(grid.scale);
+	let coverage = max(lines(p.xz, minor) * (1.0 - blend) * 0.5, lines(p.xz, minor * 10.0));
+
+	// the x axis in red, the z axis in blue
+	let axis = abs(p.zx) / max(fwidth(p.zx), vec2<f32>(1e-6));
+	var color = vec3<f32>(0.5);
+	if axis.x < 1.0 {
+		color = vec3<f32>(0.9, 0.2, 0.2);
+	} else if axis.y < 1.0 {
+		color = vec3<f32>(0.2, 0.3, 0.9);
+	}
+	let alpha = max(coverage, 1.0 - min(min(axis.x, axis.y), 1.0));
+
+	let distance = length(p - grid.position.xyz);
+	let fade = 1.0 - smoothstep(grid.fade * 0.25, grid.fade, distance);
+
+	// written so that NaNs from rays parallel to the ground are discarded too
+	let depth = clip.z / clip.w;
+	if !(t > 0.0 && depth >= 0.0 && depth <= 1.0 && alpha * fade > 0.0) {
+		discard;
+	}
+
+	var out: GridFragment;
+	out.color = vec4<f32>(color, alpha * fade * 0.6);
+	out.depth = depth;
+	return out;
+}
+
+struct GizmoIn {
+	@location(0) position: vec3<f32>,
+	@location(1) color: vec3<f32>,
+}
+
+struct GizmoOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) color: vec3<f32>,
+}
+
+const GIZMO_SIZE: f32 = 40.0;
+const GIZMO_MARGIN: f32 = 60.0;
+
+// the world axes turned with the camera, GIZMO_SIZE pixels long in the bottom left corner
+@vertex
+fn vs_gizmo(in: GizmoIn) -> GizmoOutput {
+	let rotated = (grid.view * vec4<f32>(in.position, 0.0)).xyz;
+	let pixel = 2.0 / grid.viewport;
+	let corner = -1.0 + GIZMO_MARGIN * pixel;
+	var out: GizmoOutput;
+	out.clip_position = vec4<f32>(corner + rotated.xy * GIZMO_SIZE * pixel, 0.5, 1.0);
+	out.color = in.color;
+	return out;
+}
+
+@fragment
+fn fs_gizmo(in: GizmoOutput) -> @location(0) vec4<f32> {
+	return vec4<f32>(in.color, 1.0);
+}
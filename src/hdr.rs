@@ -0,0 +1,284 @@
+use wgpu::util::DeviceExt;
+
+use crate::settings::Setting;
+use crate::texture::Texture;
+use crate::tonemapper::Tonemapper;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+	exposure: f32,
+	tonemapper: u32,
+	bloom: f32,
+	threshold: f32,
+}
+
+/// Floating point target the scene is drawn into, and the passes that bring it to the surface:
+/// exposure, optional bloom and a tonemapper.
+pub struct Hdr {
+	prefilter_pipeline: wgpu::RenderPipeline,
+	downsample_pipeline: wgpu::RenderPipeline,
+	upsample_pipeline: wgpu::RenderPipeline,
+	tonemap_pipeline: wgpu::RenderPipeline,
+	bind_group_layout: wgpu::BindGroupLayout,
+	/// Reading `target`, then each of `levels`.
+	bind_groups: Vec<wgpu::BindGroup>,
+	/// Reading `target` and the largest bloom level.
+	tonemap_bind_group: wgpu::BindGroup,
+	uniform_buffer: wgpu::Buffer,
+	sampler: wgpu::Sampler,
+	pub target: Texture,
+	/// Bloom chain from half the surface size down, each level half the one before.
+	levels: Vec<Texture>,
+	/// In stops.
+	pub exposure: f32,
+	pub tonemapper: Tonemapper,
+	/// How much bloom is added, 0 for none.
+	pub bloom: f32,
+}
+
+impl Hdr {
+	pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+	const MAX_LEVELS: u32 = 6;
+	/// Scene brightness where bloom starts, after exposure.
+	const THRESHOLD: f32 = 1.0;
+
+	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, tonemapper: Tonemapper) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("HDR Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("hdr.wgsl").into()),
+		});
+
+		let uniform_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Post Buffer"),
+				contents: bytemuck::cast_slice(&[PostUniform { exposure: 1.0, tonemapper: tonemapper as u32, bloom: 0.0, threshold: Self::THRESHOLD }]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let texture = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Texture {
+				sample_type: wgpu::TextureSampleType::Float { filterable: true },
+				view_dimension: wgpu::TextureViewDimension::D2,
+				multisampled: false,
+			},
+			count: None,
+		};
+
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				texture(1),
+				texture(2),
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+			],
+			label: Some("hdr_bind_group_layout"),
+		});
+
+		let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("HDR Pipeline Layout"),
+			bind_group_layouts: &[&bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let fullscreen = |label, entry_point, format, blend| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some(label),
+			layout: Some(&pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point,
+				targets: &[Some(wgpu::ColorTargetState {
+					format,
+					blend,
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+		let additive = wgpu::BlendState {
+			color: wgpu::BlendComponent {
+				src_factor: wgpu::BlendFactor::One,
+				dst_factor: wgpu::BlendFactor::One,
+				operation: wgpu::BlendOperation::Add,
+			},
+			alpha: wgpu::BlendComponent::REPLACE,
+		};
+		let prefilter_pipeline = fullscreen("Bloom Prefilter Pipeline", "fs_prefilter", Self::FORMAT, None);
+		let downsample_pipeline = fullscreen("Bloom Downsample Pipeline", "fs_downsample", Self::FORMAT, None);
+		let upsample_pipeline = fullscreen("Bloom Upsample Pipeline", "fs_upsample", Self::FORMAT, Some(additive));
+		let tonemap_pipeline = fullscreen("Tonemap Pipeline", "fs_tonemap", config.format, None);
+
+		let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+			address_mode_u: wgpu::AddressMode::ClampToEdge,
+			address_mode_v: wgpu::AddressMode::ClampToEdge,
+			mag_filter: wgpu::FilterMode::Linear,
+			min_filter: wgpu::FilterMode::Linear,
+			..Default::default()
+		});
+
+		let (target, levels) = Self::create_targets(device, config);
+		let (bind_groups, tonemap_bind_group) = Self::create_bind_groups(device, &bind_group_layout, &uniform_buffer, &sampler, &target, &levels);
+
+		Self {
+			prefilter_pipeline,
+			downsample_pipeline,
+			upsample_pipeline,
+			tonemap_pipeline,
+			bind_group_layout,
+			bind_groups,
+			tonemap_bind_group,
+			uniform_buffer,
+			sampler,
+			target,
+			levels,
+			exposure: 0.0,
+			tonemapper,
+			bloom: 0.0,
+		}
+	}
+
+	pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+		(self.target, self.levels) = Self::create_targets(device, config);
+		(self.bind_groups, self.tonemap_bind_group) = Self::create_bind_groups(device, &self.bind_group_layout, &self.uniform_buffer, &self.sampler, &self.target, &self.levels);
+	}
+
+	/// `passthrough` shows the scene as drawn, for debug modes whose colors are data.
+	pub fn settings(&self) -> &'static [Setting] {
+		&[Setting::Exposure, Setting::Tonemapper, Setting::Bloom]
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::Exposure => self.exposure = (self.exposure + steps * 0.5).clamp(-10.0, 10.0),
+			Setting::Tonemapper => self.tonemapper = self.tonemapper.step(steps as i32),
+			Setting::Bloom => self.bloom = (self.bloom + steps * 0.05).clamp(0.0, 1.0),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::Exposure => Some(format!("{} EV", self.exposure)),
+			Setting::Tonemapper => Some(self.tonemapper.name().into()),
+			Setting::Bloom if self.bloom <= 0.0 => Some("off".into()),
+			Setting::Bloom => Some(format!("{:.2}", self.bloom)),
+			_ => None,
+		}
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue, passthrough: bool) {
+		let uniform = if passthrough {
+			PostUniform { exposure: 1.0, tonemapper: Tonemapper::None as u32, bloom: 0.0, threshold: Self::THRESHOLD }
+		} else {
+			PostUniform {
+				exposure: 2.0_f32.powf(self.exposure),
+				tonemapper: self.tonemapper as u32,
+				// every level adds its own copy of the bright parts
+				bloom: self.bloom / self.levels.len() as f32,
+				threshold: Self::THRESHOLD,
+			}
+		};
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+	}
+
+	/// Blooms and tonemaps `target` into `view`.
+	pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+		if self.bloom > 0.0 {
+			Self::pass(encoder, "Bloom Prefilter Pass", &self.levels[0].view, &self.prefilter_pipeline, &self.bind_groups[0], true);
+			for i in 1..self.levels.len() {
+				Self::pass(encoder, "Bloom Downsample Pass", &self.levels[i].view, &self.downsample_pipeline, &self.bind_groups[i], true);
+			}
+			for i in (1..self.levels.len()).rev() {
+				Self::pass(encoder, "Bloom Upsample Pass", &self.levels[i - 1].view, &self.upsample_pipeline, &self.bind_groups[i + 1], false);
+			}
+		}
+		Self::pass(encoder, "Tonemap Pass", view, &self.tonemap_pipeline, &self.tonemap_bind_group, true);
+	}
+
+	fn pass(encoder: &mut wgpu::CommandEncoder, label: &str, target: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup, clear: bool) {
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some(label),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: target,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: if clear { wgpu::LoadOp::Clear(wgpu::Color::BLACK) } else { wgpu::LoadOp::Load },
+					store: wgpu::StoreOp::Store,
+				},
+			})],
+			depth_stencil_attachment: None,
+			occlusion_query_set: None,
+			timestamp_writes: None,
+		});
+		render_pass.set_pipeline(pipeline);
+		render_pass.set_bind_group(0, bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+
+	fn create_targets(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> (Texture, Vec<Texture>) {
+		let target = Texture::new_render_target(device, config, Self::FORMAT, "hdr_target");
+		// stop before the smallest side drops below 8 pixels
+		let smallest = config.width.min(config.height).max(16);
+		let count = (smallest.ilog2() - 3).clamp(1, Self::MAX_LEVELS);
+		let levels = (1..=count)
+			.map(|i| Texture::new_render_target_sized(device, (config.width >> i).max(1), (config.height >> i).max(1), Self::FORMAT, "bloom_level"))
+			.collect();
+		(target, levels)
+	}
+
+	/// One bind group per pass input, and the tonemap pass's. Only the tonemap pass reads a
+	/// bloom level at binding 2; the others get their input there again, since a level can't be
+	/// bound while it is drawn into.
+	fn create_bind_groups(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, sampler: &wgpu::Sampler, target: &Texture, levels: &[Texture]) -> (Vec<wgpu::BindGroup>, wgpu::BindGroup) {
+		let bind_group = |input: &Texture, bloom: &Texture| device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: uniform_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&input.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(&bloom.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: wgpu::BindingResource::Sampler(sampler),
+				},
+			],
+			label: Some("hdr_bind_group"),
+		});
+		let passes = std::iter::once(target).chain(levels).map(|input| bind_group(input, input)).collect();
+		(passes, bind_group(target, &levels[0]))
+	}
+}
@@ -0,0 +1,132 @@
+// Bloom and tonemapping of the HDR scene. Bloom thresholds the scene into a chain of ever
+// smaller textures, then adds each back into the next larger one on the way up.
+struct Post {
+	// 2^EV, applied before bloom and tonemapping
+	exposure: f32,
+	tonemapper: u32,
+	// bloom strength, already divided among the levels; 0 skips it
+	bloom: f32,
+	threshold: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> post: Post;
+
+// the scene for the prefilter and tonemap passes, the previous level for the others
+@group(0) @binding(1)
+var input_texture: texture_2d<f32>;
+
+// the largest bloom level, read by the tonemap pass
+@group(0) @binding(2)
+var bloom_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var linear_sampler: sampler;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+	// one triangle covering the screen
+	let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+	out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+	return out;
+}
+
+fn tap(uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+	return textureSampleLevel(input_texture, linear_sampler, uv + offset / vec2<f32>(textureDimensions(input_texture)), 0.0).rgb;
+}
+
+// 13 taps in overlapping 4x4 boxes, as in Jimenez's "Next Generation Post Processing in Call of
+// Duty", which keeps small bright spots from flickering as they move
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+	let center = tap(uv, vec2<f32>(0.0));
+	let inner = tap(uv, vec2<f32>(-1.0, -1.0)) + tap(uv, vec2<f32>(1.0, -1.0)) + tap(uv, vec2<f32>(-1.0, 1.0)) + tap(uv, vec2<f32>(1.0, 1.0));
+	let corners = tap(uv, vec2<f32>(-2.0, -2.0)) + tap(uv, vec2<f32>(2.0, -2.0)) + tap(uv, vec2<f32>(-2.0, 2.0)) + tap(uv, vec2<f32>(2.0, 2.0));
+	let sides = tap(uv, vec2<f32>(-2.0, 0.0)) + tap(uv, vec2<f32>(2.0, 0.0)) + tap(uv, vec2<f32>(0.0, -2.0)) + tap(uv, vec2<f32>(0.0, 2.0));
+	return center * 0.125 + inner * 0.125 + corners * 0.03125 + sides * 0.0625;
+}
+
+// keeps what is brighter than the threshold, with a soft knee below it
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+	let color = downsample(in.uv) * post.exposure;
+	let brightness = max(color.r, max(color.g, color.b));
+	let knee = post.threshold * 0.5;
+	var soft = clamp(brightness - post.threshold + knee, 0.0, 2.0 * knee);
+	soft = soft * soft / (4.0 * knee + 1e-4);
+	let contribution = max(soft, brightness - post.threshold) / max(brightness, 1e-4);
+	// half floats overflow past 65504
+	return vec4<f32>(min(color * contribution, vec3<f32>(1e4)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+	return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent, blended additively into the next level up
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+	let sides = tap(in.uv, vec2<f32>(-1.0, 0.0)) + tap(in.uv, vec2<f32>(1.0, 0.0)) + tap(in.uv, vec2<f32>(0.0, -1.0)) + tap(in.uv, vec2<f32>(0.0, 1.0));
+	let corners = tap(in.uv, vec2<f32>(-1.0, -1.0)) + tap(in.uv, vec2<f32>(1.0, -1.0)) + tap(in.uv, vec2<f32>(-1.0, 1.0)) + tap(in.uv, vec2<f32>(1.0, 1.0));
+	return vec4<f32>((tap(in.uv, vec2<f32>(0.0)) * 4.0 + sides * 2.0 + corners) / 16.0, 1.0);
+}
+
+fn aces(color: vec3<f32>) -> vec3<f32> {
+	let input = mat3x3<f32>(
+		0.59719, 0.07600, 0.02840,
+		0.35458, 0.90834, 0.13383,
+		0.04823, 0.01566, 0.83777,
+	);
+	let output = mat3x3<f32>(
+		1.60475, -0.10208, -0.00327,
+		-0.53108, 1.10813, -0.07276,
+		-0.07367, -0.00605, 1.07602,
+	);
+	let v = input * color;
+	let fitted = (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081);
+	return saturate(output * fitted);
+}
+
+fn agx(color: vec3<f32>) -> vec3<f32> {
+	let inset = mat3x3<f32>(
+		0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+		0.0784335999999992, 0.878468636469772, 0.0784336,
+		0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+	);
+	let outset = mat3x3<f32>(
+		1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+		-0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+		-0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+	);
+	let min_ev = -12.47393;
+	let max_ev = 4.026069;
+	let x = (clamp(log2(max(inset * color, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev)) - min_ev) / (max_ev - min_ev);
+	// sigmoid contrast curve, ending in the display encoding
+	let x2 = x * x;
+	let x4 = x2 * x2;
+	let curve = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+	// back to linear, which the sRGB surface encodes again
+	return pow(saturate(outset * curve), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_tonemap(in: VertexOutput) -> @location(0) vec4<f32> {
+	var color = textureSampleLevel(input_texture, linear_sampler, in.uv, 0.0).rgb * post.exposure;
+	if post.bloom > 0.0 {
+		color += textureSampleLevel(bloom_texture, linear_sampler, in.uv, 0.0).rgb * post.bloom;
+	}
+	switch post.tonemapper {
+		case 0u { color = aces(color); }
+		case 1u { color = agx(color); }
+		case 2u { color = color / (1.0 + color); }
+		default { color = saturate(color); }
+	}
+	return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use wgpu::util::DeviceExt;
+use cgmath::{Deg, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3, Vector4};
+use serde::Deserialize;
+
+/// One placement of a mesh: a model transform and a color multiplied into the shading.
+#[derive(Copy, Clone, Debug)]
+pub struct Instance {
+	pub transform: Matrix4<f32>,
+	pub color: [f32; 4],
+}
+
+impl Default for Instance {
+	fn default() -> Self {
+		Self {
+			transform: Matrix4::identity(),
+			color: [1.0, 1.0, 1.0, 1.0],
+		}
+	}
+}
+
+impl Instance {
+	/// The transform without its translation, for directions along a surface.
+	pub fn linear(&self) -> Matrix3<f32> {
+		Matrix3::from_cols(self.transform.x.truncate(), self.transform.y.truncate(), self.transform.z.truncate())
+	}
+
+	/// Inverse transpose of `linear`, which keeps normals perpendicular to the surface under
+	/// non-uniform scale.
+	pub fn normal_matrix(&self) -> Matrix3<f32> {
+		self.linear().invert().map(|m| m.transpose()).unwrap_or(Matrix3::identity())
+	}
+
+	pub fn to_raw(&self) -> InstanceRaw {
+		InstanceRaw {
+			model: self.transform.into(),
+			normal: self.normal_matrix().into(),
+			color: self.color,
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+	model: [[f32; 4]; 4],
+	normal: [[f32; 3]; 3],
+	color: [f32; 4],
+}
+
+impl InstanceRaw {
+	const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+		5 => Float32x4,
+		6 => Float32x4,
+		7 => Float32x4,
+		8 => Float32x4,
+		9 => Float32x3,
+		10 => Float32x3,
+		11 => Float32x3,
+		12 => Float32x4,
+	];
+
+	pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+		wgpu::VertexBufferLayout {
+			array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Instance,
+			attributes: &Self::ATTRIBUTES,
+		}
+	}
+}
+
+/// Per-instance transforms and colors, bound next to a mesh's vertex buffer.
+pub struct InstanceBuffer {
+	buffer: wgpu::Buffer,
+	pub instances: Vec<Instance>,
+}
+
+impl InstanceBuffer {
+	pub fn new(device: &wgpu::Device, instances: Vec<Instance>) -> Self {
+		Self {
+			buffer: Self::create_buffer(device, &instances),
+			instances,
+		}
+	}
+
+	/// Replaces the instances, reusing the GPU buffer when they still fit.
+	pub fn set(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, instances: Vec<Instance>) {
+		let raw: Vec<InstanceRaw> = instances.iter().map(Instance::to_raw).collect();
+		if std::mem::size_of_val(raw.as_slice()) as u64 <= self.buffer.size() {
+			queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(raw.as_slice()));
+		} else {
+			self.buffer = Self::create_buffer(device, &instances);
+		}
+		self.instances = instances;
+	}
+
+	pub fn len(&self) -> u32 {
+		self.instances.len() as u32
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.instances.is_empty()
+	}
+
+	pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+		self.buffer.slice(..)
+	}
+
+	fn create_buffer(device: &wgpu::Device, instances: &[Instance]) -> wgpu::Buffer {
+		let raw: Vec<InstanceRaw> = instances.iter().map(Instance::to_raw).collect();
+		device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Instance Buffer"),
+				contents: bytemuck::cast_slice(raw.as_slice()),
+				usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+			}
+		)
+	}
+}
+
+/// Scale written either as one uniform factor or per axis.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scale {
+	Uniform(f32),
+	Axes([f32; 3]),
+}
+
+/// One entry of a JSON placement file. Either `matrix` (16 column-major values) or any of
+/// `position`, `rotation` (XYZ Euler degrees) and `scale` describe the transform.
+#[derive(Deserialize)]
+struct Placement {
+	matrix: Option<[f32; 16]>,
+	position: Option<[f32; 3]>,
+	rotation: Option<[f32; 3]>,
+	scale: Option<Scale>,
+	color: Option<Vec<f32>>,
+}
+
+/// Loads instances from a placement file, picked by extension.
+///
+/// `.json` files hold an array of objects such as `{"position": [0, 0, 5], "rotation": [0, 90, 0], "scale": 2, "color": [1, 0, 0]}`.
+/// Any other file is read as CSV with the columns `x,y,z[,rx,ry,rz[,sx,sy,sz[,r,g,b[,a]]]]`,
+/// rotations in degrees. Blank lines, `#` comments and a non-numeric header row are skipped.
+/// A file without any placements is an error.
+pub fn load_placements<P: AsRef<Path>>(path: P) -> Result<Vec<Instance>, Box<dyn std::error::Error>> {
+	let path = path.as_ref();
+	let text = std::fs::read_to_string(path)?;
+	let is_json = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+	parse(&text, is_json)
+}
+
+fn parse(text: &str, is_json: bool) -> Result<Vec<Instance>, Box<dyn std::error::Error>> {
+	let instances = if is_json { parse_json(text)? } else { parse_csv(text)? };
+	if instances.is_empty() {
+		return Err("no placements".into());
+	}
+	Ok(instances)
+}
+
+fn parse_json(text: &str) -> Result<Vec<Instance>, Box<dyn std::error::Error>> {
+	let placements: Vec<Placement> = serde_json::from_str(text)?;
+	placements.into_iter().map(|p| {
+		let transform = match p.matrix {
+			Some(m) => {
+				let col = |i: usize| Vector4::new(m[i * 4], m[i * 4 + 1], m[i * 4 + 2], m[i * 4 + 3]);
+				Matrix4::from_cols(col(0), col(1), col(2), col(3))
+			}
+			None => {
+				let scale = match p.scale {
+					Some(Scale::Uniform(s)) => [s; 3],
+					Some(Scale::Axes(s)) => s,
+					None => [1.0; 3],
+				};
+				compose(p.position.unwrap_or([0.0; 3]), p.rotation.unwrap_or([0.0; 3]), scale)
+			}
+		};
+		Ok(Instance {
+			transform,
+			color: color_from(p.color.as_deref().unwrap_or(&[]))?,
+		})
+	}).collect()
+}
+
+fn parse_csv(text: &str) -> Result<Vec<Instance>, Box<dyn std::error::Error>> {
+	let mut instances = vec![];
+	let mut first_row = true;
+	for (n, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let fields: Result<Vec<f32>, _> = line.split(',').map(|f| f.trim().parse::<f32>()).collect();
+		let fields = match fields {
+			Ok(fields) => fields,
+			Err(_) if first_row => { // header row
+				first_row = false;
+				continue;
+			}
+			Err(e) => return Err(format!("line {}: {}", n + 1, e).into()),
+		};
+		first_row = false;
+
+		let get = |i: usize, default: f32| fields.get(i).copied().unwrap_or(default);
+		if fields.len() < 3 {
+			return Err(format!("line {}: expected at least x,y,z", n + 1).into());
+		}
+
+		instances.push(Instance {
+			transform: compose(
+				[fields[0], fields[1], fields[2]],
+				[get(3, 0.0), get(4, 0.0), get(5, 0.0)],
+				[get(6, 1.0), get(7, 1.0), get(8, 1.0)],
+			),
+			color: color_from(fields.get(9..).unwrap_or(&[]))?,
+		});
+	}
+	Ok(instances)
+}
+
+fn compose(position: [f32; 3], rotation: [f32; 3], scale: [f32; 3]) -> Matrix4<f32> {
+	Matrix4::from_translation(Vector3::from(position))
+		* Matrix4::from_angle_z(Deg(rotation[2]))
+		* Matrix4::from_angle_y(Deg(rotation[1]))
+		* Matrix4::from_angle_x(Deg(rotation[0]))
+		* Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+}
+
+fn color_from(values: &[f32]) -> Result<[f32; 4], Box<dyn std::error::Error>> {
+	match values {
+		[] => Ok([1.0, 1.0, 1.0, 1.0]),
+		[r, g, b] => Ok([*r, *g, *b, 1.0]),
+		[r, g, b, a] => Ok([*r, *g, *b, *a]),
+		_ => Err(format!("expected 3 or 4 color components, got {}", values.len()).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cgmath::{InnerSpace, Vector3};
+
+	fn apply(m: &Matrix4<f32>, p: [f32; 3]) -> Vector3<f32> {
+		(m * Vector3::from(p).extend(1.0)).truncate()
+	}
+
+	fn close(a: Vector3<f32>, b: [f32; 3]) -> bool {
+		(a - Vector3::from(b)).magnitude() < 1e-5
+	}
+
+	#[test]
+	fn csv_row() {
+		let text = "x,y,z,rx,ry,rz,sx,sy,sz,r,g,b\n# a comment\n\n1,2,3, 90,0,90, 2,1,1, 1,0,0\n4,5,6\n";
+		let instances = parse_csv(text).unwrap();
+		assert_eq!(instances.len(), 2);
+
+		// scaled, then turned about x, then about z, then moved
+		let m = &instances[0].transform;
+		assert!(close(apply(m, [1.0, 0.0, 0.0]), [1.0, 4.0, 3.0]));
+		assert!(close(apply(m, [0.0, 1.0, 0.0]), [1.0, 2.0, 4.0]));
+		assert_eq!(instances[0].color, [1.0, 0.0, 0.0, 1.0]);
+
+		assert!(close(apply(&instances[1].transform, [0.0, 0.0, 0.0]), [4.0, 5.0, 6.0]));
+		assert_eq!(instances[1].color, [1.0, 1.0, 1.0, 1.0]);
+	}
+
+	#[test]
+	fn csv_errors() {
+		assert!(parse_csv("1,2,3\n1,2\n").is_err());
+		assert!(parse_csv("1,2,3\n1,2,x\n").is_err());
+		assert!(parse_csv("1,2,3,0,0,0,1,1,1,1,0\n").is_err());
+	}
+
+	#[test]
+	fn empty_files_are_errors() {
+		assert!(parse("", false).is_err());
+		assert!(parse("x,y,z\n# nothing yet\n", false).is_err());
+		assert!(parse("[]", true).is_err());
+		assert_eq!(parse("1,2,3\n", false).unwrap().len(), 1);
+	}
+
+	#[test]
+	fn json_entry() {
+		let text = r#"[
+			{"position": [0, 0, 5], "rotation": [0, 90, 0], "scale": 2, "color": [0, 1, 0, 0.5]},
+			{"scale": [1, 2, 3]},
+			{"matrix": [1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 7, 8, 9, 1]}
+		]"#;
+		let instances = parse_json(text).unwrap();
+		assert_eq!(instances.len(), 3);
+
+		let m = &instances[0].transform;
+		assert!(close(apply(m, [1.0, 0.0, 0.0]), [0.0, 0.0, 3.0]));
+		assert!(close(apply(m, [0.0, 1.0, 0.0]), [0.0, 2.0, 5.0]));
+		assert_eq!(instances[0].color, [0.0, 1.0, 0.0, 0.5]);
+
+		assert!(close(apply(&instances[1].transform, [1.0, 1.0, 1.0]), [1.0, 2.0, 3.0]));
+		assert!(close(apply(&instances[2].transform, [0.0, 0.0, 0.0]), [7.0, 8.0, 9.0]));
+	}
+
+	#[test]
+	fn normal_matrix_under_non_uniform_scale() {
+		let instance = Instance {
+			transform: compose([1.0, 2.0, 3.0], [0.0, 0.0, 30.0], [2.0, 1.0, 1.0]),
+			..Default::default()
+		};
+		let normal = Matrix3::from(instance.to_raw().normal);
+
+		// the plane x = y, along a tangent and across it
+		let tangent = Vector3::new(1.0, 1.0, 0.0);
+		let n = normal * Vector3::new(1.0, -1.0, 0.0);
+		let t = (instance.transform * tangent.extend(0.0)).truncate();
+		assert!(n.dot(t).abs() < 1e-5);
+		// transforming it like a direction would tilt it off the surface
+		let wrong = (instance.transform * Vector3::new(1.0, -1.0, 0.0).extend(0.0)).truncate();
+		assert!(wrong.dot(t).abs() > 0.1);
+	}
+}
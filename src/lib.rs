@@ -1,8 +1,35 @@
 // https://sotrh.github.io/learn-wgpu/
 
+pub mod bounds;
 mod camera;
-mod model;
+mod edl;
+mod environment;
+mod explode;
+mod feature_lines;
+mod frustum;
+mod grid;
+mod hdr;
+pub mod instance;
+mod light;
+mod lines;
+mod matcap;
+pub mod material;
+pub mod model;
+mod msaa;
+mod options;
+mod outline;
+mod picking;
+mod pipelines;
+pub mod point_cloud;
+pub mod render_mode;
+mod section;
+mod settings;
+mod shader_source;
+mod shadow;
+mod ssao;
 mod texture;
+pub mod tonemapper;
+mod transparency;
 
 use cgmath::{Matrix4, Vector3};
 use winit::{
@@ -13,14 +40,47 @@ use winit::{
 
 use wgpu::util::DeviceExt;
 
-use camera::{Camera, MatrixUniform};
-use model::{Mesh, Vertex};
+use bounds::BoundingBox;
+use camera::{Camera, CameraUniform};
+use edl::EyeDomeLighting;
+use environment::Environment;
+use explode::ExplodedView;
+use feature_lines::FeatureLines;
+use frustum::{CullStats, Frustum};
+use grid::Grid;
+use hdr::Hdr;
+use instance::{Instance, InstanceBuffer};
+use light::Lights;
+use lines::Overlays;
+use material::Material;
+use model::{Draw, Mesh};
+use msaa::Multisample;
+use outline::Outline;
+use picking::Selection;
+use pipelines::Pipelines;
+use point_cloud::{PointCloud, PointStyle};
+use render_mode::RenderMode;
+use section::Section;
+use settings::Setting;
+use shader_source::ShaderSource;
+use shadow::ShadowMap;
+use ssao::Ssao;
 use texture::Texture;
+use tonemapper::Tonemapper;
+
+pub use options::Options;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct RenderState {
     render_mode: i32,
+    wireframe_width: f32,
+    viewport: [f32; 2],
+    /// View depth range of the scene, for the depth debug mode.
+    depth_range: [f32; 2],
+    two_sided: u32,
+    /// Whether to multiply lighting by the ambient occlusion texture.
+    ssao: u32,
 }
 
 struct State {
@@ -29,24 +89,55 @@ struct State {
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
-	render_pipeline: wgpu::RenderPipeline,
-	wireframe_render_pipeline: wgpu::RenderPipeline,
+	pipelines: Pipelines,
+	msaa: Multisample,
     window: Window,
+	title: String,
 	depth_texture: Texture,
 
 	camera: Camera,
 	camera_buffer: wgpu::Buffer,
-	camera_uniform: MatrixUniform,
+	camera_uniform: CameraUniform,
 	camera_bind_group: wgpu::BindGroup,
 	model: Mesh,
+	instances: InstanceBuffer,
+	points: Option<PointCloud>,
+	point_style: PointStyle,
+	point_buffer: wgpu::Buffer,
+	point_bind_group: wgpu::BindGroup,
+	edl: EyeDomeLighting,
+	setting: Setting,
+	overlays: Overlays,
+	lights: Lights,
+	lights_buffer: wgpu::Buffer,
+	shadow: ShadowMap,
+	ssao: Ssao,
+	environment: Environment,
+	matcap: Texture,
+	grid: Grid,
+	feature_lines: FeatureLines,
+	outline: Outline,
+	hdr: Hdr,
+	section: Section,
+	explode: ExplodedView,
+	selection: Selection,
+	scene_box: BoundingBox,
+	/// What frustum culling left of the last frame, shown in the title bar while `show_stats`.
+	cull_stats: CullStats,
+	show_stats: bool,
 
 	render_state_buffer: wgpu::Buffer,
+	shader_source: ShaderSource,
+	render_mode: RenderMode,
+	/// Whether transparent materials and instances are blended rather than drawn opaque.
+	transparency: bool,
 	render_state_uniform: RenderState,
+	render_state_bind_group_layout: wgpu::BindGroupLayout,
 	render_state_bind_group:wgpu::BindGroup,
 }
 
 impl State {
-    async fn new(window: Window, filename: &str) -> Self {
+    async fn new(window: Window, options: &Options) -> Result<Self, Box<dyn std::error::Error>> {
 		let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
 			backends: wgpu::Backends::all(),
             ..Default::default()
@@ -65,7 +156,8 @@ impl State {
 		
 		let (device, queue) = adapter.request_device(
 			&wgpu::DeviceDescriptor {
-				features: wgpu::Features::POLYGON_MODE_LINE,
+				// adapter-specific format features unlock sample counts other than 1 and 4
+				features: adapter.features() & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
 				limits: wgpu::Limits::default(),
 				label: None,
 			},
@@ -75,8 +167,7 @@ impl State {
 		let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps.formats.iter()
 			.copied()
-			.filter(|f| f.is_srgb())
-			.next()
+			.find(|f| f.is_srgb())
 			.unwrap_or(surface_caps.formats[0]); // attempt to use the first rgb format, otherwise use whatever's available
 	
 		let size = window.inner_size();
@@ -94,25 +185,54 @@ impl State {
 
 		let depth_texture = Texture::new_depth_texture(&device, &config, "depth_texture");
 
-		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-			label: Some("Shader"),
-			source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-		});
+		let requested_samples = options.msaa.unwrap_or(4);
+		let msaa = Multisample::new(&device, &config, Hdr::FORMAT, requested_samples, Multisample::supported_sample_counts(&adapter, &device, Hdr::FORMAT));
+		if msaa.sample_count != requested_samples && options.msaa.is_some() {
+			eprintln!("{}x MSAA is not supported, using {}x", requested_samples, msaa.sample_count);
+		}
+
+		let material_bind_group_layout = Material::bind_group_layout(&device);
+		let geometry_bind_group_layout = Mesh::geometry_bind_group_layout(&device);
+
+		let is_ply = std::path::Path::new(&options.filename).extension().is_some_and(|e| e.eq_ignore_ascii_case("ply"));
+		let model = if is_ply {
+			Mesh::empty(&device, &queue, &material_bind_group_layout, &geometry_bind_group_layout)
+		} else {
+			Mesh::from_obj(&device, &queue, &options.filename, &material_bind_group_layout, &geometry_bind_group_layout)
+				.map_err(|e| format!("{}: {}", options.filename, e))?
+		};
+
+		// files with only vertices render as a point cloud
+		let points = if is_ply || options.points || model.is_empty() {
+			Some(PointCloud::load(&device, &options.filename).map_err(|e| format!("{}: {}", options.filename, e))?)
+		} else {
+			None
+		};
+		// points are drawn once, straight from the file
+		if points.is_some() && options.instances.is_some() {
+			return Err(format!("{}: --instances only applies to meshes, not point clouds", options.filename).into());
+		}
+
+		let placements = match &options.instances {
+			Some(path) => instance::load_placements(path).map_err(|e| format!("{}: {}", path, e))?,
+			None => vec![Instance::default()],
+		};
+		let instances = InstanceBuffer::new(&device, placements);
 
-		let model = Mesh::from_obj(&device, filename).unwrap();
-		let pos = model.bounding_box.center() + Vector3::new(0.0, 0.0, model.bounding_box.diag());
+		let scene_box = Self::scene_box(&model, &instances.instances, points.as_ref());
+		let pos = scene_box.center() + Vector3::new(0.0, 0.0, scene_box.diag());
 
 		let mut camera = Camera::new(
 			Matrix4::from_translation(pos),
 			config.width as f32 / config.height as f32,
 			70.0,
 			0.1,
-			100.0_f32.max(model.bounding_box.diag() * 2.0) // initialize zfar and zoom depending on size of model bounding box
+			100.0_f32.max(scene_box.diag() * 2.0) // initialize zfar and zoom depending on size of scene bounding box
 		);
 
-		camera.zoom = model.bounding_box.diag();
+		camera.zoom = scene_box.diag();
 
-		let camera_uniform = MatrixUniform::from_matrix4(camera.view_proj());
+		let camera_uniform = CameraUniform::from_camera(&camera);
 
 		let camera_buffer = device.create_buffer_init(
 			&wgpu::util::BufferInitDescriptor {
@@ -126,7 +246,17 @@ impl State {
 			entries: &[
 				wgpu::BindGroupLayoutEntry {
 					binding: 0,
-					visibility: wgpu::ShaderStages::VERTEX,
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
 					ty: wgpu::BindingType::Buffer {
 						ty: wgpu::BufferBindingType::Uniform,
 						has_dynamic_offset: false,
@@ -138,19 +268,33 @@ impl State {
 			label: Some("camera_bind_group_layout"),
 		});
 
+		// section planes apply to everything drawn from the camera, so they're bound with it
+		let section = Section::new(&device);
+
 		let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
 			layout: &camera_bind_group_layout,
 			entries: &[
 				wgpu::BindGroupEntry {
 					binding: 0,
 					resource: camera_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: section.buffer.as_entire_binding(),
 				}
 			],
 			label: Some("camera_bind_group"),
 		});
 
+		let default_mode = if options.fragment.is_some() { RenderMode::Custom } else { RenderMode::Solid };
+		let render_mode = options.mode.unwrap_or(default_mode);
 		let render_state_uniform = RenderState { 
-			render_mode: 0
+			render_mode: render_mode as i32,
+			wireframe_width: 1.5,
+			viewport: [config.width as f32, config.height as f32],
+			depth_range: [0.0, 1.0],
+			two_sided: 0,
+			ssao: 0,
 		};
 
 		let render_state_buffer = device.create_buffer_init(
@@ -161,6 +305,22 @@ impl State {
 			}
 		);
 
+		// point and spot lights default to reaching twice across the scene
+		let lights = match &options.lights {
+			Some(path) => light::load_lights(path, scene_box.diag().max(1.0) * 2.0).map_err(|e| format!("{}: {}", path, e))?,
+			None => Lights::default(),
+		};
+
+		let lights_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Lights Buffer"),
+				contents: bytemuck::cast_slice(&[lights.to_uniform(&camera)]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let shadow = ShadowMap::new(&device, &section.buffer, &geometry_bind_group_layout);
+
 		let render_state_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
 			entries: &[
 				wgpu::BindGroupLayoutEntry {
@@ -172,103 +332,300 @@ impl State {
 						min_binding_size: None,
 					},
 					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Depth,
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 4,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 5,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 6,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 7,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::Cube,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 8,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::Cube,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 9,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 10,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
 				}
 			],
 			label: Some("render_state_bind_group_layout"),
 		});
 
-		let render_state_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-			layout: &render_state_bind_group_layout,
+		let ssao = Ssao::new(&device, &config, &camera_bind_group_layout, &geometry_bind_group_layout, &depth_texture, scene_box.diag() * 0.05);
+
+		let environment = Environment::new(&device, &queue, Hdr::FORMAT, msaa.sample_count, options.environment.as_deref())
+			.map_err(|e| format!("{}: {}", options.environment.as_deref().unwrap_or_default(), e))?;
+		let matcap = matcap::load(&device, &queue, options.matcap.as_deref())
+			.map_err(|e| format!("{}: {}", options.matcap.as_deref().unwrap_or_default(), e))?;
+
+		let render_state_bind_group = Self::create_render_state_bind_group(&device, &render_state_bind_group_layout, &render_state_buffer, &lights_buffer, &shadow, &ssao, &environment, &matcap);
+
+		let point_style = PointStyle {
+			size_px: 3.0,
+			size_world: scene_box.diag() / 500.0,
+			world_units: false,
+			round: true,
+			vertex_color: points.as_ref().is_some_and(|p| p.has_color),
+		};
+
+		let point_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Point Style Buffer"),
+				contents: bytemuck::cast_slice(&[point_style.uniform([config.width as f32, config.height as f32], camera.focal())]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let point_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
 			entries: &[
-				wgpu::BindGroupEntry {
+				wgpu::BindGroupLayoutEntry {
 					binding: 0,
-					resource: render_state_buffer.as_entire_binding(),
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
 				}
 			],
-			label: Some("render_state_bind_group"),
+			label: Some("point_bind_group_layout"),
 		});
 
-		let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-			label: Some("Render Pipeline Layout"),
-			bind_group_layouts: &[
-				&camera_bind_group_layout,
-				&render_state_bind_group_layout,
+		let point_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &point_bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: point_buffer.as_entire_binding(),
+				}
 			],
-			push_constant_ranges: &[],
+			label: Some("point_bind_group"),
 		});
 
-		let targets = &[Some(wgpu::ColorTargetState {
-			format: config.format,
-			blend: Some(wgpu::BlendState::REPLACE),
-			write_mask: wgpu::ColorWrites::ALL,
-		})];
-
-		let mut pipeline_descriptor = wgpu::RenderPipelineDescriptor {
-			label: Some("Render Pipeline"),
-			layout: Some(&render_pipeline_layout),
-			vertex: wgpu::VertexState {
-				module: &shader,
-				entry_point: "vs_main", 
-				buffers: &[
-					Vertex::desc()
-				],
-			},
-			fragment: Some(wgpu::FragmentState {
-				module: &shader,
-				entry_point: "fs_main",
-				targets: targets,
-			}),
-			primitive: wgpu::PrimitiveState {
-				topology: wgpu::PrimitiveTopology::TriangleList, 
-				strip_index_format: None,
-				front_face: wgpu::FrontFace::Ccw, 
-				cull_mode: Some(wgpu::Face::Back),
-				polygon_mode: wgpu::PolygonMode::Fill,
-				unclipped_depth: false,
-				conservative: false,
-			},
-			depth_stencil: Some(wgpu::DepthStencilState {
-				format: texture::Texture::DEPTH_FORMAT,
-				depth_write_enabled: true,
-				depth_compare: wgpu::CompareFunction::Less, 
-				stencil: wgpu::StencilState::default(), 
-				bias: wgpu::DepthBiasState::default(),
-			}),
-			multisample: wgpu::MultisampleState {
-				count: 1, 
-				mask: !0, 
-				alpha_to_coverage_enabled: false, 
-			},
-			multiview: None,
-		};
+		// shaders from files are swapped in once everything is set up, so their errors can be shown
+		let pipelines = Pipelines::new(
+			&device,
+			Hdr::FORMAT,
+			&ShaderSource::built_in(),
+			&[&camera_bind_group_layout, &render_state_bind_group_layout, &material_bind_group_layout, &geometry_bind_group_layout],
+			&[&camera_bind_group_layout, &point_bind_group_layout],
+			msaa.sample_count,
+		);
 
-		let render_pipeline = device.create_render_pipeline(&pipeline_descriptor);
-		
-		pipeline_descriptor.primitive.polygon_mode = wgpu::PolygonMode::Line;
-		pipeline_descriptor.primitive.cull_mode = None;
-		let wireframe_render_pipeline = device.create_render_pipeline(&pipeline_descriptor);
+		let edl = EyeDomeLighting::new(&device, &config, &depth_texture);
+		let grid = Grid::new(&device, Hdr::FORMAT, msaa.sample_count, scene_box.diag() * 0.05);
+		let feature_lines = FeatureLines::new(&device, Hdr::FORMAT, msaa.sample_count, &camera_bind_group_layout, &geometry_bind_group_layout, &model);
+		let outline = Outline::new(&device, &config, &camera_bind_group_layout, &geometry_bind_group_layout);
+		let hdr = Hdr::new(&device, &config, options.tonemapper.unwrap_or(Tonemapper::Aces));
+
+		let overlays = Overlays::new(&device);
 
-		Self {
+		let mut state = Self {
             window,
+			title: options.filename.clone(),
             surface,
 			model,
+			instances,
+			points,
+			point_style,
+			point_buffer,
+			point_bind_group,
+			edl,
+			setting: Setting::PointSize,
             device,
             queue,
             config,
             size,
 			depth_texture,
-			render_pipeline,
-			wireframe_render_pipeline,
+			pipelines,
+			msaa,
+			overlays,
+			lights,
+			lights_buffer,
+			shadow,
+			ssao,
+			environment,
+			matcap,
+			grid,
+			feature_lines,
+			outline,
+			hdr,
+			section,
+			explode: ExplodedView::default(),
+			selection: Selection::default(),
+			scene_box,
+			cull_stats: CullStats::default(),
+			show_stats: false,
 			camera,
 			camera_buffer,
 			camera_uniform,
 			camera_bind_group,
+			render_mode,
+			transparency: true,
 			render_state_uniform,
 			render_state_buffer,
+			shader_source: ShaderSource::new(options.shader.as_deref(), options.fragment.as_deref()),
+			render_state_bind_group_layout,
 			render_state_bind_group
-        }
+        };
+		if state.shader_source.is_external() {
+			state.reload_shader();
+		}
+		Ok(state)
     }
 
+	/// Bounds of everything drawn, with the model as currently exploded.
+	fn scene_box(model: &Mesh, instances: &[Instance], points: Option<&PointCloud>) -> BoundingBox {
+		let model_box = model.exploded_box();
+		let scene_box = instances.iter().fold(BoundingBox::empty(), |b, i| b.union(&model_box.transform(&i.transform)));
+		match points {
+			Some(points) => scene_box.union(&points.bounds.aabb),
+			None => scene_box,
+		}
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn create_render_state_bind_group(
+		device: &wgpu::Device,
+		layout: &wgpu::BindGroupLayout,
+		render_state_buffer: &wgpu::Buffer,
+		lights_buffer: &wgpu::Buffer,
+		shadow: &ShadowMap,
+		ssao: &Ssao,
+		environment: &Environment,
+		matcap: &Texture,
+	) -> wgpu::BindGroup {
+		device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: render_state_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: lights_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: shadow.buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: wgpu::BindingResource::TextureView(&shadow.map.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 4,
+					resource: wgpu::BindingResource::Sampler(&shadow.map.sampler),
+				},
+				wgpu::BindGroupEntry {
+					binding: 5,
+					resource: wgpu::BindingResource::TextureView(&ssao.ao.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 6,
+					resource: environment.buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 7,
+					resource: wgpu::BindingResource::TextureView(&environment.irradiance.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 8,
+					resource: wgpu::BindingResource::TextureView(&environment.specular.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 9,
+					resource: wgpu::BindingResource::Sampler(&environment.specular.sampler),
+				},
+				wgpu::BindGroupEntry {
+					binding: 10,
+					resource: wgpu::BindingResource::TextureView(&matcap.view),
+				}
+			],
+			label: Some("render_state_bind_group"),
+		})
+	}
+
     pub fn window(&self) -> &Window {
         &self.window
     }
@@ -281,38 +638,294 @@ impl State {
 			self.surface.configure(&self.device, &self.config);
 			self.camera.aspect = self.config.width as f32 / self.config.height as f32;
 			self.depth_texture = Texture::new_depth_texture(&self.device, &self.config, "depth_texture");
+			self.msaa.resize(&self.device, &self.config);
+			self.edl.resize(&self.device, &self.config, &self.depth_texture);
+			// the ambient occlusion texture is part of the render state group
+			self.ssao.resize(&self.device, &self.config, &self.depth_texture);
+			self.outline.resize(&self.device, &self.config);
+			self.hdr.resize(&self.device, &self.config);
+			self.render_state_bind_group = Self::create_render_state_bind_group(
+				&self.device,
+				&self.render_state_bind_group_layout,
+				&self.render_state_buffer,
+				&self.lights_buffer,
+				&self.shadow,
+				&self.ssao,
+				&self.environment,
+				&self.matcap,
+			);
 		}
 	}
 
     fn input(&mut self, event: &WindowEvent) -> bool { 
 		self.camera.input(event);
-		if let WindowEvent::KeyboardInput { input, .. } = event {
-			if let Some(keycode) = input.virtual_keycode {
-				if input.state == ElementState::Pressed && keycode == VirtualKeyCode::V {
-					self.render_state_uniform.render_mode = (self.render_state_uniform.render_mode + 1) % 2;
-
-					self.queue.write_buffer(
-						&self.render_state_buffer,
-						0,
-						bytemuck::cast_slice(&[self.render_state_uniform]),
-					);
-				}
+		let viewport = [self.config.width as f32, self.config.height as f32];
+		if let Some(status) = self.selection.input(event, &self.camera, viewport, &self.model, &self.instances.instances) {
+			self.show(&status);
+		}
+		if let WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(keycode), .. }, .. } = event {
+			if let Some(status) = self.key_input(*keycode) {
+				self.show(&status);
 			}
 		}
 
 		false
 	}
 
+	/// Hands a key to the feature it belongs to, or handles it here if it's about the view as a
+	/// whole. Returns the status to show.
+	fn key_input(&mut self, keycode: VirtualKeyCode) -> Option<String> {
+		let status = self.lights.input(keycode, &self.camera)
+			.or_else(|| self.section.input(keycode, &self.device, &self.camera, &self.scene_box))
+			.or_else(|| self.overlays.input(keycode, &self.device, &self.model, &self.instances.instances))
+			.or_else(|| self.grid.input(keycode, &self.camera))
+			.or_else(|| self.point_style.input(keycode))
+			.or_else(|| self.edl.input(keycode))
+			.or_else(|| self.ssao.input(keycode))
+			.or_else(|| self.shadow.input(keycode))
+			.or_else(|| self.feature_lines.input(keycode));
+		if status.is_some() {
+			// section planes may have come or gone
+			self.update_culling();
+			return status;
+		}
+
+		match keycode {
+			VirtualKeyCode::V | VirtualKeyCode::M => {
+				self.render_mode = if keycode == VirtualKeyCode::V { self.render_mode.next() } else { self.render_mode.next_debug() };
+				self.render_state_uniform.render_mode = self.render_mode as i32;
+				Some(self.render_mode.name().into())
+			}
+			VirtualKeyCode::F3 => {
+				self.show_stats = !self.show_stats;
+				if !self.show_stats {
+					self.window.set_title(&self.title);
+				}
+				self.show_stats.then(|| self.stats())
+			}
+			VirtualKeyCode::R => {
+				if !self.shader_source.is_external() {
+					return Some("shaders are built in, see --shader and --fragment".into());
+				}
+				self.reload_shader();
+				None
+			}
+			VirtualKeyCode::T => {
+				self.transparency = !self.transparency;
+				Some(settings::toggled("transparency", self.transparency))
+			}
+			VirtualKeyCode::F => {
+				self.render_state_uniform.two_sided ^= 1;
+				self.update_culling();
+				Some(settings::toggled("two-sided", self.render_state_uniform.two_sided != 0))
+			}
+			VirtualKeyCode::Tab => {
+				self.setting = self.setting.next(&self.settings())?;
+				Some(self.setting_status())
+			}
+			VirtualKeyCode::Minus => self.adjust_setting(-1.0),
+			VirtualKeyCode::Equals => self.adjust_setting(1.0),
+			_ => None,
+		}
+	}
+
     fn update(&mut self) {
+		if self.shader_source.changed() {
+			self.reload_shader();
+		}
 		self.camera.update();
-        self.camera_uniform = MatrixUniform::from_matrix4(self.camera.view_proj());
+		self.animate_explode();
+        self.camera_uniform = CameraUniform::from_camera(&self.camera);
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+		self.queue.write_buffer(
+			&self.lights_buffer,
+			0,
+			bytemuck::cast_slice(&[self.lights.to_uniform(&self.camera)]),
+		);
+		self.shadow.update(&self.queue, &self.lights, &self.scene_box);
+
+		let view_box = self.scene_box.transform(&self.camera.view());
+		self.render_state_uniform.viewport = [self.config.width as f32, self.config.height as f32];
+		self.render_state_uniform.depth_range = [(-view_box.max.z).max(self.camera.znear), (-view_box.min.z).max(self.camera.znear)];
+		self.render_state_uniform.ssao = self.ssao.enabled as u32;
+		self.queue.write_buffer(
+			&self.render_state_buffer,
+			0,
+			bytemuck::cast_slice(&[self.render_state_uniform]),
+		);
+
+		let viewport = [self.config.width as f32, self.config.height as f32];
+		self.queue.write_buffer(
+			&self.point_buffer,
+			0,
+			bytemuck::cast_slice(&[self.point_style.uniform(viewport, self.camera.focal())]),
+		);
+		self.edl.update(&self.queue, self.camera.znear, self.camera.zfar);
+		self.ssao.update(&self.queue, &self.camera);
+		self.environment.update(&self.queue, &self.camera);
+		self.grid.update(&self.queue, &self.camera, self.render_state_uniform.viewport);
+		self.feature_lines.update(&self.queue, self.render_state_uniform.viewport);
+		self.outline.update(&self.queue);
+		self.hdr.update(&self.queue, self.render_mode.is_raw());
+		self.section.update(&self.queue, self.render_mode.draws_caps());
     }
 
+	/// Settings that have an effect on what's shown, in the order Tab steps through them.
+	fn settings(&self) -> Vec<Setting> {
+		let mut settings = vec![];
+		if self.points.is_some() {
+			settings.extend(self.point_style.settings());
+			settings.extend(self.edl.settings());
+		} else {
+			if self.render_mode.is_lit() {
+				settings.extend(self.lights.settings());
+				settings.extend(self.shadow.settings());
+			}
+			if self.render_mode.shows_occlusion() {
+				settings.extend(self.ssao.settings());
+			}
+			if self.render_mode.draws_edges() {
+				settings.push(Setting::WireframeWidth);
+			}
+			settings.extend(self.feature_lines.settings());
+			if self.render_mode.draws_caps() {
+				settings.extend(self.section.settings());
+			}
+			if self.model.submeshes.len() > 1 {
+				settings.extend(self.explode.settings());
+			}
+			settings.extend(self.outline.settings());
+		}
+		settings.extend(self.environment.settings());
+		if !self.render_mode.is_raw() {
+			settings.extend(self.hdr.settings());
+		}
+		if self.msaa.supported.len() > 1 {
+			settings.push(Setting::Msaa);
+		}
+		settings
+	}
+
+	/// Steps the selected setting. If it stopped applying when the mode or what's shown changed,
+	/// the first that does apply is selected instead.
+	fn adjust_setting(&mut self, steps: f32) -> Option<String> {
+		let settings = self.settings();
+		if !settings.contains(&self.setting) {
+			self.setting = *settings.first()?;
+			return Some(self.setting_status());
+		}
+
+		let setting = self.setting;
+		let adjusted = self.point_style.adjust(setting, steps)
+			|| self.edl.adjust(setting, steps)
+			|| self.lights.adjust(setting, steps)
+			|| self.shadow.adjust(setting, steps)
+			|| self.ssao.adjust(setting, steps)
+			|| self.feature_lines.adjust(setting, steps)
+			|| self.section.adjust(setting, steps)
+			|| self.explode.adjust(setting, steps)
+			|| self.outline.adjust(setting, steps)
+			|| self.environment.adjust(setting, steps)
+			|| self.hdr.adjust(setting, steps);
+		if !adjusted {
+			match setting {
+				Setting::Msaa => self.set_sample_count(self.msaa.step(steps as i32)),
+				Setting::WireframeWidth => {
+					let width = &mut self.render_state_uniform.wireframe_width;
+					*width = (*width + steps * 0.5).clamp(0.5, 10.0);
+				}
+				_ => {}
+			}
+		}
+		// whether cuts are capped decides whether back faces are drawn
+		self.update_culling();
+		Some(self.setting_status())
+	}
+
+	fn set_sample_count(&mut self, requested: u32) {
+		let sample_count = self.msaa.set_sample_count(&self.device, &self.config, requested);
+		if sample_count != self.pipelines.sample_count {
+			self.pipelines.set_sample_count(&self.device, sample_count);
+			self.environment.set_sample_count(&self.device, sample_count);
+			self.grid.set_sample_count(&self.device, sample_count);
+			self.feature_lines.set_sample_count(&self.device, sample_count);
+		}
+	}
+
+	fn animate_explode(&mut self) {
+		if let Some(amount) = self.explode.animate() {
+			self.model.explode(&self.queue, amount);
+			self.scene_box = Self::scene_box(&self.model, &self.instances.instances, self.points.as_ref());
+			self.overlays.update(&self.device, &self.model, &self.instances.instances, self.explode.settled());
+		}
+	}
+
+	/// Rebuilds the mesh pipelines from the shader files. Errors are shown in the title and
+	/// printed in full, and the last shader that compiled stays in use.
+	fn reload_shader(&mut self) {
+		let result = self.shader_source.read().and_then(|source| self.pipelines.set_shader(&self.device, &source));
+		match result {
+			Ok(()) => self.show("shader loaded"),
+			Err(error) => {
+				eprintln!("{}", error);
+				self.show(&format!("shader error: {}", shader_source::summary(&error)));
+			}
+		}
+	}
+
+	/// Back faces are drawn when asked for, and where they might show through a capped cut.
+	fn update_culling(&mut self) {
+		let two_sided = self.render_state_uniform.two_sided != 0 || (self.section.cap.is_some() && !self.section.planes.is_empty());
+		if two_sided != self.pipelines.two_sided {
+			self.pipelines.set_two_sided(&self.device, two_sided);
+		}
+	}
+
+	fn show(&self, status: &str) {
+		self.window.set_title(&format!("{} - {}", self.title, status));
+	}
+
+	fn stats(&self) -> String {
+		let stats = &self.cull_stats;
+		format!(
+			"drawn {} of {} object instances ({} culled), {} triangles",
+			stats.drawn, stats.drawn + stats.culled, stats.culled, stats.triangles,
+		)
+	}
+
+	fn setting_status(&self) -> String {
+		let setting = self.setting;
+		let value = self.point_style.value(setting)
+			.or_else(|| self.edl.value(setting))
+			.or_else(|| self.lights.value(setting))
+			.or_else(|| self.shadow.value(setting))
+			.or_else(|| self.ssao.value(setting))
+			.or_else(|| self.feature_lines.value(setting))
+			.or_else(|| self.section.value(setting))
+			.or_else(|| self.explode.value(setting))
+			.or_else(|| self.outline.value(setting))
+			.or_else(|| self.environment.value(setting))
+			.or_else(|| self.hdr.value(setting))
+			.unwrap_or_else(|| match setting {
+				Setting::Msaa => format!("{}x", self.msaa.sample_count),
+				Setting::WireframeWidth => format!("{} px", self.render_state_uniform.wireframe_width),
+				_ => "-".into(),
+			});
+		format!("{}: {}", setting.name(), value)
+	}
+
+	fn draw_model<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, draws: &[Draw]) {
+		if self.render_mode.fetches_triangles() {
+			self.model.draw_triangles(render_pass, &self.instances, draws);
+		} else {
+			self.model.draw(render_pass, &self.instances, draws);
+		}
+	}
+
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
 		let output = self.surface.get_current_texture()?;
 		let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -320,12 +933,50 @@ impl State {
 			label: Some("Render Encoder"),
 		});
 
+		let (opaque, transparent) = if self.points.is_some() {
+			(vec![], vec![])
+		} else if self.render_mode == RenderMode::XRay {
+			// nothing hides anything, so it all goes over the sky like blended submeshes
+			(vec![], self.model.all_draws(self.instances.len()))
+		} else if self.transparency && self.render_mode.blends() {
+			let materials = &self.model.materials;
+			transparency::split_draws(&self.model.submeshes, |m| materials[m].is_transparent(), &self.instances.instances, self.camera.transform.w.truncate())
+		} else {
+			(self.model.all_draws(self.instances.len()), vec![])
+		};
+
+		// submesh instances entirely outside the view aren't drawn
+		let frustum = Frustum::from_matrix(&self.camera.view_proj());
+		let mut stats = CullStats::default();
+		let opaque = frustum::cull(&self.model.submeshes, &self.instances.instances, &opaque, &frustum, &mut stats);
+		let transparent = frustum::cull(&self.model.submeshes, &self.instances.instances, &transparent, &frustum, &mut stats);
+		let visible = [opaque.as_slice(), transparent.as_slice()].concat();
+		if stats != self.cull_stats {
+			self.cull_stats = stats;
+			if self.show_stats {
+				self.show(&self.stats());
+			}
+		}
+
+		if self.points.is_none() {
+			// shadows can fall from outside the view, so the shadow map has everything
+			self.shadow.draw(&mut encoder, &self.model, &self.instances);
+			if self.ssao.enabled {
+				self.ssao.draw(&mut encoder, &self.camera_bind_group, &self.depth_texture, &self.model, &self.instances, &visible);
+			}
+		}
+
+		// eye-dome lighting shades point clouds from the depth buffer in a second pass
+		let edl = self.edl.enabled && self.points.is_some();
+		let scene_view = if edl { &self.edl.target.view } else { &self.hdr.target.view };
+		let (color_view, resolve_target) = self.msaa.color_attachment(scene_view);
+
 		{ // render pass must not exist to finish encoder (because render_pass borrows encoder)
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: color_view,
+                    resolve_target,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
                             r: 0.02,
@@ -337,7 +988,7 @@ impl State {
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-					view: &self.depth_texture.view,
+					view: self.msaa.depth_attachment(&self.depth_texture),
 					depth_ops: Some(wgpu::Operations {
 						load: wgpu::LoadOp::Clear(1.0),
 						store: wgpu::StoreOp::Store,
@@ -348,16 +999,64 @@ impl State {
                 timestamp_writes: None,
             });
 
-			let pipeline = match &self.render_state_uniform.render_mode {
-				1 => &self.wireframe_render_pipeline,
-				_ => &self.render_pipeline,
+			let pipeline = match self.render_mode {
+				RenderMode::Wireframe => &self.pipelines.wireframe,
+				RenderMode::HiddenLine => &self.pipelines.hidden_line_fill,
+				mode if mode.fetches_triangles() => &self.pipelines.triangles,
+				_ => &self.pipelines.render,
+			};
+			let transparent_pipeline = match self.render_mode {
+				RenderMode::XRay => &self.pipelines.xray,
+				mode if mode.fetches_triangles() => &self.pipelines.transparent_triangles,
+				_ => &self.pipelines.transparent,
 			};
 
-            render_pass.set_pipeline(&pipeline);
 			render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-			render_pass.set_bind_group(1, &self.render_state_bind_group, &[]);
-			self.model.draw(&mut render_pass);
+			if let Some(points) = &self.points {
+				render_pass.set_pipeline(&self.pipelines.point);
+				render_pass.set_bind_group(1, &self.point_bind_group, &[]);
+				points.draw(&mut render_pass);
+			} else {
+				render_pass.set_pipeline(pipeline);
+				render_pass.set_bind_group(1, &self.render_state_bind_group, &[]);
+				self.draw_model(&mut render_pass, &opaque);
+				if self.render_mode == RenderMode::HiddenLine {
+					// the fill has written depth, so edges behind it fail the depth test
+					render_pass.set_pipeline(&self.pipelines.hidden_line_edges);
+					self.draw_model(&mut render_pass, &opaque);
+				}
+			}
+
+			self.environment.draw_sky(&mut render_pass);
+			self.grid.draw(&mut render_pass);
+
+			if !transparent.is_empty() {
+				render_pass.set_pipeline(transparent_pipeline);
+				render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+				render_pass.set_bind_group(1, &self.render_state_bind_group, &[]);
+				self.draw_model(&mut render_pass, &transparent);
+			}
+
+			if self.points.is_none() {
+				self.feature_lines.draw(&mut render_pass, &self.camera_bind_group, &self.model, &self.instances, &visible);
+			}
+
+			render_pass.set_pipeline(&self.pipelines.line);
+			render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+			if self.grid.enabled {
+				self.grid.origin.draw(&mut render_pass);
+			}
+			self.overlays.draw(&mut render_pass);
+			self.section.draw_planes(&mut render_pass);
         }
+
+		if edl {
+			self.msaa.resolve_depth(&mut encoder, &self.depth_texture);
+			self.edl.draw(&mut encoder, &self.hdr.target.view);
+		}
+		self.hdr.draw(&mut encoder, &view);
+
+		self.outline.draw(&mut encoder, &view, &self.camera_bind_group, &self.model, &self.instances, self.selection.selected.as_ref(), self.selection.hovered.as_ref());
 	
 		self.queue.submit(std::iter::once(encoder.finish()));
 		output.present();
@@ -366,14 +1065,14 @@ impl State {
     }
 }
 
-pub async fn run(filename: &str) {
+pub async fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
-	window.set_title(filename);
+	window.set_title(&options.filename);
 
-	let mut state = State::new(window, filename).await;
+	let mut state = State::new(window, &options).await?;
 
     event_loop.run(move |event, _, control_flow| {
 		match event {
@@ -392,25 +1091,23 @@ pub async fn run(filename: &str) {
 			Event::WindowEvent {
 				ref event,
 				window_id,
-			} if window_id == state.window().id() => if !state.input(event) {
-				match event {
-					WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
-						input:
-							KeyboardInput {
-								state: ElementState::Pressed,
-								virtual_keycode: Some(VirtualKeyCode::Escape),
-								..
-							},
-						..
-					} => *control_flow = ControlFlow::Exit,
-					WindowEvent::Resized(physical_size) => {
-						state.resize(*physical_size);
-					}
-					WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-						state.resize(**new_inner_size);
-					}
-					_ => {}
+			} if window_id == state.window().id() && !state.input(event) => match event {
+				WindowEvent::CloseRequested | WindowEvent::KeyboardInput {
+					input:
+						KeyboardInput {
+							state: ElementState::Pressed,
+							virtual_keycode: Some(VirtualKeyCode::Escape),
+							..
+						},
+					..
+				} => *control_flow = ControlFlow::Exit,
+				WindowEvent::Resized(physical_size) => {
+					state.resize(*physical_size);
+				}
+				WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+					state.resize(**new_inner_size);
 				}
+				_ => {}
 			}
 			_ => {}
 		}
@@ -0,0 +1,435 @@
+use std::path::Path;
+
+use bytemuck::Zeroable;
+use cgmath::{Deg, InnerSpace, Matrix3, Vector3};
+use serde::Deserialize;
+use winit::event::VirtualKeyCode;
+
+use crate::camera::Camera;
+use crate::settings::Setting;
+
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKind {
+	Directional,
+	Point,
+	Spot,
+}
+
+impl LightKind {
+	pub fn next(self) -> Self {
+		match self {
+			LightKind::Directional => LightKind::Point,
+			LightKind::Point => LightKind::Spot,
+			LightKind::Spot => LightKind::Directional,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			LightKind::Directional => "directional",
+			LightKind::Point => "point",
+			LightKind::Spot => "spot",
+		}
+	}
+}
+
+/// A light in world space. `direction` is the way the light travels; `range` is where point and
+/// spot lights fade out, and the spot cone fades from `inner_angle` to `outer_angle`.
+/// Point and spot lights fall off with the square of the distance, scaled so that `intensity`
+/// is their brightness at half their range.
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+	pub kind: LightKind,
+	pub position: Vector3<f32>,
+	pub direction: Vector3<f32>,
+	pub color: [f32; 3],
+	pub intensity: f32,
+	pub range: f32,
+	pub inner_angle: Deg<f32>,
+	pub outer_angle: Deg<f32>,
+}
+
+impl Light {
+	pub fn directional(direction: Vector3<f32>, color: [f32; 3], intensity: f32) -> Self {
+		Self {
+			kind: LightKind::Directional,
+			position: Vector3::new(0.0, 0.0, 0.0),
+			direction: direction.normalize(),
+			color,
+			intensity,
+			range: 10.0,
+			inner_angle: Deg(20.0),
+			outer_angle: Deg(30.0),
+		}
+	}
+
+	/// Turns the light's direction by `yaw` around the world up axis and by `pitch` up or down.
+	pub fn rotate(&mut self, yaw: Deg<f32>, pitch: Deg<f32>) {
+		let side = self.direction.cross(Vector3::unit_y());
+		let pitched = if side.magnitude2() > 1e-6 {
+			Matrix3::from_axis_angle(side.normalize(), pitch) * self.direction
+		} else {
+			self.direction
+		};
+		self.direction = (Matrix3::from_angle_y(yaw) * pitched).normalize();
+	}
+
+	fn to_raw(self) -> LightRaw {
+		let kind = match self.kind {
+			LightKind::Directional => 0.0,
+			LightKind::Point => 1.0,
+			LightKind::Spot => 2.0,
+		};
+		LightRaw {
+			position: self.position.extend(kind).into(),
+			direction: self.direction.normalize().extend(self.range).into(),
+			color: [self.color[0], self.color[1], self.color[2], self.intensity],
+			cone: [
+				self.inner_angle.0.to_radians().cos(),
+				self.outer_angle.0.to_radians().cos(),
+				0.0,
+				0.0,
+			],
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightRaw {
+	position: [f32; 4],
+	direction: [f32; 4],
+	color: [f32; 4],
+	cone: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+	ambient: [f32; 4],
+	count: u32,
+	_padding: [u32; 3],
+	lights: [LightRaw; MAX_LIGHTS],
+}
+
+/// The light rig: user lights plus an optional headlight that follows the camera.
+pub struct Lights {
+	pub lights: Vec<Light>,
+	pub ambient: [f32; 3],
+	pub headlight: bool,
+	pub headlight_intensity: f32,
+	pub selected: usize,
+	/// Color temperature in kelvin the temperature setting last gave the selected light.
+	pub temperature: f32,
+}
+
+impl Default for Lights {
+	/// One white key light, matching the viewer's original fixed light.
+	fn default() -> Self {
+		Self {
+			lights: vec![Light::directional(Vector3::new(0.3, -1.0, 0.0), [1.0, 1.0, 1.0], 1.0)],
+			ambient: [0.03, 0.03, 0.03],
+			headlight: false,
+			headlight_intensity: 0.8,
+			selected: 0,
+			temperature: 6500.0,
+		}
+	}
+}
+
+impl Lights {
+	pub fn selected_mut(&mut self) -> Option<&mut Light> {
+		self.lights.get_mut(self.selected)
+	}
+
+	/// Adds a light if there's room for it (and the headlight), and selects it.
+	pub fn add(&mut self, light: Light) -> bool {
+		if self.lights.len() + 1 >= MAX_LIGHTS {
+			return false;
+		}
+		self.lights.push(light);
+		self.selected = self.lights.len() - 1;
+		true
+	}
+
+	pub fn remove_selected(&mut self) {
+		if self.selected < self.lights.len() {
+			self.lights.remove(self.selected);
+			self.selected = self.selected.min(self.lights.len().saturating_sub(1));
+		}
+	}
+
+	pub fn select_next(&mut self) {
+		if !self.lights.is_empty() {
+			self.selected = (self.selected + 1) % self.lights.len();
+		}
+	}
+
+	/// L selects the next light, H turns the headlight on and off, Y changes the selected light's
+	/// kind, Insert adds a point light at the eye and Delete removes the selected light. Home
+	/// moves the selected light to the eye, facing the same way, and the arrow keys turn it.
+	/// Returns the status to show, `None` for keys that aren't the lights'.
+	pub fn input(&mut self, keycode: VirtualKeyCode, camera: &Camera) -> Option<String> {
+		let eye = camera.transform.w.truncate();
+		let forward = -camera.transform.z.truncate();
+		match keycode {
+			VirtualKeyCode::L => self.select_next(),
+			VirtualKeyCode::H => self.headlight = !self.headlight,
+			VirtualKeyCode::Y => if let Some(light) = self.selected_mut() {
+				light.kind = light.kind.next();
+			},
+			VirtualKeyCode::Insert => {
+				let mut light = Light::directional(forward, [1.0, 1.0, 1.0], 1.0);
+				light.kind = LightKind::Point;
+				light.position = eye;
+				light.range = camera.zoom.max(1.0) * 4.0;
+				self.add(light);
+			}
+			VirtualKeyCode::Delete => self.remove_selected(),
+			VirtualKeyCode::Home => if let Some(light) = self.selected_mut() {
+				light.position = eye;
+				light.direction = forward;
+			},
+			VirtualKeyCode::Left => self.rotate_selected(Deg(5.0), Deg(0.0)),
+			VirtualKeyCode::Right => self.rotate_selected(Deg(-5.0), Deg(0.0)),
+			VirtualKeyCode::Up => self.rotate_selected(Deg(0.0), Deg(5.0)),
+			VirtualKeyCode::Down => self.rotate_selected(Deg(0.0), Deg(-5.0)),
+			_ => return None,
+		}
+		Some(self.status())
+	}
+
+	fn rotate_selected(&mut self, yaw: Deg<f32>, pitch: Deg<f32>) {
+		if let Some(light) = self.selected_mut() {
+			light.rotate(yaw, pitch);
+		}
+	}
+
+	pub fn status(&self) -> String {
+		let headlight = if self.headlight { ", headlight on" } else { "" };
+		match self.lights.get(self.selected) {
+			Some(light) => format!("light {}/{}: {}{}", self.selected + 1, self.lights.len(), light.kind.name(), headlight),
+			None => format!("no lights{}", headlight),
+		}
+	}
+
+	/// Settings of the selected light, and the ambient light.
+	pub fn settings(&self) -> &'static [Setting] {
+		match self.lights.get(self.selected) {
+			Some(light) if light.kind == LightKind::Spot => &[Setting::LightIntensity, Setting::LightTemperature, Setting::SpotAngle, Setting::Ambient],
+			Some(_) => &[Setting::LightIntensity, Setting::LightTemperature, Setting::Ambient],
+			None => &[Setting::Ambient],
+		}
+	}
+
+	/// Steps `setting` if it's one of the lights', returning whether it was.
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::LightIntensity => if let Some(light) = self.selected_mut() {
+				light.intensity = (light.intensity * 1.25_f32.powf(steps)).max(0.01);
+			},
+			Setting::LightTemperature => {
+				self.temperature = (self.temperature + steps * 500.0).clamp(1000.0, 20000.0);
+				let color = color_temperature(self.temperature);
+				if let Some(light) = self.selected_mut() {
+					light.color = color;
+				}
+			}
+			Setting::SpotAngle => if let Some(light) = self.selected_mut() {
+				let outer = (light.outer_angle.0 + steps * 5.0).clamp(5.0, 85.0);
+				light.inner_angle = Deg(outer * 2.0 / 3.0);
+				light.outer_angle = Deg(outer);
+			},
+			Setting::Ambient => self.ambient = self.ambient.map(|a| (a + steps * 0.01).clamp(0.0, 1.0)),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		let light = self.lights.get(self.selected);
+		match setting {
+			Setting::LightIntensity => Some(light.map_or("-".into(), |l| format!("{}", l.intensity))),
+			Setting::LightTemperature => Some(format!("{} K", self.temperature)),
+			Setting::SpotAngle => Some(light.map_or("-".into(), |l| format!("{}°", l.outer_angle.0))),
+			Setting::Ambient => Some(format!("{}", self.ambient[0])),
+			_ => None,
+		}
+	}
+
+	pub fn to_uniform(&self, camera: &Camera) -> LightsUniform {
+		let mut raw: Vec<LightRaw> = self.lights.iter().take(MAX_LIGHTS).map(|l| l.to_raw()).collect();
+		if self.headlight && raw.len() < MAX_LIGHTS {
+			let forward = -camera.transform.z.truncate();
+			raw.push(Light::directional(forward, [1.0, 1.0, 1.0], self.headlight_intensity).to_raw());
+		}
+
+		let mut lights = [LightRaw::zeroed(); MAX_LIGHTS];
+		lights[..raw.len()].copy_from_slice(&raw);
+		LightsUniform {
+			ambient: [self.ambient[0], self.ambient[1], self.ambient[2], 0.0],
+			count: raw.len() as u32,
+			_padding: [0; 3],
+			lights,
+		}
+	}
+}
+
+/// Color of a black body at `kelvin`, approximated per Tanner Helland's fit.
+pub fn color_temperature(kelvin: f32) -> [f32; 3] {
+	let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+	let r = if t <= 66.0 { 255.0 } else { 329.699 * (t - 60.0).powf(-0.133_205) };
+	let g = if t <= 66.0 { 99.470_8 * t.ln() - 161.119_57 } else { 288.122_16 * (t - 60.0).powf(-0.075_514_85) };
+	let b = if t >= 66.0 { 255.0 } else if t <= 19.0 { 0.0 } else { 138.517_73 * (t - 10.0).ln() - 305.044_8 };
+	[r, g, b].map(|c: f32| (c / 255.0).clamp(0.0, 1.0))
+}
+
+#[derive(Deserialize)]
+struct LightsFile {
+	ambient: Option<[f32; 3]>,
+	headlight: Option<bool>,
+	lights: Vec<LightEntry>,
+}
+
+#[derive(Deserialize)]
+struct LightEntry {
+	#[serde(rename = "type")]
+	kind: LightKind,
+	position: Option<[f32; 3]>,
+	direction: Option<[f32; 3]>,
+	color: Option<[f32; 3]>,
+	intensity: Option<f32>,
+	range: Option<f32>,
+	inner_angle: Option<f32>,
+	outer_angle: Option<f32>,
+}
+
+/// Loads a light rig from JSON, e.g.
+/// `{"ambient": [0.05, 0.05, 0.05], "headlight": true, "lights": [{"type": "spot", "position": [0, 5, 0], "direction": [0, -1, 0], "outer_angle": 40}]}`.
+/// Unset fields fall back to a white, unit-intensity light pointing down with a range of `default_range`.
+pub fn load_lights<P: AsRef<Path>>(path: P, default_range: f32) -> Result<Lights, Box<dyn std::error::Error>> {
+	parse_lights(&std::fs::read_to_string(path)?, default_range)
+}
+
+fn parse_lights(text: &str, default_range: f32) -> Result<Lights, Box<dyn std::error::Error>> {
+	let file: LightsFile = serde_json::from_str(text)?;
+	if file.lights.len() >= MAX_LIGHTS {
+		return Err(format!("at most {} lights are supported", MAX_LIGHTS - 1).into());
+	}
+
+	let defaults = Lights::default();
+	Ok(Lights {
+		lights: file.lights.into_iter().map(|e| Light {
+			kind: e.kind,
+			position: Vector3::from(e.position.unwrap_or([0.0; 3])),
+			direction: Vector3::from(e.direction.unwrap_or([0.0, -1.0, 0.0])).normalize(),
+			color: e.color.unwrap_or([1.0; 3]),
+			intensity: e.intensity.unwrap_or(1.0),
+			range: e.range.unwrap_or(default_range),
+			inner_angle: Deg(e.inner_angle.unwrap_or(20.0)),
+			outer_angle: Deg(e.outer_angle.unwrap_or(30.0)),
+		}).collect(),
+		ambient: file.ambient.unwrap_or(defaults.ambient),
+		headlight: file.headlight.unwrap_or(defaults.headlight),
+		..defaults
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Matrix4, SquareMatrix};
+
+	use super::*;
+
+	fn close(a: Vector3<f32>, b: [f32; 3]) -> bool {
+		(a - Vector3::from(b)).magnitude() < 1e-5
+	}
+
+	#[test]
+	fn parses_each_kind() {
+		let text = r#"{"ambient": [0.1, 0.2, 0.3], "headlight": true, "lights": [
+			{"type": "directional", "direction": [0, 0, -2], "color": [1, 0.5, 0], "intensity": 2},
+			{"type": "point", "position": [1, 2, 3], "range": 4},
+			{"type": "spot", "position": [0, 5, 0], "inner_angle": 10, "outer_angle": 40}
+		]}"#;
+		let lights = parse_lights(text, 20.0).unwrap();
+		assert_eq!(lights.ambient, [0.1, 0.2, 0.3]);
+		assert!(lights.headlight);
+		assert_eq!(lights.lights.len(), 3);
+
+		let directional = &lights.lights[0];
+		assert_eq!(directional.kind, LightKind::Directional);
+		assert!(close(directional.direction, [0.0, 0.0, -1.0]));
+		assert_eq!(directional.color, [1.0, 0.5, 0.0]);
+		assert_eq!(directional.intensity, 2.0);
+
+		let point = &lights.lights[1];
+		assert_eq!(point.kind, LightKind::Point);
+		assert!(close(point.position, [1.0, 2.0, 3.0]));
+		assert_eq!(point.range, 4.0);
+
+		let spot = &lights.lights[2];
+		assert_eq!(spot.kind, LightKind::Spot);
+		assert!(close(spot.direction, [0.0, -1.0, 0.0]));
+		assert_eq!(spot.range, 20.0);
+		assert_eq!((spot.inner_angle, spot.outer_angle), (Deg(10.0), Deg(40.0)));
+	}
+
+	#[test]
+	fn rejects_unknown_kinds() {
+		assert!(parse_lights(r#"{"lights": [{"type": "area"}]}"#, 1.0).is_err());
+		assert!(parse_lights(r#"{"lights": [{"position": [0, 0, 0]}]}"#, 1.0).is_err());
+	}
+
+	#[test]
+	fn keeps_room_for_the_headlight() {
+		let entries = |n| format!(r#"{{"lights": [{}]}}"#, vec![r#"{"type": "point"}"#; n].join(", "));
+		assert_eq!(parse_lights(&entries(MAX_LIGHTS - 1), 1.0).unwrap().lights.len(), MAX_LIGHTS - 1);
+		assert!(parse_lights(&entries(MAX_LIGHTS), 1.0).is_err());
+
+		let mut lights = Lights::default();
+		while lights.add(lights.lights[0]) {}
+		assert_eq!(lights.lights.len(), MAX_LIGHTS - 1);
+
+		lights.headlight = true;
+		let camera = Camera::new(Matrix4::identity(), 1.0, 45.0, 0.1, 100.0);
+		assert_eq!(lights.to_uniform(&camera).count as usize, MAX_LIGHTS);
+	}
+
+	#[test]
+	fn settings_follow_the_selected_light() {
+		let camera = Camera::new(Matrix4::identity(), 1.0, 45.0, 0.1, 100.0);
+		let mut lights = Lights::default();
+		assert!(!lights.settings().contains(&Setting::SpotAngle));
+
+		// directional, then point, then spot
+		lights.input(VirtualKeyCode::Y, &camera);
+		assert_eq!(lights.input(VirtualKeyCode::Y, &camera).as_deref(), Some("light 1/1: spot"));
+		assert!(lights.settings().contains(&Setting::SpotAngle));
+
+		assert_eq!(lights.input(VirtualKeyCode::Delete, &camera).as_deref(), Some("no lights"));
+		assert_eq!(lights.settings(), &[Setting::Ambient]);
+		assert_eq!(lights.input(VirtualKeyCode::W, &camera), None);
+	}
+
+	#[test]
+	fn color_temperature_runs_from_red_to_blue() {
+		let warm = color_temperature(2000.0);
+		assert_eq!(warm[0], 1.0);
+		assert!(warm[2] < warm[1] && warm[1] < warm[0]);
+
+		// daylight is close to white
+		assert!(color_temperature(6500.0).iter().all(|&c| c > 0.95));
+
+		let cool = color_temperature(12000.0);
+		assert_eq!(cool[2], 1.0);
+		assert!(cool[0] < cool[1] && cool[1] < cool[2]);
+
+		// out of range temperatures clamp to the ends of the fit
+		assert_eq!(color_temperature(100.0), color_temperature(1000.0));
+		assert_eq!(color_temperature(1e6), color_temperature(40000.0));
+	}
+}
@@ -0,0 +1,319 @@
+use wgpu::util::DeviceExt;
+use cgmath::{InnerSpace, Matrix4, Vector3, Zero};
+use winit::event::VirtualKeyCode;
+
+use crate::bounds::{box_edges, sphere_edges, Bounds};
+use crate::instance::Instance;
+use crate::model::{Mesh, Vertex};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+	pub position: [f32; 3],
+	pub color: [f32; 3],
+}
+
+impl LineVertex {
+	pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+		wgpu::VertexBufferLayout {
+			array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Vertex,
+			attributes: &[
+				wgpu::VertexAttribute {
+					offset: 0,
+					shader_location: 0,
+					format: wgpu::VertexFormat::Float32x3,
+				},
+				wgpu::VertexAttribute {
+					offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+					shader_location: 1,
+					format: wgpu::VertexFormat::Float32x3,
+				}
+			]
+		}
+	}
+}
+
+/// Colored line list drawn with the line pipeline.
+pub struct Lines {
+	vertex_buffer: wgpu::Buffer,
+	n: u32,
+}
+
+impl Lines {
+	pub fn new(device: &wgpu::Device, vertices: &[LineVertex], label: &str) -> Self {
+		let vertex_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some(label),
+				contents: bytemuck::cast_slice(vertices),
+				usage: wgpu::BufferUsages::VERTEX,
+			}
+		);
+
+		Self {
+			vertex_buffer,
+			n: vertices.len() as u32,
+		}
+	}
+
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		if self.n == 0 {
+			return;
+		}
+		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+		render_pass.draw(0..self.n, 0..1);
+	}
+}
+
+/// X, Y and Z axes from `origin`, red, green and blue.
+pub fn axes(origin: Vector3<f32>, length: f32) -> Vec<LineVertex> {
+	[Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()].into_iter()
+		.flat_map(|axis| {
+			let color = axis.into();
+			[
+				LineVertex { position: origin.into(), color },
+				LineVertex { position: (origin + axis * length).into(), color },
+			]
+		})
+		.collect()
+}
+
+/// The bounding volume and direction glyph overlays, with their lines as last built.
+pub struct Overlays {
+	bounds: BoundsOverlay,
+	bounds_lines: Lines,
+	vectors: VectorOverlay,
+	vector_lines: Lines,
+}
+
+impl Overlays {
+	pub fn new(device: &wgpu::Device) -> Self {
+		Self {
+			bounds: BoundsOverlay::None,
+			bounds_lines: Lines::new(device, &[], "Bounds Lines"),
+			vectors: VectorOverlay::None,
+			vector_lines: Lines::new(device, &[], "Vector Lines"),
+		}
+	}
+
+	/// B cycles the bounding volumes and N the glyphs. Returns the status to show, `None` for
+	/// other keys.
+	pub fn input(&mut self, keycode: VirtualKeyCode, device: &wgpu::Device, model: &Mesh, instances: &[Instance]) -> Option<String> {
+		match keycode {
+			VirtualKeyCode::B => {
+				self.bounds = self.bounds.next();
+				self.bounds_lines = Lines::new(device, &self.bounds.lines(model, instances), "Bounds Lines");
+				Some(self.bounds.name().into())
+			}
+			VirtualKeyCode::N => {
+				self.vectors = self.vectors.next();
+				self.vector_lines = Lines::new(device, &self.vectors.lines(model, instances), "Vector Lines");
+				Some(self.vectors.name().into())
+			}
+			_ => None,
+		}
+	}
+
+	/// Rebuilds the lines after the model's submeshes have moved. Glyphs are too many to rebuild
+	/// every frame of an animation, so they're left out unless `glyphs`.
+	pub fn update(&mut self, device: &wgpu::Device, model: &Mesh, instances: &[Instance], glyphs: bool) {
+		self.bounds_lines = Lines::new(device, &self.bounds.lines(model, instances), "Bounds Lines");
+		if glyphs {
+			self.vector_lines = Lines::new(device, &self.vectors.lines(model, instances), "Vector Lines");
+		}
+	}
+
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		self.bounds_lines.draw(render_pass);
+		self.vector_lines.draw(render_pass);
+	}
+}
+
+/// Which bounding volume, if any, is drawn over the model.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BoundsOverlay {
+	None,
+	Aabb,
+	Sphere,
+	Obb,
+}
+
+impl BoundsOverlay {
+	const MODEL_COLOR: [f32; 3] = [1.0, 0.8, 0.1];
+	const SUBMESH_COLOR: [f32; 3] = [0.1, 0.6, 1.0];
+
+	pub fn next(self) -> Self {
+		match self {
+			Self::None => Self::Aabb,
+			Self::Aabb => Self::Sphere,
+			Self::Sphere => Self::Obb,
+			Self::Obb => Self::None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::None => "no bounds",
+			Self::Aabb => "bounding boxes",
+			Self::Sphere => "bounding spheres",
+			Self::Obb => "oriented bounding boxes",
+		}
+	}
+
+	/// Line vertices outlining the model's volume and, for multi-object files, each submesh's volume,
+	/// once per instance.
+	pub fn lines(self, model: &Mesh, instances: &[Instance]) -> Vec<LineVertex> {
+		let mut vertices = vec![];
+		for instance in instances {
+			if model.submeshes.len() > 1 {
+				for submesh in model.submeshes.iter() {
+					let transform = instance.transform * Matrix4::from_translation(submesh.offset);
+					vertices.extend(self.volume_lines(&submesh.bounds.transform(&transform), Self::SUBMESH_COLOR));
+				}
+			}
+			vertices.extend(self.volume_lines(&model.bounds.transform(&instance.transform), Self::MODEL_COLOR));
+		}
+		vertices
+	}
+
+	fn volume_lines(self, bounds: &Bounds, color: [f32; 3]) -> Vec<LineVertex> {
+		let points: Vec<Vector3<f32>> = match self {
+			Self::None => vec![],
+			Self::Aabb if bounds.aabb.is_empty() => vec![],
+			Self::Aabb => box_edges(&bounds.aabb.corners()),
+			Self::Sphere => sphere_edges(&bounds.sphere, 48),
+			Self::Obb => box_edges(&bounds.obb.corners()),
+		};
+
+		points.into_iter()
+			.map(|p| LineVertex { position: p.into(), color })
+			.collect()
+	}
+}
+
+/// Which direction glyphs, if any, are drawn on the model's surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VectorOverlay {
+	None,
+	/// The file's `vn` normal at every vertex.
+	Normals,
+	/// Normal, tangent and bitangent at every vertex, the tangents following the UVs.
+	TangentFrames,
+	/// The winding order's normal at every face center.
+	FaceNormals,
+}
+
+/// A vector drawn from a point of the model, in model space.
+struct Glyph {
+	origin: Vector3<f32>,
+	direction: Vector3<f32>,
+	color: [f32; 3],
+	/// Whether `direction` is a normal rather than one along the surface.
+	normal: bool,
+}
+
+impl VectorOverlay {
+	const NORMAL_COLOR: [f32; 3] = [0.2, 0.4, 1.0];
+	const TANGENT_COLOR: [f32; 3] = [1.0, 0.2, 0.2];
+	const BITANGENT_COLOR: [f32; 3] = [0.2, 1.0, 0.2];
+	const FACE_NORMAL_COLOR: [f32; 3] = [1.0, 0.9, 0.2];
+
+	pub fn next(self) -> Self {
+		match self {
+			Self::None => Self::Normals,
+			Self::Normals => Self::TangentFrames,
+			Self::TangentFrames => Self::FaceNormals,
+			Self::FaceNormals => Self::None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::None => "no vectors",
+			Self::Normals => "vertex normals",
+			Self::TangentFrames => "tangent frames",
+			Self::FaceNormals => "face normals",
+		}
+	}
+
+	/// Line vertices of the glyphs, 2% of the model's diagonal long, once per instance.
+	pub fn lines(self, model: &Mesh, instances: &[Instance]) -> Vec<LineVertex> {
+		let length = model.bounding_box().diag() * 0.02;
+		let mut glyphs: Vec<Glyph> = vec![];
+		let positions = if self == Self::None { vec![] } else { model.positions() };
+		match self {
+			Self::None => {}
+			Self::Normals => {
+				for (v, p) in model.vertices.iter().zip(&positions) {
+					glyphs.push(Glyph { origin: *p, direction: Vector3::from(v.normal), color: Self::NORMAL_COLOR, normal: true });
+				}
+			}
+			Self::TangentFrames => {
+				let tangents = tangents(&model.vertices, &model.indices);
+				for ((v, p), frame) in model.vertices.iter().zip(&positions).zip(tangents) {
+					glyphs.push(Glyph { origin: *p, direction: Vector3::from(v.normal), color: Self::NORMAL_COLOR, normal: true });
+					if let Some((tangent, bitangent)) = frame {
+						glyphs.push(Glyph { origin: *p, direction: tangent, color: Self::TANGENT_COLOR, normal: false });
+						glyphs.push(Glyph { origin: *p, direction: bitangent, color: Self::BITANGENT_COLOR, normal: false });
+					}
+				}
+			}
+			Self::FaceNormals => {
+				for triangle in model.indices.chunks_exact(3) {
+					let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+					glyphs.push(Glyph { origin: (a + b + c) / 3.0, direction: (b - a).cross(c - a), color: Self::FACE_NORMAL_COLOR, normal: true });
+				}
+			}
+		}
+
+		let mut vertices = Vec::with_capacity(glyphs.len() * instances.len() * 2);
+		for instance in instances {
+			let (linear, normal) = (instance.linear(), instance.normal_matrix());
+			for glyph in glyphs.iter() {
+				let direction = if glyph.normal { normal * glyph.direction } else { linear * glyph.direction };
+				if direction.magnitude2() == 0.0 {
+					continue;
+				}
+				// placed with the instance but kept the same length however it's scaled
+				let origin = (instance.transform * glyph.origin.extend(1.0)).truncate();
+				let tip = origin + direction.normalize() * length;
+				for position in [origin, tip] {
+					vertices.push(LineVertex { position: position.into(), color: glyph.color });
+				}
+			}
+		}
+		vertices
+	}
+}
+
+/// Per-vertex tangent and bitangent from the UV gradients of the surrounding triangles, made
+/// perpendicular to the normal. `None` where the UVs don't vary, e.g. without `vt` data.
+fn tangents(vertices: &[Vertex], indices: &[u32]) -> Vec<Option<(Vector3<f32>, Vector3<f32>)>> {
+	let mut sums = vec![(Vector3::zero(), Vector3::zero()); vertices.len()];
+	for triangle in indices.chunks_exact(3) {
+		let [a, b, c] = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+		let (e1, e2) = (Vector3::from(b.position) - Vector3::from(a.position), Vector3::from(c.position) - Vector3::from(a.position));
+		let (du1, dv1) = (b.tex_coords[0] - a.tex_coords[0], b.tex_coords[1] - a.tex_coords[1]);
+		let (du2, dv2) = (c.tex_coords[0] - a.tex_coords[0], c.tex_coords[1] - a.tex_coords[1]);
+		let det = du1 * dv2 - du2 * dv1;
+		if det.abs() < 1e-12 {
+			continue;
+		}
+		let tangent = (e1 * dv2 - e2 * dv1) / det;
+		let bitangent = (e2 * du1 - e1 * du2) / det;
+		for &i in triangle {
+			sums[i as usize].0 += tangent;
+			sums[i as usize].1 += bitangent;
+		}
+	}
+
+	vertices.iter().zip(sums).map(|(v, (tangent, bitangent))| {
+		let n = Vector3::from(v.normal);
+		let orthogonal = |t: Vector3<f32>| {
+			let t = t - n * n.dot(t);
+			(t.magnitude2() > 1e-12).then(|| t.normalize())
+		};
+		// tex_coords has v flipped, the bitangent should follow the file's v
+		orthogonal(tangent).zip(orthogonal(-bitangent))
+	}).collect()
+}
@@ -0,0 +1,36 @@
+// Vertex shader
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+@group(0) @binding(0) 
+var<uniform> camera: Camera;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    line: VertexIn,
+) -> VertexOutput {
+    var out: VertexOutput;
+    
+    out.clip_position = camera.view_proj * vec4<f32>(line.position, 1.0);
+    out.color = line.color;
+    
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	return vec4<f32>(in.color, 1.0);
+}
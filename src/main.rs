@@ -1,8 +1,16 @@
 use std::env;
-use objrs::run;
+use objrs::{run, Options};
 
 pub fn main() {
-	let args: Vec<String> = env::args().collect();
-	let def = String::from("hello");
-    pollster::block_on(run(args.get(1).unwrap_or(&def))); 
-}
\ No newline at end of file
+	let options = match Options::from_args(env::args().skip(1)) {
+		Ok(options) => options,
+		Err(e) => {
+			eprintln!("{}\n{}", e, Options::USAGE);
+			std::process::exit(2);
+		}
+	};
+	if let Err(e) = pollster::block_on(run(options)) {
+		eprintln!("{}", e);
+		std::process::exit(1);
+	}
+}
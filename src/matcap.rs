@@ -0,0 +1,35 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::texture::Texture;
+
+/// The matcap render mode's sphere image, read from `path` or else a neutral clay generated here.
+pub fn load(device: &wgpu::Device, queue: &wgpu::Queue, path: Option<&str>) -> Result<Texture, Box<dyn std::error::Error>> {
+	let img = match path {
+		Some(path) => image::open(path)?,
+		None => clay(256),
+	};
+	Ok(Texture::from_image_srgb(device, queue, &img, "matcap"))
+}
+
+/// A warm gray sphere under a key light from the upper left, a dim fill, a highlight and a rim.
+fn clay(size: u32) -> image::DynamicImage {
+	let albedo = Vector3::new(0.62, 0.58, 0.54);
+	let key = Vector3::new(-0.5, 0.6, 0.6).normalize();
+	let fill = Vector3::new(0.6, -0.2, 0.4).normalize();
+	let half = (key + Vector3::unit_z()).normalize();
+
+	image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(size, size, |x, y| {
+		let nx = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+		let ny = 1.0 - (y as f32 + 0.5) / size as f32 * 2.0;
+		// outside the sphere the rim carries on, for filtering at its edge
+		let r2 = nx * nx + ny * ny;
+		let n = if r2 < 1.0 { Vector3::new(nx, ny, (1.0 - r2).sqrt()) } else { Vector3::new(nx, ny, 0.0).normalize() };
+
+		let diffuse = 0.12 + n.dot(key).max(0.0) * 0.85 + n.dot(fill).max(0.0) * 0.2;
+		let specular = n.dot(half).max(0.0).powf(40.0) * 0.3;
+		let rim = (1.0 - n.z).powi(3) * 0.25;
+		let linear = albedo * diffuse + Vector3::new(1.0, 1.0, 1.0) * (specular + rim);
+		let srgb = |c: f32| (c.clamp(0.0, 1.0).powf(1.0 / 2.2) * 255.0).round() as u8;
+		image::Rgba([srgb(linear.x), srgb(linear.y), srgb(linear.z), 255])
+	}))
+}
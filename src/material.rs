@@ -0,0 +1,269 @@
+use std::path::Path;
+
+use wgpu::util::DeviceExt;
+
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+	/// `Kd`, with the dissolve `d` in alpha.
+	pub diffuse: [f32; 4],
+	/// `Ks`, with the shininess `Ns` in w.
+	pub specular: [f32; 4],
+	/// `Pr`, `Pm`, `Pc` and `Pcr`.
+	pub roughness_metallic_clearcoat: [f32; 4],
+	/// `Ps`, w unused.
+	pub sheen: [f32; 4],
+	/// Alpha-test threshold for `map_d` in x, 0 without one; yzw unused.
+	pub cutout: [f32; 4],
+	/// `Ke`, w unused.
+	pub emissive: [f32; 4],
+}
+
+impl MaterialUniform {
+	/// The parameters `Material::from_mtl` reads, given which of the maps it could load.
+	pub fn from_mtl(mtl: &tobj::Material, maps: &Maps) -> Self {
+		let scalar = |key: &str| param(mtl, key).and_then(|v| v.first().copied());
+		let diffuse = mtl.diffuse.unwrap_or([0.8; 3]);
+		let specular = mtl.specular.unwrap_or([0.0; 3]);
+		let shininess = mtl.shininess.unwrap_or(0.0);
+
+		let dissolve = mtl.dissolve.or_else(|| scalar("Tr").map(|tr| 1.0 - tr)).unwrap_or(1.0);
+
+		// a map on its own is used as is; a factor scales it
+		let roughness = scalar("Pr").unwrap_or(if maps.roughness { 1.0 } else { (2.0 / (shininess + 2.0)).sqrt() });
+		let metallic = scalar("Pm").unwrap_or(if maps.metallic { 1.0 } else { 0.0 });
+		let sheen = match param(mtl, "Ps").as_deref() {
+			Some([r, g, b, ..]) => [*r, *g, *b, 0.0],
+			Some([s]) => [*s, *s, *s, 0.0],
+			_ => [0.0; 4],
+		};
+		// a map on its own glows at full strength
+		let emissive = match param(mtl, "Ke").as_deref() {
+			Some([r, g, b, ..]) => [*r, *g, *b, 0.0],
+			Some([e]) => [*e, *e, *e, 0.0],
+			_ if maps.emissive => [1.0, 1.0, 1.0, 0.0],
+			_ => [0.0; 4],
+		};
+
+		MaterialUniform {
+			diffuse: [diffuse[0], diffuse[1], diffuse[2], dissolve.clamp(0.0, 1.0)],
+			specular: [specular[0], specular[1], specular[2], shininess],
+			roughness_metallic_clearcoat: [
+				roughness.clamp(0.0, 1.0),
+				metallic.clamp(0.0, 1.0),
+				scalar("Pc").unwrap_or(0.0),
+				scalar("Pcr").unwrap_or(0.03),
+			],
+			sheen,
+			cutout: [if maps.dissolve { 0.5 } else { 0.0 }, 0.0, 0.0, 0.0],
+			emissive,
+		}
+	}
+
+	pub fn is_transparent(&self) -> bool {
+		self.diffuse[3] < 1.0
+	}
+}
+
+/// Which of a material's maps were loaded; some factors default differently with a map.
+#[derive(Default)]
+pub struct Maps {
+	pub roughness: bool,
+	pub metallic: bool,
+	pub dissolve: bool,
+	pub emissive: bool,
+}
+
+/// Surface parameters of one MTL material, bound at group 2 while its submeshes are drawn.
+pub struct Material {
+	pub name: String,
+	pub uniform: MaterialUniform,
+	#[allow(dead_code)] // kept alive alongside the bind group that reads them
+	buffer: wgpu::Buffer,
+	#[allow(dead_code)]
+	roughness_map: Texture,
+	#[allow(dead_code)]
+	metallic_map: Texture,
+	#[allow(dead_code)]
+	dissolve_map: Texture,
+	#[allow(dead_code)]
+	emissive_map: Texture,
+	pub bind_group: wgpu::BindGroup,
+}
+
+impl Material {
+	pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+		let texture = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Texture {
+				multisampled: false,
+				view_dimension: wgpu::TextureViewDimension::D2,
+				sample_type: wgpu::TextureSampleType::Float { filterable: true },
+			},
+			count: None,
+		};
+
+		device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				texture(1),
+				texture(2),
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+				texture(4),
+				texture(5),
+			],
+			label: Some("material_bind_group_layout"),
+		})
+	}
+
+	/// Gray with a soft highlight, for meshes without an MTL file.
+	pub fn default(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> Self {
+		let uniform = MaterialUniform {
+			diffuse: [0.8, 0.8, 0.8, 1.0],
+			specular: [0.2, 0.2, 0.2, 32.0],
+			roughness_metallic_clearcoat: [0.5, 0.0, 0.0, 0.0],
+			sheen: [0.0; 4],
+			cutout: [0.0; 4],
+			emissive: [0.0; 4],
+		};
+		Self::new(device, queue, layout, "default".into(), uniform, None, None, None, None)
+	}
+
+	/// Reads the classic `Kd`/`Ks`/`Ns`/`d`/`Ke` parameters and the PBR extensions `Pr`, `Pm`, `Ps`,
+	/// `Pc`, `Pcr`, `map_Pr` and `map_Pm`. Without `Pr`, roughness is derived from `Ns`, and
+	/// without `d` the dissolve is `1 - Tr`. Maps are looked up relative to `dir` and read from
+	/// their red channel, except `map_d`, which is read from its alpha channel when it has one
+	/// and cuts out whatever is below half, and `map_Ke`, an sRGB color.
+	pub fn from_mtl(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout, mtl: &tobj::Material, dir: &Path) -> Self {
+		let roughness_map = map(mtl, "map_Pr", dir).map(|img| Texture::from_image(device, queue, &img, "map_Pr"));
+		let metallic_map = map(mtl, "map_Pm", dir).map(|img| Texture::from_image(device, queue, &img, "map_Pm"));
+		let dissolve_map = mtl.dissolve_texture.as_deref()
+			.and_then(|spec| open_map(spec, dir))
+			.map(|img| Texture::from_image(device, queue, &coverage(&img), "map_d"));
+		let emissive_map = map(mtl, "map_Ke", dir).map(|img| Texture::from_image_srgb(device, queue, &img, "map_Ke"));
+
+		let maps = Maps {
+			roughness: roughness_map.is_some(),
+			metallic: metallic_map.is_some(),
+			dissolve: dissolve_map.is_some(),
+			emissive: emissive_map.is_some(),
+		};
+		let uniform = MaterialUniform::from_mtl(mtl, &maps);
+		Self::new(device, queue, layout, mtl.name.clone(), uniform, roughness_map, metallic_map, dissolve_map, emissive_map)
+	}
+
+	/// Blended in the transparent pass rather than drawn opaque.
+	pub fn is_transparent(&self) -> bool {
+		self.uniform.is_transparent()
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn new(
+		device: &wgpu::Device,
+		queue: &wgpu::Queue,
+		layout: &wgpu::BindGroupLayout,
+		name: String,
+		uniform: MaterialUniform,
+		roughness_map: Option<Texture>,
+		metallic_map: Option<Texture>,
+		dissolve_map: Option<Texture>,
+		emissive_map: Option<Texture>,
+	) -> Self {
+		let buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Material Buffer"),
+				contents: bytemuck::cast_slice(&[uniform]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+		let white = |label| Texture::from_color(device, queue, [255; 4], label);
+		let roughness_map = roughness_map.unwrap_or_else(|| white("map_Pr"));
+		let metallic_map = metallic_map.unwrap_or_else(|| white("map_Pm"));
+		let dissolve_map = dissolve_map.unwrap_or_else(|| white("map_d"));
+		let emissive_map = emissive_map.unwrap_or_else(|| white("map_Ke"));
+
+		let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&roughness_map.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(&metallic_map.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: wgpu::BindingResource::Sampler(&roughness_map.sampler),
+				},
+				wgpu::BindGroupEntry {
+					binding: 4,
+					resource: wgpu::BindingResource::TextureView(&dissolve_map.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 5,
+					resource: wgpu::BindingResource::TextureView(&emissive_map.view),
+				}
+			],
+			label: Some("material_bind_group"),
+		});
+
+		Self {
+			name,
+			uniform,
+			buffer,
+			roughness_map,
+			metallic_map,
+			dissolve_map,
+			emissive_map,
+			bind_group,
+		}
+	}
+}
+
+/// Values of a parameter tobj doesn't know about, such as `Pr 0.5` or `Ps 1 1 1`.
+fn param(mtl: &tobj::Material, key: &str) -> Option<Vec<f32>> {
+	let values: Result<Vec<f32>, _> = mtl.unknown_param.get(key)?.split_whitespace().map(str::parse).collect();
+	values.ok().filter(|v| !v.is_empty())
+}
+
+/// Loads a map parameter's image, ignoring any options before the file name.
+fn map(mtl: &tobj::Material, key: &str, dir: &Path) -> Option<image::DynamicImage> {
+	open_map(mtl.unknown_param.get(key)?, dir)
+}
+
+fn open_map(spec: &str, dir: &Path) -> Option<image::DynamicImage> {
+	let path = dir.join(spec.split_whitespace().last()?);
+	image::open(&path).map_err(|e| eprintln!("{}: {}", path.display(), e)).ok()
+}
+
+/// A `map_d` image's coverage in gray: its alpha if it has any, otherwise its brightness.
+fn coverage(img: &image::DynamicImage) -> image::DynamicImage {
+	if !img.color().has_alpha() {
+		return image::DynamicImage::ImageLuma8(img.to_luma8());
+	}
+	let rgba = img.to_rgba8();
+	image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| image::Luma([rgba.get_pixel(x, y)[3]])))
+}
@@ -1,12 +1,18 @@
+use std::ops::Range;
+
 use wgpu::util::DeviceExt;
-use tobj;
-use cgmath::{InnerSpace, Vector3};
+use cgmath::{InnerSpace, Matrix4, Vector3, Zero};
+
+use crate::bounds::{BoundingBox, Bounds};
+use crate::instance::InstanceBuffer;
+use crate::material::Material;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
 	pub position: [f32; 3],
 	pub normal: [f32; 3],
+	pub tex_coords: [f32; 2],
 }
 
 impl Vertex {
@@ -24,101 +30,503 @@ impl Vertex {
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
                 }
             ]
         }
 	}
 }
 
-#[derive(Debug)]
-pub struct BoundingBox {
-	min: Vector3<f32>,
-	max: Vector3<f32>,
+/// Indices identifying a submesh to the shader and where the exploded view has moved it, one per
+/// submesh at a dynamic offset.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SubmeshUniform {
+	object: u32,
+	material: u32,
+	_padding: [u32; 2],
+	/// `Submesh::offset`, w unused.
+	offset: [f32; 4],
 }
 
-impl BoundingBox {
-	pub fn center(&self) -> Vector3<f32> {
-		(self.min + self.max) / 2.0
-	}
+/// One `o`/`g` object of the source file, drawn from a contiguous range of the index buffer.
+pub struct Submesh {
+	pub name: String,
+	pub indices: Range<u32>,
+	pub bounds: Bounds,
+	/// Index into `Mesh::materials`.
+	pub material: usize,
+	/// The range of `Mesh::vertices` its indices point into.
+	pub vertices: Range<u32>,
+	/// How far the exploded view has pushed it out of place, see `Mesh::explode`. Shaders add it
+	/// to the positions in the vertex buffer, which stay as loaded.
+	pub offset: Vector3<f32>,
+}
 
-	pub fn diag(&self) -> f32 {
-		(self.max - self.min).magnitude()
-	}
+/// One submesh drawn for a range of instances.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Draw {
+	pub submesh: usize,
+	pub instances: Range<u32>,
 }
 
 pub struct Mesh {
 	vertex_buffer: wgpu::Buffer,
 	index_buffer: wgpu::Buffer,
-	pub bounding_box: BoundingBox,
+	/// The vertex and index buffers as storage, for shaders that fetch whole triangles,
+	/// and the submesh uniforms.
+	geometry_bind_group: wgpu::BindGroup,
+	submesh_buffer: wgpu::Buffer,
+	/// Distance between consecutive submeshes' uniforms.
+	submesh_stride: u32,
+	pub bounds: Bounds,
+	pub submeshes: Vec<Submesh>,
+	pub materials: Vec<Material>,
+	/// CPU copies of the geometry as loaded, also what the vertex buffer holds. See `positions`
+	/// for where the vertices are drawn.
+	pub vertices: Vec<Vertex>,
+	pub indices: Vec<u32>,
 	n: u32,
 }
 
 impl Mesh {
-	pub fn from_obj(device: &wgpu::Device, filename: &str) -> Result<Self, Box<dyn std::error::Error>> {
-		let (models, _) = tobj::load_obj(filename, &tobj::GPU_LOAD_OPTIONS)?;
+	/// Vertices (as `f32`s, 8 per vertex), indices and per-triangle edge masks (see [`triangulate`]),
+	/// readable from the vertex stage at group 3, and the uniform of the submesh being drawn with
+	/// its object and material index and its exploded-view offset.
+	pub fn geometry_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+		let storage = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::VERTEX,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: true },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				storage(0),
+				storage(1),
+				storage(2),
+				wgpu::BindGroupLayoutEntry {
+					binding: 3,
+					visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: true,
+						min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SubmeshUniform>() as u64),
+					},
+					count: None,
+				},
+			],
+			label: Some("geometry_bind_group_layout"),
+		})
+	}
+
+	/// Loads an OBJ and its MTL materials. A missing or broken MTL file falls back to the default material.
+	/// Faces are triangulated here rather than by tobj, to keep track of the polygons' own edges.
+	pub fn from_obj(device: &wgpu::Device, queue: &wgpu::Queue, filename: &str, material_layout: &wgpu::BindGroupLayout, geometry_layout: &wgpu::BindGroupLayout) -> Result<Self, Box<dyn std::error::Error>> {
+		let load_options = tobj::LoadOptions {
+			triangulate: false,
+			..tobj::GPU_LOAD_OPTIONS
+		};
+		let (models, mtls) = tobj::load_obj(filename, &load_options)?;
+		let mtls = mtls.unwrap_or_else(|e| {
+			eprintln!("{}: {}, using the default material", filename, e);
+			vec![]
+		});
+		let dir = std::path::Path::new(filename).parent().unwrap_or(std::path::Path::new(""));
+		let materials = mtls.iter().map(|m| Material::from_mtl(device, queue, material_layout, m, dir)).collect();
+		Ok(Self::from_models(device, queue, &models, materials, material_layout, geometry_layout))
+	}
+
+	/// Mesh with nothing to draw, used when the file is shown as a point cloud.
+	pub fn empty(device: &wgpu::Device, queue: &wgpu::Queue, material_layout: &wgpu::BindGroupLayout, geometry_layout: &wgpu::BindGroupLayout) -> Self {
+		Self::from_models(device, queue, &[], vec![], material_layout, geometry_layout)
+	}
+
+	fn from_models(device: &wgpu::Device, queue: &wgpu::Queue, models: &[tobj::Model], mut materials: Vec<Material>, material_layout: &wgpu::BindGroupLayout, geometry_layout: &wgpu::BindGroupLayout) -> Self {
+		// faces without a usable `usemtl` share a default material at the end of the list
+		let default_material = materials.len();
+		materials.push(Material::default(device, queue, material_layout));
+		let Geometry { vertices, indices, edges, submeshes } = Geometry::from_models(models, default_material);
+
+		let points: Vec<Vector3<f32>> = vertices.iter().map(|v| Vector3::from(v.position)).collect();
+		let bounds = Bounds::from_points(&points);
+
+		// storage bindings can't be empty
+		let buffer = |label, contents: &[u8], usage| device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some(label),
+				contents: if contents.is_empty() { &[0; 16] } else { contents },
+				usage,
+			}
+		);
+		let vertex_buffer = buffer("Vertex Buffer", bytemuck::cast_slice(vertices.as_slice()), wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE);
+		let index_buffer = buffer("Index Buffer", bytemuck::cast_slice(indices.as_slice()), wgpu::BufferUsages::INDEX | wgpu::BufferUsages::STORAGE);
+		let edge_buffer = buffer("Edge Buffer", bytemuck::cast_slice(edges.as_slice()), wgpu::BufferUsages::STORAGE);
+
+		// dynamic offsets must be aligned, so each submesh's uniform gets a padded slot
+		let submesh_stride = device.limits().min_uniform_buffer_offset_alignment;
+		let submesh_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Submesh Buffer"),
+				contents: &submesh_data(&submeshes, submesh_stride),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let geometry_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: geometry_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: vertex_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: index_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: edge_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 3,
+					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+						buffer: &submesh_buffer,
+						offset: 0,
+						size: wgpu::BufferSize::new(std::mem::size_of::<SubmeshUniform>() as u64),
+					}),
+				}
+			],
+			label: Some("geometry_bind_group"),
+		});
 
+		Self {
+			vertex_buffer,
+			index_buffer,
+			geometry_bind_group,
+			submesh_buffer,
+			submesh_stride,
+			bounds,
+			submeshes,
+			materials,
+			n: indices.len() as u32,
+			vertices,
+			indices,
+		}
+	}
+
+	/// True when no faces were loaded, e.g. for files with only `v` lines.
+	pub fn is_empty(&self) -> bool {
+		self.n == 0
+	}
+
+	/// The vertices as loaded, also readable as storage by shaders that fetch them by index.
+	pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+		&self.vertex_buffer
+	}
+
+	/// Axis-aligned bounds of the whole model.
+	pub fn bounding_box(&self) -> &BoundingBox {
+		&self.bounds.aabb
+	}
+
+	/// Pushes every submesh out from the model's center, along the line to its own center, by
+	/// `amount` times the distance between them. Only the submesh uniforms are uploaded again.
+	pub fn explode(&mut self, queue: &wgpu::Queue, amount: f32) {
+		let center = self.bounds.aabb.center();
+		for submesh in self.submeshes.iter_mut().filter(|s| !s.bounds.aabb.is_empty()) {
+			submesh.offset = (submesh.bounds.aabb.center() - center) * amount;
+		}
+		queue.write_buffer(&self.submesh_buffer, 0, &submesh_data(&self.submeshes, self.submesh_stride));
+	}
+
+	/// Binds the geometry at `group` with `submesh`'s uniform, for passes drawing it one submesh
+	/// at a time.
+	pub fn bind_submesh<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, group: u32, submesh: usize) {
+		render_pass.set_bind_group(group, &self.geometry_bind_group, &[submesh as u32 * self.submesh_stride]);
+	}
+
+	/// Vertex positions as drawn, moved by their submesh's exploded-view offset.
+	pub fn positions(&self) -> Vec<Vector3<f32>> {
+		let mut positions: Vec<Vector3<f32>> = self.vertices.iter().map(|v| Vector3::from(v.position)).collect();
+		for submesh in self.submeshes.iter() {
+			for p in &mut positions[submesh.vertices.start as usize..submesh.vertices.end as usize] {
+				*p += submesh.offset;
+			}
+		}
+		positions
+	}
+
+	/// Axis-aligned bounds of the model as drawn, with its submeshes exploded.
+	pub fn exploded_box(&self) -> BoundingBox {
+		self.submeshes.iter()
+			.filter(|s| !s.bounds.aabb.is_empty())
+			.fold(BoundingBox::empty(), |b, s| b.union(&s.bounds.aabb.transform(&Matrix4::from_translation(s.offset))))
+	}
+
+	/// Every submesh for every one of `instance_count` instances.
+	pub fn all_draws(&self, instance_count: u32) -> Vec<Draw> {
+		(0..self.submeshes.len()).map(|submesh| Draw { submesh, instances: 0..instance_count }).collect()
+	}
+
+	/// Draws each of `draws` with its submesh's material bound at group 2.
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: &'a InstanceBuffer, draws: &[Draw]) {
+		if instances.is_empty() || self.n == 0 {
+			return;
+		}
+		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+		render_pass.set_vertex_buffer(1, instances.slice());
+		render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+		for draw in draws {
+			let submesh = &self.submeshes[draw.submesh];
+			render_pass.set_bind_group(2, &self.materials[submesh.material].bind_group, &[]);
+			self.bind_submesh(render_pass, 3, draw.submesh);
+			render_pass.draw_indexed(submesh.indices.clone(), 0, draw.instances.clone());
+		}
+	}
+
+	/// Like `draw`, but without vertex or index buffers: the shader fetches its triangle corners
+	/// from group 3 by `vertex_index`, so it also knows which corner it is. Instances are in slot 0.
+	pub fn draw_triangles<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: &'a InstanceBuffer, draws: &[Draw]) {
+		if instances.is_empty() || self.n == 0 {
+			return;
+		}
+		render_pass.set_vertex_buffer(0, instances.slice());
+		for draw in draws {
+			let submesh = &self.submeshes[draw.submesh];
+			render_pass.set_bind_group(2, &self.materials[submesh.material].bind_group, &[]);
+			self.bind_submesh(render_pass, 3, draw.submesh);
+			render_pass.draw(submesh.indices.clone(), draw.instances.clone());
+		}
+	}
+
+	/// Draws the faces of `draws` without binding materials, for depth-only passes. The geometry
+	/// is bound at group 1, for the submeshes' offsets.
+	pub fn draw_geometry<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, instances: &'a InstanceBuffer, draws: &[Draw]) {
+		if instances.is_empty() || self.n == 0 {
+			return;
+		}
+		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+		render_pass.set_vertex_buffer(1, instances.slice());
+		render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+		for draw in draws {
+			self.bind_submesh(render_pass, 1, draw.submesh);
+			render_pass.draw_indexed(self.submeshes[draw.submesh].indices.clone(), 0, draw.instances.clone());
+		}
+	}
+}
+
+/// Each submesh's uniform, `stride` bytes apart.
+fn submesh_data(submeshes: &[Submesh], stride: u32) -> Vec<u8> {
+	let mut data = vec![0u8; submeshes.len().max(1) * stride as usize];
+	for (i, submesh) in submeshes.iter().enumerate() {
+		let uniform = SubmeshUniform {
+			object: i as u32,
+			material: submesh.material as u32,
+			_padding: [0; 2],
+			offset: submesh.offset.extend(0.0).into(),
+		};
+		let start = i * stride as usize;
+		data[start..start + std::mem::size_of::<SubmeshUniform>()].copy_from_slice(bytemuck::bytes_of(&uniform));
+	}
+	data
+}
+
+/// CPU side of a mesh, before anything is uploaded.
+struct Geometry {
+	vertices: Vec<Vertex>,
+	indices: Vec<u32>,
+	edges: Vec<u32>,
+	submeshes: Vec<Submesh>,
+}
+
+impl Geometry {
+	fn from_models(models: &[tobj::Model], default_material: usize) -> Self {
 		let mut vertices: Vec<Vertex> = vec![];
 		let mut indices: Vec<u32> = vec![];
+		let mut edges: Vec<u32> = vec![];
+		let mut submeshes: Vec<Submesh> = vec![];
 		let mut off: u32 = 0;
-		let mut min_bound = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
-		let mut max_bound = Vector3::new(0.0_f32, 0.0_f32, 0.0_f32);
-		let mut first = true;
 		for m in models.iter() {
 			let mesh = &m.mesh;
-			
-			indices.extend(mesh.indices.iter().map(|i| (i + off) as u32));
-			
+		
+			let start = indices.len() as u32;
+			triangulate(&mesh.indices, &mesh.face_arities, off, &mut indices, &mut edges);
+		
 			let n = mesh.positions.len() / 3;
-			for i in 0..n {
-				let pos = [mesh.positions[(i*3) as usize], mesh.positions[(i*3+1) as usize], mesh.positions[(i*3+2) as usize]];
-				if first {
-					first = false;
-					min_bound = Vector3::new(pos[0], pos[1], pos[2]);
-					max_bound = Vector3::new(pos[0], pos[1], pos[2]);
-				} else {
-					min_bound = Vector3::new(min_bound.x.min(pos[0]), min_bound.y.min(pos[1]), min_bound.z.min(pos[2]));
-					max_bound = Vector3::new(max_bound.x.max(pos[0]), max_bound.y.max(pos[1]), max_bound.z.max(pos[2]));
-				}
+			// files without `vn` lines get smooth normals from the faces around each vertex
+			let normals = if mesh.normals.is_empty() {
+				face_normals(&mesh.positions, &indices[start as usize..], off)
+			} else {
+				mesh.normals.chunks_exact(3).map(|n| [n[0], n[1], n[2]]).collect()
+			};
+			let mut points = Vec::with_capacity(n);
+			for (i, normal) in normals.into_iter().enumerate() {
+				let pos = [mesh.positions[i*3], mesh.positions[i*3+1], mesh.positions[i*3+2]];
+				points.push(Vector3::from(pos));
 
 				vertices.push(Vertex {
 					position: pos,
-					normal: [mesh.normals[(i*3) as usize], mesh.normals[(i*3+1) as usize], mesh.normals[(i*3+2) as usize]],
+					normal,
+					tex_coords: if mesh.texcoords.is_empty() { [0.0, 0.0] } else { [mesh.texcoords[i*2], 1.0 - mesh.texcoords[i*2+1]] },
 				});
 			}
-			
+
+			submeshes.push(Submesh {
+				name: m.name.clone(),
+				indices: start..indices.len() as u32,
+				bounds: Bounds::from_points(&points),
+				material: mesh.material_id.filter(|&id| id < default_material).unwrap_or(default_material),
+				vertices: off..off + n as u32,
+				offset: Vector3::zero(),
+			});
+		
 			off += n as u32;
 		}
 
-		let vertex_buffer = device.create_buffer_init(
-			&wgpu::util::BufferInitDescriptor {
-				label: Some("Vertex Buffer"),
-				contents: bytemuck::cast_slice(vertices.as_slice()),
-				usage: wgpu::BufferUsages::VERTEX,
-			}
-		);
+		Self { vertices, indices, edges, submeshes }
+	}
+}
 
-		let index_buffer = device.create_buffer_init(
-			&wgpu::util::BufferInitDescriptor {
-				label: Some("Index Buffer"),
-				contents: bytemuck::cast_slice(indices.as_slice()),
-				usage: wgpu::BufferUsages::INDEX,
-			}
-		);
+/// Area-weighted vertex normals for `positions`, from triangles whose `indices` are offset by `offset`.
+/// Each triangle adds its unnormalized cross product, so larger faces count for more.
+fn face_normals(positions: &[f32], indices: &[u32], offset: u32) -> Vec<[f32; 3]> {
+	let position = |i: u32| {
+		let i = (i - offset) as usize;
+		Vector3::new(positions[i*3], positions[i*3+1], positions[i*3+2])
+	};
+	let mut sums = vec![Vector3::zero(); positions.len() / 3];
+	for triangle in indices.chunks_exact(3) {
+		let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+		let normal = (position(b) - position(a)).cross(position(c) - position(a));
+		for i in [a, b, c] {
+			sums[(i - offset) as usize] += normal;
+		}
+	}
+	// vertices only on degenerate faces still need a unit normal
+	sums.into_iter().map(|n: Vector3<f32>| if n.magnitude2() > 0.0 { n.normalize().into() } else { [0.0, 0.0, 1.0] }).collect()
+}
 
-		Ok(Self {
-			vertex_buffer,
-			index_buffer,
-			bounding_box: BoundingBox{
-				min: min_bound, 
-				max: max_bound
-			},
-			n: indices.len() as u32,
-		})
+/// Fans each polygon of `face_arities` (all triangles when empty, as tobj leaves it) into triangles,
+/// appending their indices offset by `offset`. For each triangle one mask goes into `edges`: bit k is
+/// set when the edge opposite its k-th corner is an edge of the polygon rather than a fan diagonal.
+fn triangulate(polygons: &[u32], face_arities: &[u32], offset: u32, indices: &mut Vec<u32>, edges: &mut Vec<u32>) {
+	if face_arities.is_empty() {
+		for triangle in polygons.chunks_exact(3) {
+			fan(triangle, offset, indices, edges);
+		}
+		return;
 	}
 
-	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
-		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-		render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-		render_pass.draw_indexed(0..self.n, 0, 0..1);
+	let mut start = 0;
+	for &arity in face_arities {
+		let end = start + arity as usize;
+		fan(&polygons[start..end], offset, indices, edges);
+		start = end;
+	}
+}
+
+fn fan(polygon: &[u32], offset: u32, indices: &mut Vec<u32>, edges: &mut Vec<u32>) {
+	let n = polygon.len();
+	for i in 1..n.saturating_sub(1) {
+		indices.extend([polygon[0], polygon[i], polygon[i + 1]].map(|v| v + offset));
+		// the outer edge is always kept, the edges back to the first corner only at the ends of the fan
+		let mut mask = 0b001;
+		if i + 2 == n {
+			mask |= 0b010;
+		}
+		if i == 1 {
+			mask |= 0b100;
+		}
+		edges.push(mask);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn run(polygons: &[u32], face_arities: &[u32], offset: u32) -> (Vec<u32>, Vec<u32>) {
+		let (mut indices, mut edges) = (vec![], vec![]);
+		triangulate(polygons, face_arities, offset, &mut indices, &mut edges);
+		(indices, edges)
+	}
+
+	#[test]
+	fn triangle_keeps_every_edge() {
+		assert_eq!(run(&[0, 1, 2], &[3], 0), (vec![0, 1, 2], vec![0b111]));
+	}
+
+	#[test]
+	fn quad_drops_its_diagonal() {
+		// the diagonal 0-2 is opposite corner 1 of the first triangle and corner 2 of the second
+		assert_eq!(run(&[0, 1, 2, 3], &[4], 0), (vec![0, 1, 2, 0, 2, 3], vec![0b101, 0b011]));
 	}
-} 
\ No newline at end of file
+
+	#[test]
+	fn pentagon_keeps_only_outer_edges() {
+		let (indices, edges) = run(&[0, 1, 2, 3, 4], &[5], 0);
+		assert_eq!(indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+		assert_eq!(edges, vec![0b101, 0b001, 0b011]);
+	}
+
+	#[test]
+	fn mixed_polygons_are_offset() {
+		let (indices, edges) = run(&[0, 1, 2, 3, 4, 5, 6], &[3, 4], 10);
+		assert_eq!(indices, vec![10, 11, 12, 13, 14, 15, 13, 15, 16]);
+		assert_eq!(edges, vec![0b111, 0b101, 0b011]);
+	}
+
+	#[test]
+	fn no_arities_means_triangles() {
+		let (indices, edges) = run(&[0, 1, 2, 2, 1, 3], &[], 4);
+		assert_eq!(indices, vec![4, 5, 6, 6, 5, 7]);
+		assert_eq!(edges, vec![0b111, 0b111]);
+	}
+
+	fn load(obj: &str) -> Geometry {
+		let load_options = tobj::LoadOptions {
+			triangulate: false,
+			..tobj::GPU_LOAD_OPTIONS
+		};
+		let (models, _) = tobj::load_obj_buf(&mut obj.as_bytes(), &load_options, |_| Ok(Default::default())).unwrap();
+		Geometry::from_models(&models, 0)
+	}
+
+	#[test]
+	fn faces_without_normals_get_face_normals() {
+		// a unit square in the xy plane and a triangle standing on it along z
+		let geometry = load("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\no wall\nv 0 0 0\nv 1 0 0\nv 0 0 1\nf 5 6 7\n");
+		assert_eq!(geometry.submeshes.len(), 2);
+		assert_eq!(geometry.indices.len(), 9);
+		let normals: Vec<[f32; 3]> = geometry.vertices.iter().map(|v| v.normal).collect();
+		assert_eq!(&normals[..4], &[[0.0, 0.0, 1.0]; 4]);
+		assert_eq!(&normals[4..], &[[0.0, -1.0, 0.0]; 3]);
+	}
+
+	#[test]
+	fn shared_vertices_average_by_area() {
+		// two faces of a box meeting along the x axis, the top twice the size of the front
+		let geometry = load("v 0 0 0\nv 1 0 0\nv 1 2 0\nv 0 2 0\nv 1 0 -1\nv 0 0 -1\nf 1 2 3 4\nf 1 6 5 2\n");
+		let normal = Vector3::from(geometry.vertices[0].normal);
+		assert!((normal.magnitude() - 1.0).abs() < 1e-6);
+		assert!((normal - Vector3::new(0.0, -1.0, 2.0).normalize()).magnitude() < 1e-6);
+		// unshared corners keep their own face's normal
+		assert_eq!(geometry.vertices[2].normal, [0.0, 0.0, 1.0]);
+	}
+
+	#[test]
+	fn degenerate_faces_still_get_a_unit_normal() {
+		let geometry = load("v 0 0 0\nv 1 1 1\nv 2 2 2\nf 1 2 3\n");
+		assert!(geometry.vertices.iter().all(|v| v.normal == [0.0, 0.0, 1.0]));
+	}
+}
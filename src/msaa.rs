@@ -0,0 +1,178 @@
+use crate::texture::Texture;
+
+/// Multisampled color and depth targets the scene is drawn into when `sample_count > 1`.
+/// Color resolves into the frame; depth is copied into the single-sample depth texture on request,
+/// for passes that read it afterwards.
+pub struct Multisample {
+	pub sample_count: u32,
+	/// Sample counts usable with both the color and the depth format, ascending.
+	pub supported: Vec<u32>,
+	format: wgpu::TextureFormat,
+	color: Option<Texture>,
+	depth: Option<Texture>,
+	resolve_pipeline: wgpu::RenderPipeline,
+	resolve_layout: wgpu::BindGroupLayout,
+	resolve_bind_group: Option<wgpu::BindGroup>,
+}
+
+impl Multisample {
+	/// Counts the device can render `format` and depth with. Without adapter-specific format
+	/// features only WebGPU's guaranteed 1 and 4 are allowed.
+	pub fn supported_sample_counts(adapter: &wgpu::Adapter, device: &wgpu::Device, format: wgpu::TextureFormat) -> Vec<u32> {
+		// wgpu's GL backend can't create multisampled textures that are also sampled, which the
+		// depth resolve needs
+		if adapter.get_info().backend == wgpu::Backend::Gl {
+			return vec![1];
+		}
+		let adapter_specific = device.features().contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+		let color = adapter.get_texture_format_features(format).flags;
+		let depth = adapter.get_texture_format_features(Texture::DEPTH_FORMAT).flags;
+		[1, 2, 4, 8].into_iter()
+			.filter(|&n| n == 1 || adapter_specific || n == 4)
+			.filter(|&n| color.sample_count_supported(n) && depth.sample_count_supported(n))
+			.collect()
+	}
+
+	/// Uses the largest supported count not above `requested`, for color in `format`.
+	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, format: wgpu::TextureFormat, requested: u32, supported: Vec<u32>) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Depth Resolve Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("msaa.wgsl").into()),
+		});
+
+		let resolve_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: true,
+					},
+					count: None,
+				}
+			],
+			label: Some("depth_resolve_bind_group_layout"),
+		});
+
+		let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Depth Resolve Pipeline Layout"),
+			bind_group_layouts: &[&resolve_layout],
+			push_constant_ranges: &[],
+		});
+
+		let resolve_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Depth Resolve Pipeline"),
+			layout: Some(&pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::Always,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+
+		let mut multisample = Self {
+			sample_count: 1,
+			supported,
+			format,
+			color: None,
+			depth: None,
+			resolve_pipeline,
+			resolve_layout,
+			resolve_bind_group: None,
+		};
+		multisample.set_sample_count(device, config, requested);
+		multisample
+	}
+
+	/// Switches to the largest supported count not above `requested` and returns it.
+	pub fn set_sample_count(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, requested: u32) -> u32 {
+		self.sample_count = self.supported.iter().copied().filter(|&n| n <= requested).max().unwrap_or(1);
+		self.resize(device, config);
+		self.sample_count
+	}
+
+	/// The next supported count up (`steps > 0`) or down, staying at the ends.
+	pub fn step(&self, steps: i32) -> u32 {
+		let i = self.supported.iter().position(|&n| n == self.sample_count).unwrap_or(0) as i32;
+		self.supported[(i + steps).clamp(0, self.supported.len() as i32 - 1) as usize]
+	}
+
+	pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+		if self.sample_count == 1 {
+			self.color = None;
+			self.depth = None;
+			self.resolve_bind_group = None;
+			return;
+		}
+
+		let color = Texture::new_multisampled(device, config, self.format, self.sample_count, "msaa_color");
+		let depth = Texture::new_multisampled(device, config, Texture::DEPTH_FORMAT, self.sample_count, "msaa_depth");
+		self.resolve_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &self.resolve_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::TextureView(&depth.view),
+				}
+			],
+			label: Some("depth_resolve_bind_group"),
+		}));
+		self.color = Some(color);
+		self.depth = Some(depth);
+	}
+
+	/// Color attachment view and resolve target for drawing into `target`.
+	pub fn color_attachment<'a>(&'a self, target: &'a wgpu::TextureView) -> (&'a wgpu::TextureView, Option<&'a wgpu::TextureView>) {
+		match &self.color {
+			Some(color) => (&color.view, Some(target)),
+			None => (target, None),
+		}
+	}
+
+	/// Depth attachment view, `depth_texture` itself when not multisampling.
+	pub fn depth_attachment<'a>(&'a self, depth_texture: &'a Texture) -> &'a wgpu::TextureView {
+		self.depth.as_ref().map_or(&depth_texture.view, |d| &d.view)
+	}
+
+	/// Copies multisampled depth into `depth_texture`; does nothing when not multisampling.
+	pub fn resolve_depth(&self, encoder: &mut wgpu::CommandEncoder, depth_texture: &Texture) {
+		let Some(bind_group) = &self.resolve_bind_group else {
+			return;
+		};
+
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Depth Resolve Pass"),
+			color_attachments: &[],
+			depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+				view: &depth_texture.view,
+				depth_ops: Some(wgpu::Operations {
+					load: wgpu::LoadOp::Clear(1.0),
+					store: wgpu::StoreOp::Store,
+				}),
+				stencil_ops: None,
+			}),
+			occlusion_query_set: None,
+			timestamp_writes: None,
+		});
+		render_pass.set_pipeline(&self.resolve_pipeline);
+		render_pass.set_bind_group(0, bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+}
@@ -0,0 +1,15 @@
+// Copies the first sample of the multisampled depth buffer into the single-sample one
+@group(0) @binding(0)
+var depth_texture: texture_multisampled_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // one triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @builtin(frag_depth) f32 {
+    return textureLoad(depth_texture, vec2<i32>(position.xy), 0).r;
+}
@@ -0,0 +1,68 @@
+use crate::render_mode::RenderMode;
+use crate::tonemapper::Tonemapper;
+
+/// Command line options, see [`Options::USAGE`].
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+	pub filename: String,
+	pub instances: Option<String>,
+	/// JSON light rig replacing the default key light.
+	pub lights: Option<String>,
+	/// Equirectangular `.hdr` or `.exr` image to light the model with and draw as the sky.
+	pub environment: Option<String>,
+	/// MSAA sample count, 4 when unset.
+	pub msaa: Option<u32>,
+	/// Render mode to start in instead of solid.
+	pub mode: Option<RenderMode>,
+	/// Matcap sphere image for the matcap render mode, instead of the built-in clay.
+	pub matcap: Option<String>,
+	/// Mesh shader to use instead of the built-in `shader.wgsl`, reloaded whenever it changes.
+	pub shader: Option<String>,
+	/// Shading for the custom render mode, replacing `custom.wgsl` and reloaded whenever it
+	/// changes. Starts in that mode unless another is asked for.
+	pub fragment: Option<String>,
+	/// Tonemapper to start with instead of ACES.
+	pub tonemapper: Option<Tonemapper>,
+	/// Draw the file's vertices as a point cloud even if it has faces.
+	pub points: bool,
+}
+
+impl Options {
+	pub const USAGE: &'static str = "usage: objrs <file.obj|file.ply> [--instances <placements.csv|json>] [--lights <lights.json>] [--environment <sky.hdr|exr>] [--msaa <1|2|4|8>] [--mode <name>] [--matcap <image>] [--shader <shader.wgsl>] [--fragment <custom.wgsl>] [--tonemap <aces|agx|reinhard|none>] [--points]";
+
+	pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+		let mut options = Options::default();
+		let mut filename = None;
+		while let Some(arg) = args.next() {
+			let mut value = |name: &str| args.next().ok_or(format!("{} expects a value", name));
+			match arg.as_str() {
+				"--instances" => options.instances = Some(value(&arg)?),
+				"--lights" => options.lights = Some(value(&arg)?),
+				"--environment" => options.environment = Some(value(&arg)?),
+				"--msaa" => options.msaa = match value(&arg)?.as_str() {
+					n @ ("1" | "2" | "4" | "8") => Some(n.parse().unwrap()),
+					n => return Err(format!("--msaa expects 1, 2, 4 or 8, got {}", n)),
+				},
+				"--mode" => {
+					let name = value(&arg)?;
+					options.mode = Some(RenderMode::from_name(&name)
+						.ok_or_else(|| format!("unknown render mode {}, expected one of {}", name, RenderMode::names().join(", ")))?);
+				}
+				"--matcap" => options.matcap = Some(value(&arg)?),
+				"--shader" => options.shader = Some(value(&arg)?),
+				"--fragment" => options.fragment = Some(value(&arg)?),
+				"--tonemap" => {
+					let name = value(&arg)?;
+					options.tonemapper = Some(Tonemapper::from_name(&name)
+						.ok_or_else(|| format!("unknown tonemapper {}, expected one of {}", name, Tonemapper::names().join(", ")))?);
+				}
+				"--points" => options.points = true,
+				_ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+				_ if filename.is_none() => filename = Some(arg),
+				_ => return Err(format!("unexpected argument {}", arg)),
+			}
+		}
+		options.filename = filename.ok_or("missing model filename")?;
+		Ok(options)
+	}
+}
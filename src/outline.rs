@@ -0,0 +1,345 @@
+use wgpu::util::DeviceExt;
+
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::model::{Draw, Mesh, Vertex};
+use crate::settings::Setting;
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+	color: [f32; 4],
+	width: f32,
+	_padding: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FloodUniform {
+	step: i32,
+	_padding: [i32; 3],
+}
+
+/// Outlines around the selected and hovered objects, drawn over the finished frame. The objects
+/// are drawn into a seed texture without depth testing, so hidden parts are outlined too, and
+/// jump flooding spreads the nearest seed to every pixel within `width` of them.
+pub struct Outline {
+	mask_pipelines: [wgpu::RenderPipeline; 2],
+	flood_pipeline: wgpu::RenderPipeline,
+	composite_pipeline: wgpu::RenderPipeline,
+	bind_group_layout: wgpu::BindGroupLayout,
+	/// Read from each of `seeds`, in that order.
+	bind_groups: [wgpu::BindGroup; 2],
+	uniform_buffer: wgpu::Buffer,
+	flood_buffer: wgpu::Buffer,
+	/// Distance between the flood passes' uniforms.
+	flood_stride: u32,
+	/// Ping-ponged between flood passes.
+	seeds: [Texture; 2],
+	/// Index into `COLORS`.
+	pub color: usize,
+	/// In pixels.
+	pub width: f32,
+}
+
+impl Outline {
+	pub const COLORS: [(&'static str, [f32; 4]); 6] = [
+		("orange", [1.0, 0.6, 0.1, 1.0]),
+		("yellow", [1.0, 0.9, 0.2, 1.0]),
+		("white", [1.0, 1.0, 1.0, 1.0]),
+		("cyan", [0.2, 0.9, 1.0, 1.0]),
+		("green", [0.3, 1.0, 0.3, 1.0]),
+		("magenta", [1.0, 0.3, 0.9, 1.0]),
+	];
+	const SEED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+	pub const MAX_WIDTH: f32 = 32.0;
+	/// Enough passes to flood `MAX_WIDTH` pixels: steps of 32, 16, 8, 4, 2 and 1.
+	const MAX_PASSES: u32 = 6;
+
+	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, camera_layout: &wgpu::BindGroupLayout, geometry_layout: &wgpu::BindGroupLayout) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Outline Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("outline.wgsl").into()),
+		});
+
+		let uniform_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Outline Buffer"),
+				contents: bytemuck::cast_slice(&[OutlineUniform { color: [1.0; 4], width: 1.0, _padding: [0.0; 3] }]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		// every pass's step size is in the buffer up front, each pass binding its own slot
+		let flood_stride = device.limits().min_uniform_buffer_offset_alignment;
+		let flood_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Outline Flood Buffer"),
+			size: (Self::MAX_PASSES * flood_stride) as u64,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: true,
+						min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<FloodUniform>() as u64),
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 2,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: false },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+			],
+			label: Some("outline_bind_group_layout"),
+		});
+
+		let mask_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Outline Mask Pipeline Layout"),
+			bind_group_layouts: &[camera_layout, geometry_layout],
+			push_constant_ranges: &[],
+		});
+
+		let mask = |label, entry_point| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some(label),
+			layout: Some(&mask_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_mask",
+				buffers: &[
+					Vertex::desc(),
+					InstanceRaw::desc(),
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point,
+				targets: &[Some(Self::SEED_FORMAT.into())],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				cull_mode: None,
+				..Default::default()
+			},
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+		let mask_pipelines = [mask("Outline Selected Pipeline", "fs_selected"), mask("Outline Hovered Pipeline", "fs_hovered")];
+
+		let pass_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Outline Pipeline Layout"),
+			bind_group_layouts: &[&bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let fullscreen = |label, entry_point, target: wgpu::ColorTargetState| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some(label),
+			layout: Some(&pass_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_fullscreen",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point,
+				targets: &[Some(target)],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+		let flood_pipeline = fullscreen("Outline Flood Pipeline", "fs_flood", Self::SEED_FORMAT.into());
+		let composite_pipeline = fullscreen("Outline Composite Pipeline", "fs_composite", wgpu::ColorTargetState {
+			format: config.format,
+			blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+			write_mask: wgpu::ColorWrites::ALL,
+		});
+
+		let seeds = Self::create_seeds(device, config);
+		let bind_groups = Self::create_bind_groups(device, &bind_group_layout, &uniform_buffer, &flood_buffer, &seeds);
+
+		Self {
+			mask_pipelines,
+			flood_pipeline,
+			composite_pipeline,
+			bind_group_layout,
+			bind_groups,
+			uniform_buffer,
+			flood_buffer,
+			flood_stride,
+			seeds,
+			color: 0,
+			width: 3.0,
+		}
+	}
+
+	pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+		self.seeds = Self::create_seeds(device, config);
+		self.bind_groups = Self::create_bind_groups(device, &self.bind_group_layout, &self.uniform_buffer, &self.flood_buffer, &self.seeds);
+	}
+
+	/// Flood steps for the current width, largest first.
+	fn steps(&self) -> Vec<i32> {
+		let passes = (self.width.max(1.0).log2().ceil() as u32 + 1).min(Self::MAX_PASSES);
+		(0..passes).rev().map(|k| 1 << k).collect()
+	}
+
+	pub fn settings(&self) -> &'static [Setting] {
+		&[Setting::OutlineWidth, Setting::OutlineColor]
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::OutlineWidth => self.width = (self.width + steps).clamp(1.0, Self::MAX_WIDTH),
+			Setting::OutlineColor => self.color = (self.color as i32 + steps as i32).rem_euclid(Self::COLORS.len() as i32) as usize,
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::OutlineWidth => Some(format!("{} px", self.width)),
+			Setting::OutlineColor => Some(Self::COLORS[self.color].0.into()),
+			_ => None,
+		}
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue) {
+		let uniform = OutlineUniform { color: Self::COLORS[self.color].1, width: self.width, _padding: [0.0; 3] };
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+		for (i, step) in self.steps().into_iter().enumerate() {
+			let flood = FloodUniform { step, _padding: [0; 3] };
+			queue.write_buffer(&self.flood_buffer, (i as u32 * self.flood_stride) as u64, bytemuck::cast_slice(&[flood]));
+		}
+	}
+
+	/// Outlines `selected` and `hovered` over `target`. Nothing is drawn when both are `None`.
+	#[allow(clippy::too_many_arguments)]
+	pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, camera_bind_group: &wgpu::BindGroup, model: &Mesh, instances: &InstanceBuffer, selected: Option<&Draw>, hovered: Option<&Draw>) {
+		// the selection wins where both are outlined
+		let hovered = hovered.filter(|draw| Some(*draw) != selected);
+		if selected.is_none() && hovered.is_none() {
+			return;
+		}
+
+		{
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("Outline Mask Pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: &self.seeds[0].view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+						store: wgpu::StoreOp::Store,
+					},
+				})],
+				depth_stencil_attachment: None,
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+			render_pass.set_bind_group(0, camera_bind_group, &[]);
+			for (pipeline, draw) in [(&self.mask_pipelines[1], hovered), (&self.mask_pipelines[0], selected)] {
+				if let Some(draw) = draw {
+					render_pass.set_pipeline(pipeline);
+					model.draw_geometry(&mut render_pass, instances, std::slice::from_ref(draw));
+				}
+			}
+		}
+
+		let steps = self.steps();
+		for i in 0..steps.len() {
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("Outline Flood Pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: &self.seeds[(i + 1) % 2].view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+						store: wgpu::StoreOp::Store,
+					},
+				})],
+				depth_stencil_attachment: None,
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+			render_pass.set_pipeline(&self.flood_pipeline);
+			render_pass.set_bind_group(0, &self.bind_groups[i % 2], &[i as u32 * self.flood_stride]);
+			render_pass.draw(0..3, 0..1);
+		}
+
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Outline Composite Pass"),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: target,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Load,
+					store: wgpu::StoreOp::Store,
+				},
+			})],
+			depth_stencil_attachment: None,
+			occlusion_query_set: None,
+			timestamp_writes: None,
+		});
+		render_pass.set_pipeline(&self.composite_pipeline);
+		render_pass.set_bind_group(0, &self.bind_groups[steps.len() % 2], &[0]);
+		render_pass.draw(0..3, 0..1);
+	}
+
+	fn create_seeds(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> [Texture; 2] {
+		[
+			Texture::new_render_target(device, config, Self::SEED_FORMAT, "outline_seeds_a"),
+			Texture::new_render_target(device, config, Self::SEED_FORMAT, "outline_seeds_b"),
+		]
+	}
+
+	fn create_bind_groups(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, flood_buffer: &wgpu::Buffer, seeds: &[Texture; 2]) -> [wgpu::BindGroup; 2] {
+		seeds.each_ref().map(|input| device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: uniform_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+						buffer: flood_buffer,
+						offset: 0,
+						size: wgpu::BufferSize::new(std::mem::size_of::<FloodUniform>() as u64),
+					}),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(&input.view),
+				},
+			],
+			label: Some("outline_bind_group"),
+		}))
+	}
+}
@@ -0,0 +1,115 @@
+// Screen-space outlines by jump flooding. The outlined objects are drawn as seeds, each flood
+// pass points every pixel at the nearest seed found `step` pixels away, and the composite pass
+// colors pixels by their distance to it.
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct Outline {
+	color: vec4<f32>,
+	// in pixels
+	width: f32,
+}
+
+struct Flood {
+	step: i32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// where the exploded view has moved the submesh being drawn, see `Mesh::draw_geometry`
+struct Submesh {
+	object: u32,
+	material: u32,
+	offset: vec3<f32>,
+}
+
+@group(1) @binding(3)
+var<uniform> submesh: Submesh;
+
+@group(0) @binding(0)
+var<uniform> outline: Outline;
+
+@group(0) @binding(1)
+var<uniform> flood: Flood;
+
+// xy: offset from the pixel to its nearest seed, z: the seed's strength, w: 1 once a seed is known
+@group(0) @binding(2)
+var seeds: texture_2d<f32>;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+}
+
+struct InstanceIn {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+}
+
+@vertex
+fn vs_mask(model: VertexIn, instance: InstanceIn) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    return camera.view_proj * model_matrix * vec4<f32>(model.position + submesh.offset, 1.0);
+}
+
+// every covered pixel is its own seed
+@fragment
+fn fs_selected() -> @location(0) vec4<f32> {
+	return vec4<f32>(0.0, 0.0, 1.0, 1.0);
+}
+
+// hovered objects get a fainter outline
+@fragment
+fn fs_hovered() -> @location(0) vec4<f32> {
+	return vec4<f32>(0.0, 0.0, 0.5, 1.0);
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // one triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_flood(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let size = vec2<i32>(textureDimensions(seeds));
+	let pixel = vec2<i32>(position.xy);
+	var best = vec4<f32>(0.0);
+	var best_distance = 1e20;
+	for (var i = 0; i < 9; i++) {
+		let q = pixel + vec2<i32>(i % 3 - 1, i / 3 - 1) * flood.step;
+		if any(q < vec2<i32>(0)) || any(q >= size) {
+			continue;
+		}
+		let texel = textureLoad(seeds, q, 0);
+		if texel.w == 0.0 {
+			continue;
+		}
+		// offsets rather than positions are stored, so half floats stay exact on large screens
+		let offset = vec2<f32>(q - pixel) + texel.xy;
+		let distance = dot(offset, offset);
+		if distance < best_distance {
+			best_distance = distance;
+			best = vec4<f32>(offset, texel.z, 1.0);
+		}
+	}
+	return best;
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let texel = textureLoad(seeds, vec2<i32>(position.xy), 0);
+	let distance = length(texel.xy);
+	// pixels covered by the objects are their own seeds and stay clear
+	if texel.w == 0.0 || distance == 0.0 {
+		discard;
+	}
+	let coverage = clamp(outline.width + 0.5 - distance, 0.0, 1.0);
+	return vec4<f32>(outline.color.rgb, outline.color.a * texel.z * coverage);
+}
@@ -0,0 +1,160 @@
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+use winit::{dpi::PhysicalPosition, event::*};
+
+use crate::bounds::BoundingBox;
+use crate::camera::Camera;
+use crate::instance::Instance;
+use crate::model::{Draw, Mesh};
+
+/// Half-line `origin + t * direction`, t >= 0. `direction` is not normalized, so distances
+/// along rays transformed by a non-uniform scale still compare.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+	pub origin: Vector3<f32>,
+	pub direction: Vector3<f32>,
+}
+
+impl Ray {
+	/// Ray from the eye through the pixel at `cursor`, in physical pixels from the top left.
+	pub fn through_pixel(camera: &Camera, cursor: [f32; 2], viewport: [f32; 2]) -> Self {
+		let ndc = Vector4::new(cursor[0] / viewport[0] * 2.0 - 1.0, 1.0 - cursor[1] / viewport[1] * 2.0, 1.0, 1.0);
+		let far = camera.view_proj().invert().unwrap_or(Matrix4::identity()) * ndc;
+		let origin = camera.transform.w.truncate();
+		Self {
+			origin,
+			direction: far.truncate() / far.w - origin,
+		}
+	}
+
+	pub fn transform(&self, m: &Matrix4<f32>) -> Self {
+		Self {
+			origin: (m * self.origin.extend(1.0)).truncate(),
+			direction: (m * self.direction.extend(0.0)).truncate(),
+		}
+	}
+
+	/// Where the ray enters `aabb`, if it does before `t_max`.
+	fn hit_box(&self, aabb: &BoundingBox, t_max: f32) -> Option<f32> {
+		let (mut near, mut far) = (0.0_f32, t_max);
+		for axis in 0..3 {
+			let inv = 1.0 / self.direction[axis];
+			let t0 = (aabb.min[axis] - self.origin[axis]) * inv;
+			let t1 = (aabb.max[axis] - self.origin[axis]) * inv;
+			near = near.max(t0.min(t1));
+			far = far.min(t0.max(t1));
+		}
+		(near <= far).then_some(near)
+	}
+
+	/// Möller–Trumbore, hitting either side of the triangle.
+	fn hit_triangle(&self, a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Option<f32> {
+		let ab = b - a;
+		let ac = c - a;
+		let p = self.direction.cross(ac);
+		let det = ab.dot(p);
+		if det == 0.0 {
+			return None;
+		}
+		let s = self.origin - a;
+		let u = s.dot(p) / det;
+		let q = s.cross(ab);
+		let v = self.direction.dot(q) / det;
+		let t = ac.dot(q) / det;
+		(u >= 0.0 && v >= 0.0 && u + v <= 1.0 && t >= 0.0).then_some(t)
+	}
+}
+
+/// The nearest submesh instance hit by `ray`, as a draw of that one instance. Each instance is
+/// tested in its own space, skipping submeshes whose boxes are missed or farther than the best hit.
+pub fn pick(model: &Mesh, instances: &[Instance], ray: &Ray) -> Option<Draw> {
+	let mut best: Option<Draw> = None;
+	let mut nearest = f32::INFINITY;
+	for (i, instance) in instances.iter().enumerate() {
+		let Some(inverse) = instance.transform.invert() else { continue };
+		let local = ray.transform(&inverse);
+		for (submesh_index, submesh) in model.submeshes.iter().enumerate() {
+			// exploded submeshes are tested where they were loaded, with the ray moved the other way
+			let local = Ray { origin: local.origin - submesh.offset, ..local };
+			if local.hit_box(&submesh.bounds.aabb, nearest).is_none() {
+				continue;
+			}
+			let indices = &model.indices[submesh.indices.start as usize..submesh.indices.end as usize];
+			for triangle in indices.chunks_exact(3) {
+				let [a, b, c] = [0, 1, 2].map(|k| Vector3::from(model.vertices[triangle[k] as usize].position));
+				if let Some(t) = local.hit_triangle(a, b, c).filter(|&t| t < nearest) {
+					nearest = t;
+					best = Some(Draw { submesh: submesh_index, instances: i as u32..i as u32 + 1 });
+				}
+			}
+		}
+	}
+	best
+}
+
+/// The object instance clicked last, and the one under the cursor.
+#[derive(Default)]
+pub struct Selection {
+	pub selected: Option<Draw>,
+	pub hovered: Option<Draw>,
+	cursor: PhysicalPosition<f64>,
+	/// Where the left button went down, to tell clicks from drags.
+	press: Option<PhysicalPosition<f64>>,
+}
+
+impl Selection {
+	/// Follows the cursor, picking what is under it except while orbiting. A click selects it, or
+	/// clears the selection over the background, and `[` and `]` step through the submeshes of the
+	/// selected instance. Returns the status to show when the selection changes.
+	pub fn input(&mut self, event: &WindowEvent, camera: &Camera, viewport: [f32; 2], model: &Mesh, instances: &[Instance]) -> Option<String> {
+		let pick_at = |cursor: PhysicalPosition<f64>| {
+			pick(model, instances, &Ray::through_pixel(camera, [cursor.x as f32, cursor.y as f32], viewport))
+		};
+		match event {
+			WindowEvent::CursorMoved { position, .. } => {
+				self.cursor = *position;
+				if self.press.is_none() {
+					self.hovered = pick_at(self.cursor);
+				}
+			}
+			WindowEvent::CursorLeft { .. } => self.hovered = None,
+			WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => self.press = Some(self.cursor),
+			WindowEvent::MouseInput { state: ElementState::Released, button: MouseButton::Left, .. } => {
+				if let Some(press) = self.press.take() {
+					if (press.x - self.cursor.x).abs() + (press.y - self.cursor.y).abs() < 4.0 {
+						self.hovered = pick_at(self.cursor);
+						self.selected = self.hovered.clone();
+						return Some(self.status(model, instances.len()));
+					}
+				}
+			}
+			WindowEvent::KeyboardInput {
+				input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(keycode @ (VirtualKeyCode::LBracket | VirtualKeyCode::RBracket)), .. },
+				..
+			} => {
+				let step = if *keycode == VirtualKeyCode::LBracket { -1 } else { 1 };
+				self.select_next(step, model.submeshes.len(), instances.len());
+				return Some(self.status(model, instances.len()));
+			}
+			_ => {}
+		}
+		None
+	}
+
+	/// Steps the selection through the submeshes of the selected instance, and through nothing
+	/// after the last one.
+	fn select_next(&mut self, step: i32, submeshes: usize, instances: usize) {
+		let count = submeshes as i32;
+		let instance = self.selected.as_ref().map_or(0, |draw| draw.instances.start);
+		let current = self.selected.as_ref().map_or(count, |draw| draw.submesh as i32);
+		let next = (current + step).rem_euclid(count + 1);
+		self.selected = (next < count && (instance as usize) < instances).then(|| Draw { submesh: next as usize, instances: instance..instance + 1 });
+	}
+
+	fn status(&self, model: &Mesh, instances: usize) -> String {
+		match &self.selected {
+			Some(draw) if instances > 1 => format!("{} ({}/{})", model.submeshes[draw.submesh].name, draw.instances.start + 1, instances),
+			Some(draw) => model.submeshes[draw.submesh].name.clone(),
+			None => "nothing selected".into(),
+		}
+	}
+}
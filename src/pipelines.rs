@@ -0,0 +1,341 @@
+use crate::instance::InstanceRaw;
+use crate::lines::LineVertex;
+use crate::model::Vertex;
+use crate::point_cloud::PointVertex;
+use crate::texture::Texture;
+
+/// The scene's render pipelines, along with the shaders and layouts they're built from so they
+/// can be rebuilt when the sample count or the mesh shader changes.
+pub struct Pipelines {
+	shader: wgpu::ShaderModule,
+	line_shader: wgpu::ShaderModule,
+	point_shader: wgpu::ShaderModule,
+	render_layout: wgpu::PipelineLayout,
+	line_layout: wgpu::PipelineLayout,
+	point_layout: wgpu::PipelineLayout,
+	format: wgpu::TextureFormat,
+
+	pub render: wgpu::RenderPipeline,
+	/// Edges only, blended over whatever is behind them.
+	pub wireframe: wgpu::RenderPipeline,
+	/// Like `render`, but fetching whole triangles, see `Mesh::draw_triangles`.
+	pub triangles: wgpu::RenderPipeline,
+	/// `render` and `triangles` blended over what's behind them without writing depth,
+	/// for submeshes with a dissolve below 1.
+	pub transparent: wgpu::RenderPipeline,
+	pub transparent_triangles: wgpu::RenderPipeline,
+	/// Fills triangles white and writes their depth, for hidden line edges to be tested against.
+	pub hidden_line_fill: wgpu::RenderPipeline,
+	/// Edges blended over the hidden line fill where they aren't behind it.
+	pub hidden_line_edges: wgpu::RenderPipeline,
+	/// Blended without writing depth or culling, so every surface shows through the ones in front.
+	pub xray: wgpu::RenderPipeline,
+	pub line: wgpu::RenderPipeline,
+	pub point: wgpu::RenderPipeline,
+	pub sample_count: u32,
+	/// Whether the mesh pipelines draw back faces instead of culling them.
+	pub two_sided: bool,
+}
+
+impl Pipelines {
+	/// `source` is the mesh shader's, see `ShaderSource`.
+	/// `render_groups` are the bind group layouts of the mesh shader, `point_groups` those of the point shader.
+	/// The wireframe pipelines share the mesh shader's layout.
+	/// The line shader only uses the first (camera) group.
+	pub fn new(
+		device: &wgpu::Device,
+		format: wgpu::TextureFormat,
+		source: &str,
+		render_groups: &[&wgpu::BindGroupLayout],
+		point_groups: &[&wgpu::BindGroupLayout],
+		sample_count: u32,
+	) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Shader"),
+			source: wgpu::ShaderSource::Wgsl(source.into()),
+		});
+
+		let line_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Line Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("lines.wgsl").into()),
+		});
+
+		let point_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Point Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("points.wgsl").into()),
+		});
+
+		let render_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Render Pipeline Layout"),
+			bind_group_layouts: render_groups,
+			push_constant_ranges: &[],
+		});
+
+		let line_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Line Pipeline Layout"),
+			bind_group_layouts: &render_groups[..1],
+			push_constant_ranges: &[],
+		});
+
+		let point_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Point Pipeline Layout"),
+			bind_group_layouts: point_groups,
+			push_constant_ranges: &[],
+		});
+
+		let [render, wireframe, triangles, transparent, transparent_triangles, hidden_line_fill, hidden_line_edges, xray, line, point] = Self::build(device, format, &shader, &line_shader, &point_shader, &render_layout, &line_layout, &point_layout, sample_count, false);
+
+		Self {
+			shader,
+			line_shader,
+			point_shader,
+			render_layout,
+			line_layout,
+			point_layout,
+			format,
+			render,
+			wireframe,
+			triangles,
+			transparent,
+			transparent_triangles,
+			hidden_line_fill,
+			hidden_line_edges,
+			xray,
+			line,
+			point,
+			sample_count,
+			two_sided: false,
+		}
+	}
+
+	pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+		self.sample_count = sample_count;
+		self.rebuild(device);
+	}
+
+	pub fn set_two_sided(&mut self, device: &wgpu::Device, two_sided: bool) {
+		self.two_sided = two_sided;
+		self.rebuild(device);
+	}
+
+	/// Rebuilds the pipelines with a new mesh shader. If it doesn't compile, or doesn't fit the
+	/// pipelines' layouts, the old one stays in use and wgpu's error is returned.
+	pub fn set_shader(&mut self, device: &wgpu::Device, source: &str) -> Result<(), String> {
+		// validation errors would otherwise go to the device's handler, which panics
+		device.push_error_scope(wgpu::ErrorFilter::Validation);
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Shader"),
+			source: wgpu::ShaderSource::Wgsl(source.into()),
+		});
+		let pipelines = self.build_with(device, &shader);
+		if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+			return Err(error.to_string());
+		}
+		self.shader = shader;
+		self.set(pipelines);
+		Ok(())
+	}
+
+	fn rebuild(&mut self, device: &wgpu::Device) {
+		let pipelines = self.build_with(device, &self.shader);
+		self.set(pipelines);
+	}
+
+	fn set(&mut self, pipelines: [wgpu::RenderPipeline; 10]) {
+		[
+			self.render,
+			self.wireframe,
+			self.triangles,
+			self.transparent,
+			self.transparent_triangles,
+			self.hidden_line_fill,
+			self.hidden_line_edges,
+			self.xray,
+			self.line,
+			self.point,
+		] = pipelines;
+	}
+
+	fn build_with(&self, device: &wgpu::Device, shader: &wgpu::ShaderModule) -> [wgpu::RenderPipeline; 10] {
+		Self::build(
+			device,
+			self.format,
+			shader,
+			&self.line_shader,
+			&self.point_shader,
+			&self.render_layout,
+			&self.line_layout,
+			&self.point_layout,
+			self.sample_count,
+			self.two_sided,
+		)
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn build(
+		device: &wgpu::Device,
+		format: wgpu::TextureFormat,
+		shader: &wgpu::ShaderModule,
+		line_shader: &wgpu::ShaderModule,
+		point_shader: &wgpu::ShaderModule,
+		render_layout: &wgpu::PipelineLayout,
+		line_layout: &wgpu::PipelineLayout,
+		point_layout: &wgpu::PipelineLayout,
+		sample_count: u32,
+		two_sided: bool,
+	) -> [wgpu::RenderPipeline; 10] {
+		let targets = &[Some(wgpu::ColorTargetState {
+			format,
+			blend: Some(wgpu::BlendState::REPLACE),
+			write_mask: wgpu::ColorWrites::ALL,
+		})];
+
+		let multisample = wgpu::MultisampleState {
+			count: sample_count,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		};
+
+		let render_buffers = &[
+			Vertex::desc(),
+			InstanceRaw::desc(),
+		];
+
+		let mut pipeline_descriptor = wgpu::RenderPipelineDescriptor {
+			label: Some("Render Pipeline"),
+			layout: Some(render_layout),
+			vertex: wgpu::VertexState {
+				module: shader,
+				entry_point: "vs_main",
+				buffers: render_buffers,
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: shader,
+				entry_point: "fs_main",
+				targets,
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: if two_sided { None } else { Some(wgpu::Face::Back) },
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::Less,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample,
+			multiview: None,
+		};
+
+		let render = device.create_render_pipeline(&pipeline_descriptor);
+
+		// edges are found from barycentrics rather than drawn with PolygonMode::Line, which
+		// needs a native-only feature and can't be wider than a pixel
+		let triangle_buffers = &[InstanceRaw::desc()];
+		pipeline_descriptor.vertex.entry_point = "vs_triangles";
+		pipeline_descriptor.vertex.buffers = triangle_buffers;
+		let triangles = device.create_render_pipeline(&pipeline_descriptor);
+
+		// the same vertex stage as the edges, so they land on exactly the depth it writes
+		pipeline_descriptor.fragment.as_mut().unwrap().entry_point = "fs_fill";
+		let hidden_line_fill = device.create_render_pipeline(&pipeline_descriptor);
+		pipeline_descriptor.fragment.as_mut().unwrap().entry_point = "fs_main";
+
+		let blended = &[Some(wgpu::ColorTargetState {
+			format,
+			blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+			write_mask: wgpu::ColorWrites::ALL,
+		})];
+		pipeline_descriptor.fragment.as_mut().unwrap().targets = blended;
+		// sorted back to front, so depth is tested but not written
+		pipeline_descriptor.depth_stencil.as_mut().unwrap().depth_write_enabled = false;
+		let transparent_triangles = device.create_render_pipeline(&pipeline_descriptor);
+
+		pipeline_descriptor.vertex.entry_point = "vs_main";
+		pipeline_descriptor.vertex.buffers = render_buffers;
+		let transparent = device.create_render_pipeline(&pipeline_descriptor);
+
+		pipeline_descriptor.vertex.entry_point = "vs_triangles";
+		pipeline_descriptor.vertex.buffers = triangle_buffers;
+		pipeline_descriptor.depth_stencil.as_mut().unwrap().depth_write_enabled = true;
+		pipeline_descriptor.primitive.cull_mode = None;
+		let wireframe = device.create_render_pipeline(&pipeline_descriptor);
+
+		let depth_stencil = pipeline_descriptor.depth_stencil.as_mut().unwrap();
+		depth_stencil.depth_write_enabled = false;
+		depth_stencil.depth_compare = wgpu::CompareFunction::LessEqual;
+		let hidden_line_edges = device.create_render_pipeline(&pipeline_descriptor);
+
+		pipeline_descriptor.vertex.entry_point = "vs_main";
+		pipeline_descriptor.vertex.buffers = render_buffers;
+		pipeline_descriptor.depth_stencil.as_mut().unwrap().depth_compare = wgpu::CompareFunction::Less;
+		let xray = device.create_render_pipeline(&pipeline_descriptor);
+		// points write depth for eye-dome lighting like the opaque meshes
+		pipeline_descriptor.depth_stencil.as_mut().unwrap().depth_write_enabled = true;
+
+		let line = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Line Render Pipeline"),
+			layout: Some(line_layout),
+			vertex: wgpu::VertexState {
+				module: line_shader,
+				entry_point: "vs_main",
+				buffers: &[
+					LineVertex::desc()
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: line_shader,
+				entry_point: "fs_main",
+				targets,
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::LineList,
+				cull_mode: None,
+				..Default::default()
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: false,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample,
+			multiview: None,
+		});
+
+		let point = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Point Render Pipeline"),
+			layout: Some(point_layout),
+			vertex: wgpu::VertexState {
+				module: point_shader,
+				entry_point: "vs_main",
+				buffers: &[
+					PointVertex::desc()
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: point_shader,
+				entry_point: "fs_main",
+				targets,
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleStrip,
+				cull_mode: None,
+				..Default::default()
+			},
+			depth_stencil: pipeline_descriptor.depth_stencil.clone(),
+			multisample,
+			multiview: None,
+		});
+
+		[render, wireframe, triangles, transparent, transparent_triangles, hidden_line_fill, hidden_line_edges, xray, line, point]
+	}
+}
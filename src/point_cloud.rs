@@ -0,0 +1,484 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use wgpu::util::DeviceExt;
+use cgmath::Vector3;
+use winit::event::VirtualKeyCode;
+
+use crate::bounds::Bounds;
+use crate::settings::Setting;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointVertex {
+	pub position: [f32; 3],
+	pub color: [f32; 3],
+}
+
+impl PointVertex {
+	/// Points are stepped per instance; each instance expands into one camera-facing quad.
+	pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+		wgpu::VertexBufferLayout {
+			array_stride: std::mem::size_of::<PointVertex>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Instance,
+			attributes: &[
+				wgpu::VertexAttribute {
+					offset: 0,
+					shader_location: 0,
+					format: wgpu::VertexFormat::Float32x3,
+				},
+				wgpu::VertexAttribute {
+					offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+					shader_location: 1,
+					format: wgpu::VertexFormat::Float32x3,
+				}
+			]
+		}
+	}
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PointUniform {
+	pub viewport: [f32; 2],
+	pub size: f32,
+	/// `proj[1][1]`, used to turn a world size into pixels.
+	pub focal: f32,
+	pub flags: u32,
+	_padding: [u32; 3],
+}
+
+impl PointUniform {
+	pub const WORLD_UNITS: u32 = 1;
+	pub const ROUND: u32 = 2;
+	pub const VERTEX_COLOR: u32 = 4;
+
+	pub fn new(viewport: [f32; 2], size: f32, focal: f32, flags: u32) -> Self {
+		Self {
+			viewport,
+			size,
+			focal,
+			flags,
+			_padding: [0; 3],
+		}
+	}
+}
+
+/// How splats are sized and colored.
+#[derive(Copy, Clone, Debug)]
+pub struct PointStyle {
+	pub size_px: f32,
+	pub size_world: f32,
+	pub world_units: bool,
+	pub round: bool,
+	pub vertex_color: bool,
+}
+
+impl PointStyle {
+	/// Scales whichever size is in use.
+	pub fn scale_size(&mut self, factor: f32) {
+		if self.world_units {
+			self.size_world *= factor;
+		} else {
+			self.size_px = (self.size_px * factor).max(1.0);
+		}
+	}
+
+	/// U switches the size between pixels and world units, O between round and square splats, and
+	/// C turns vertex colors on and off.
+	pub fn input(&mut self, keycode: VirtualKeyCode) -> Option<String> {
+		match keycode {
+			VirtualKeyCode::U => self.world_units = !self.world_units,
+			VirtualKeyCode::O => self.round = !self.round,
+			VirtualKeyCode::C => self.vertex_color = !self.vertex_color,
+			_ => return None,
+		}
+		let shape = if self.round { "round" } else { "square" };
+		let color = if self.vertex_color { "vertex colors" } else { "white" };
+		Some(format!("points: {}, {}, {}", self.size(), shape, color))
+	}
+
+	pub fn settings(&self) -> &'static [Setting] {
+		&[Setting::PointSize]
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		if setting != Setting::PointSize {
+			return false;
+		}
+		self.scale_size(1.25_f32.powf(steps));
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		(setting == Setting::PointSize).then(|| self.size())
+	}
+
+	fn size(&self) -> String {
+		if self.world_units {
+			format!("{} units", self.size_world)
+		} else {
+			format!("{} px", self.size_px)
+		}
+	}
+
+	pub fn uniform(&self, viewport: [f32; 2], focal: f32) -> PointUniform {
+		let mut flags = 0;
+		if self.world_units {
+			flags |= PointUniform::WORLD_UNITS;
+		}
+		if self.round {
+			flags |= PointUniform::ROUND;
+		}
+		if self.vertex_color {
+			flags |= PointUniform::VERTEX_COLOR;
+		}
+		let size = if self.world_units { self.size_world } else { self.size_px };
+		PointUniform::new(viewport, size, focal, flags)
+	}
+}
+
+/// Vertices drawn as splats, for files with no faces.
+pub struct PointCloud {
+	vertex_buffer: wgpu::Buffer,
+	n: u32,
+	pub bounds: Bounds,
+	pub has_color: bool,
+}
+
+impl PointCloud {
+	/// Loads `.ply` vertices, or the `v` lines of any other (OBJ) file.
+	pub fn load<P: AsRef<Path>>(device: &wgpu::Device, path: P) -> Result<Self, Box<dyn std::error::Error>> {
+		let path = path.as_ref();
+		let is_ply = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("ply"));
+		let (points, has_color) = if is_ply {
+			read_ply(path)?
+		} else {
+			read_obj(path)?
+		};
+		Ok(Self::from_points(device, &points, has_color))
+	}
+
+	pub fn from_points(device: &wgpu::Device, points: &[PointVertex], has_color: bool) -> Self {
+		let vertex_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Point Buffer"),
+				contents: bytemuck::cast_slice(points),
+				usage: wgpu::BufferUsages::VERTEX,
+			}
+		);
+
+		let positions: Vec<Vector3<f32>> = points.iter().map(|p| Vector3::from(p.position)).collect();
+
+		Self {
+			vertex_buffer,
+			n: points.len() as u32,
+			bounds: Bounds::from_points(&positions),
+			has_color,
+		}
+	}
+
+	pub fn len(&self) -> u32 {
+		self.n
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.n == 0
+	}
+
+	pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		if self.n == 0 {
+			return;
+		}
+		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+		render_pass.draw(0..4, 0..self.n);
+	}
+}
+
+const DEFAULT_COLOR: [f32; 3] = [0.8, 0.8, 0.8];
+
+/// Reads `v x y z [r g b]` lines, ignoring everything else.
+fn read_obj(path: &Path) -> Result<(Vec<PointVertex>, bool), Box<dyn std::error::Error>> {
+	let reader = BufReader::new(std::fs::File::open(path)?);
+	let mut points = vec![];
+	let mut has_color = false;
+	for (n, line) in reader.lines().enumerate() {
+		let line = line?;
+		let mut fields = line.split_whitespace();
+		if fields.next() != Some("v") {
+			continue;
+		}
+
+		let values: Result<Vec<f32>, _> = fields.map(str::parse::<f32>).collect();
+		let values = values.map_err(|e| format!("line {}: {}", n + 1, e))?;
+		let color = match values.len() {
+			3 | 4 => DEFAULT_COLOR,
+			6 | 7 => {
+				has_color = true;
+				[values[3], values[4], values[5]]
+			}
+			_ => return Err(format!("line {}: malformed vertex", n + 1).into()),
+		};
+		points.push(PointVertex {
+			position: [values[0], values[1], values[2]],
+			color,
+		});
+	}
+	Ok((points, has_color))
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum PlyFormat {
+	Ascii,
+	LittleEndian,
+	BigEndian,
+}
+
+#[derive(Clone)]
+enum PlyProperty {
+	Scalar(String, String),
+	List(String, String),
+}
+
+struct PlyElement {
+	name: String,
+	count: usize,
+	properties: Vec<PlyProperty>,
+}
+
+fn read_ply(path: &Path) -> Result<(Vec<PointVertex>, bool), Box<dyn std::error::Error>> {
+	parse_ply(BufReader::new(std::fs::File::open(path)?))
+}
+
+/// Reads the `vertex` element of an ascii or binary PLY file, taking `x`/`y`/`z` and, when present,
+/// `red`/`green`/`blue` (as 8 or 16 bit integers, or normalized floats).
+fn parse_ply<R: BufRead>(mut reader: R) -> Result<(Vec<PointVertex>, bool), Box<dyn std::error::Error>> {
+	let mut format = None;
+	let mut elements: Vec<PlyElement> = vec![];
+	let mut line = String::new();
+	reader.read_line(&mut line)?;
+	if line.trim() != "ply" {
+		return Err("not a PLY file".into());
+	}
+	loop {
+		line.clear();
+		if reader.read_line(&mut line)? == 0 {
+			return Err("unexpected end of PLY header".into());
+		}
+		let fields: Vec<&str> = line.split_whitespace().collect();
+		match fields.as_slice() {
+			["format", "ascii", ..] => format = Some(PlyFormat::Ascii),
+			["format", "binary_little_endian", ..] => format = Some(PlyFormat::LittleEndian),
+			["format", "binary_big_endian", ..] => format = Some(PlyFormat::BigEndian),
+			["element", name, count] => elements.push(PlyElement {
+				name: name.to_string(),
+				count: count.parse()?,
+				properties: vec![],
+			}),
+			["property", "list", count_ty, item_ty, _] => elements.last_mut()
+				.ok_or("property before element")?
+				.properties.push(PlyProperty::List(count_ty.to_string(), item_ty.to_string())),
+			["property", ty, name] => elements.last_mut()
+				.ok_or("property before element")?
+				.properties.push(PlyProperty::Scalar(ty.to_string(), name.to_string())),
+			["end_header"] => break,
+			_ => {}
+		}
+	}
+	let format = format.ok_or("PLY header has no format")?;
+
+	let mut points = vec![];
+	let mut has_color = false;
+	let mut words = PlyWords::new(reader, format);
+	for element in elements.iter() {
+		if element.name != "vertex" {
+			// vertices are all we need; skip any element that precedes them
+			for _ in 0..element.count {
+				for property in element.properties.iter() {
+					match property {
+						PlyProperty::Scalar(ty, _) => { words.read(ty)?; }
+						PlyProperty::List(count_ty, item_ty) => {
+							for _ in 0..words.read(count_ty)? as usize {
+								words.read(item_ty)?;
+							}
+						}
+					}
+				}
+			}
+			continue;
+		}
+
+		let find = |name: &str| element.properties.iter().position(|p| matches!(p, PlyProperty::Scalar(_, n) if n == name));
+		let [x, y, z] = ["x", "y", "z"].map(find);
+		let [r, g, b] = ["red", "green", "blue"].map(find);
+		let (x, y, z) = (x.ok_or("PLY vertex has no x")?, y.ok_or("PLY vertex has no y")?, z.ok_or("PLY vertex has no z")?);
+		has_color = r.is_some() && g.is_some() && b.is_some();
+
+		let mut values = vec![0.0_f64; element.properties.len()];
+		for _ in 0..element.count {
+			for (i, property) in element.properties.iter().enumerate() {
+				values[i] = match property {
+					PlyProperty::Scalar(ty, _) => {
+						let v = words.read(ty)?;
+						if ![r, g, b].contains(&Some(i)) {
+							v
+						} else {
+							match ty.as_str() {
+								"uchar" | "uint8" => v / 255.0,
+								"ushort" | "uint16" => v / 65535.0,
+								_ => v,
+							}
+						}
+					}
+					PlyProperty::List(count_ty, item_ty) => {
+						for _ in 0..words.read(count_ty)? as usize {
+							words.read(item_ty)?;
+						}
+						0.0
+					}
+				};
+			}
+
+			let color = match (r, g, b) {
+				(Some(r), Some(g), Some(b)) => [values[r] as f32, values[g] as f32, values[b] as f32],
+				_ => DEFAULT_COLOR,
+			};
+			points.push(PointVertex {
+				position: [values[x] as f32, values[y] as f32, values[z] as f32],
+				color,
+			});
+		}
+		break;
+	}
+
+	Ok((points, has_color))
+}
+
+/// Reads PLY property values one at a time from either the ascii or binary body.
+struct PlyWords<R: BufRead> {
+	reader: R,
+	format: PlyFormat,
+	line: Vec<String>,
+}
+
+impl<R: BufRead> PlyWords<R> {
+	fn new(reader: R, format: PlyFormat) -> Self {
+		Self {
+			reader,
+			format,
+			line: vec![],
+		}
+	}
+
+	fn read(&mut self, ty: &str) -> Result<f64, Box<dyn std::error::Error>> {
+		if self.format == PlyFormat::Ascii {
+			while self.line.is_empty() {
+				let mut text = String::new();
+				if self.reader.read_line(&mut text)? == 0 {
+					return Err("unexpected end of PLY data".into());
+				}
+				self.line = text.split_whitespace().rev().map(String::from).collect();
+			}
+			return Ok(self.line.pop().unwrap().parse()?);
+		}
+
+		macro_rules! binary {
+			($t:ty) => {{
+				let mut bytes = [0u8; std::mem::size_of::<$t>()];
+				self.reader.read_exact(&mut bytes)?;
+				if self.format == PlyFormat::LittleEndian {
+					<$t>::from_le_bytes(bytes) as f64
+				} else {
+					<$t>::from_be_bytes(bytes) as f64
+				}
+			}};
+		}
+
+		Ok(match ty {
+			"char" | "int8" => binary!(i8),
+			"uchar" | "uint8" => binary!(u8),
+			"short" | "int16" => binary!(i16),
+			"ushort" | "uint16" => binary!(u16),
+			"int" | "int32" => binary!(i32),
+			"uint" | "uint32" => binary!(u32),
+			"float" | "float32" => binary!(f32),
+			"double" | "float64" => binary!(f64),
+			_ => return Err(format!("unknown PLY property type {}", ty).into()),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const HEADER: &str = "element vertex 2\nproperty float x\nproperty float y\nproperty float z\n";
+
+	fn binary(format: &str, color_ty: &str, rest: &[u8]) -> Vec<u8> {
+		let header = format!("ply\nformat {} 1.0\ncomment scanner\n{}property {} red\nproperty {} green\nproperty {} blue\nend_header\n", format, HEADER, color_ty, color_ty, color_ty);
+		[header.as_bytes(), rest].concat()
+	}
+
+	#[test]
+	fn ascii_with_uchar_color() {
+		let text = format!("ply\nformat ascii 1.0\nelement face 1\nproperty list uchar int vertex_indices\n{}property uchar red\nproperty uchar green\nproperty uchar blue\nend_header\n3 0 1 0\n1 2 3 255 0 51\n-1 0.5 0 0 255 0\n", HEADER);
+		let (points, has_color) = parse_ply(text.as_bytes()).unwrap();
+		assert!(has_color);
+		assert_eq!(points.len(), 2);
+		assert_eq!(points[0].position, [1.0, 2.0, 3.0]);
+		assert_eq!(points[0].color, [1.0, 0.0, 0.2]);
+		assert_eq!(points[1].position, [-1.0, 0.5, 0.0]);
+		assert_eq!(points[1].color, [0.0, 1.0, 0.0]);
+	}
+
+	#[test]
+	fn ascii_without_color() {
+		let text = format!("ply\nformat ascii 1.0\n{}end_header\n0 0 0\n1 1 1\n", HEADER);
+		let (points, has_color) = parse_ply(text.as_bytes()).unwrap();
+		assert!(!has_color);
+		assert_eq!(points[1].position, [1.0, 1.0, 1.0]);
+		assert_eq!(points[1].color, DEFAULT_COLOR);
+	}
+
+	#[test]
+	fn binary_little_endian_with_ushort_color() {
+		let mut body = vec![];
+		for (position, color) in [([1.0_f32, 2.0, 3.0], [65535_u16, 0, 13107]), ([4.0, 5.0, 6.0], [0, 65535, 0])] {
+			for v in position {
+				body.extend(v.to_le_bytes());
+			}
+			for c in color {
+				body.extend(c.to_le_bytes());
+			}
+		}
+		let (points, has_color) = parse_ply(binary("binary_little_endian", "ushort", &body).as_slice()).unwrap();
+		assert!(has_color);
+		assert_eq!(points[0].position, [1.0, 2.0, 3.0]);
+		assert_eq!(points[0].color, [1.0, 0.0, 0.2]);
+		assert_eq!(points[1].position, [4.0, 5.0, 6.0]);
+		assert_eq!(points[1].color, [0.0, 1.0, 0.0]);
+	}
+
+	#[test]
+	fn binary_big_endian_with_float_color() {
+		let mut body = vec![];
+		for (position, color) in [([1.0_f32, 2.0, 3.0], [0.25_f32, 0.5, 0.75]), ([-4.0, 5.0, 6.0], [1.0, 0.0, 0.0])] {
+			for v in position.into_iter().chain(color) {
+				body.extend(v.to_be_bytes());
+			}
+		}
+		let (points, has_color) = parse_ply(binary("binary_big_endian", "float", &body).as_slice()).unwrap();
+		assert!(has_color);
+		assert_eq!(points[0].position, [1.0, 2.0, 3.0]);
+		assert_eq!(points[0].color, [0.25, 0.5, 0.75]);
+		assert_eq!(points[1].position, [-4.0, 5.0, 6.0]);
+		assert_eq!(points[1].color, [1.0, 0.0, 0.0]);
+	}
+
+	#[test]
+	fn truncated_data() {
+		let text = format!("ply\nformat ascii 1.0\n{}end_header\n0 0 0\n", HEADER);
+		assert!(parse_ply(text.as_bytes()).is_err());
+		assert!(parse_ply("solid\n".as_bytes()).is_err());
+	}
+}
@@ -0,0 +1,85 @@
+// Vertex shader
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct Section {
+	planes: array<vec4<f32>, 3>,
+	count: u32,
+}
+
+struct PointUniform {
+	viewport: vec2<f32>,
+	size: f32,
+	focal: f32,
+	flags: u32,
+}
+
+const WORLD_UNITS: u32 = 1u;
+const ROUND: u32 = 2u;
+const VERTEX_COLOR: u32 = 4u;
+
+@group(0) @binding(0) 
+var<uniform> camera: Camera;
+
+@group(0) @binding(1)
+var<uniform> section: Section;
+
+@group(1) @binding(0)
+var<uniform> points: PointUniform;
+
+struct PointIn {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) corner: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    point: PointIn,
+) -> VertexOutput {
+    // triangle strip corners (-1, -1), (1, -1), (-1, 1), (1, 1)
+    let corner = vec2<f32>(f32(index & 1u), f32(index >> 1u)) * 2.0 - 1.0;
+    let center = camera.view_proj * vec4<f32>(point.position, 1.0);
+
+    var size = points.size;
+    if (points.flags & WORLD_UNITS) != 0u {
+        size = points.size * points.focal * points.viewport.y / (2.0 * center.w);
+    }
+    size = max(size, 1.0);
+
+    var out: VertexOutput;
+    
+    out.clip_position = center + vec4<f32>(corner * size / points.viewport * center.w, 0.0, 0.0);
+    out.color = point.color;
+    out.corner = corner;
+
+    // points the section planes cut away go outside the view volume
+    for (var i = 0u; i < section.count; i++) {
+        if dot(section.planes[i].xyz, point.position) > section.planes[i].w {
+            out.clip_position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+        }
+    }
+    
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	if (points.flags & ROUND) != 0u && dot(in.corner, in.corner) > 1.0 {
+		discard;
+	}
+
+	if (points.flags & VERTEX_COLOR) != 0u {
+		return vec4<f32>(in.color, 1.0);
+	}
+	return vec4<f32>(0.8, 0.8, 0.8, 1.0);
+}
@@ -0,0 +1,149 @@
+/// How meshes are shaded. The discriminant is what `fs_main` switches on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenderMode {
+	Solid = 0,
+	Wireframe = 1,
+	Pbr = 2,
+	/// Solid shading with the triangle edges drawn over it.
+	ShadedWireframe = 3,
+	/// World-space normals as RGB.
+	Normals = 4,
+	/// View depth across the scene's bounds, near is white.
+	Depth = 5,
+	UvChecker = 6,
+	/// A random color per `o`/`g` object.
+	ObjectId = 7,
+	/// A random color per material.
+	MaterialId = 8,
+	/// Triangles colored by their size on screen, from blue for large to red for subpixel.
+	TriangleDensity = 9,
+	/// Color looked up from a matcap sphere image by the surface's view-space normal.
+	Matcap = 10,
+	/// Cel shading in a few flat bands.
+	Toon = 11,
+	/// Gooch cool-to-warm shading, for technical illustrations.
+	Gooch = 12,
+	/// Visible polygon edges in black over a white fill.
+	HiddenLine = 13,
+	/// Surfaces see-through, most opaque where they turn away from the view.
+	XRay = 14,
+	/// Whatever `custom.wgsl`, or the file given with `--fragment`, makes of it.
+	Custom = 15,
+}
+
+impl RenderMode {
+	/// Order `V` cycles through.
+	const ALL: [RenderMode; 10] = [
+		RenderMode::Solid,
+		RenderMode::Pbr,
+		RenderMode::Matcap,
+		RenderMode::Toon,
+		RenderMode::Gooch,
+		RenderMode::ShadedWireframe,
+		RenderMode::Wireframe,
+		RenderMode::HiddenLine,
+		RenderMode::XRay,
+		RenderMode::Custom,
+	];
+
+	/// Diagnostic modes, in the order `M` cycles through.
+	const DEBUG: [RenderMode; 6] = [
+		RenderMode::Normals,
+		RenderMode::Depth,
+		RenderMode::UvChecker,
+		RenderMode::ObjectId,
+		RenderMode::MaterialId,
+		RenderMode::TriangleDensity,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			RenderMode::Solid => "solid",
+			RenderMode::Wireframe => "wireframe",
+			RenderMode::Pbr => "pbr",
+			RenderMode::ShadedWireframe => "shaded wireframe",
+			RenderMode::Normals => "normals",
+			RenderMode::Depth => "depth",
+			RenderMode::UvChecker => "uv checker",
+			RenderMode::ObjectId => "object id",
+			RenderMode::MaterialId => "material id",
+			RenderMode::TriangleDensity => "triangle density",
+			RenderMode::Matcap => "matcap",
+			RenderMode::Toon => "toon",
+			RenderMode::Gooch => "gooch",
+			RenderMode::HiddenLine => "hidden line",
+			RenderMode::XRay => "xray",
+			RenderMode::Custom => "custom",
+		}
+	}
+
+	/// Looks a mode up by its name, with hyphens allowed in place of spaces.
+	pub fn from_name(name: &str) -> Option<Self> {
+		let name = name.replace('-', " ");
+		Self::ALL.into_iter().chain(Self::DEBUG).find(|m| m.name().eq_ignore_ascii_case(&name))
+	}
+
+	/// Names of every mode, hyphenated, for usage messages.
+	pub fn names() -> Vec<String> {
+		Self::ALL.into_iter().chain(Self::DEBUG).map(|m| m.name().replace(' ', "-")).collect()
+	}
+
+	/// Whether meshes are drawn with `Mesh::draw_triangles`, for modes that need each fragment's
+	/// barycentrics or its triangle's area.
+	pub fn fetches_triangles(self) -> bool {
+		matches!(self, RenderMode::Wireframe | RenderMode::ShadedWireframe | RenderMode::TriangleDensity | RenderMode::HiddenLine)
+	}
+
+	/// Whether materials' dissolve is shown, blending transparent submeshes over the rest.
+	pub fn blends(self) -> bool {
+		matches!(self, RenderMode::Solid | RenderMode::Pbr | RenderMode::ShadedWireframe | RenderMode::Matcap | RenderMode::Toon | RenderMode::Gooch | RenderMode::Custom)
+	}
+
+	/// Whether cut solids are capped; the modes that show through surfaces leave cuts open.
+	pub fn draws_caps(self) -> bool {
+		!matches!(self, RenderMode::Wireframe | RenderMode::HiddenLine | RenderMode::XRay)
+	}
+
+	/// Whether surfaces are shaded by the lights, and so by their shadows.
+	pub fn is_lit(self) -> bool {
+		matches!(self, RenderMode::Solid | RenderMode::Pbr | RenderMode::ShadedWireframe | RenderMode::Toon | RenderMode::Gooch | RenderMode::Custom)
+	}
+
+	/// Whether shading is darkened by ambient occlusion while it's on.
+	pub fn shows_occlusion(self) -> bool {
+		self.is_lit() || self == RenderMode::Matcap
+	}
+
+	/// Whether polygon edges are drawn, at the wireframe width.
+	pub fn draws_edges(self) -> bool {
+		matches!(self, RenderMode::Wireframe | RenderMode::ShadedWireframe | RenderMode::HiddenLine)
+	}
+
+	/// Whether the mode's colors stand for data.
+	pub fn is_debug(self) -> bool {
+		Self::DEBUG.contains(&self)
+	}
+
+	/// Whether colors are shown as they are, without exposure or tonemapping, so debug colors
+	/// keep their meaning and hidden line drawings stay black on white.
+	pub fn is_raw(self) -> bool {
+		self.is_debug() || self == RenderMode::HiddenLine
+	}
+
+	/// The next shading mode; debug modes go back to the first.
+	pub fn next(self) -> Self {
+		match Self::ALL.iter().position(|m| *m == self) {
+			Some(i) => Self::ALL[(i + 1) % Self::ALL.len()],
+			None => Self::ALL[0],
+		}
+	}
+
+	/// The next debug mode, back to solid after the last.
+	pub fn next_debug(self) -> Self {
+		match Self::DEBUG.iter().position(|m| *m == self) {
+			Some(i) if i + 1 < Self::DEBUG.len() => Self::DEBUG[i + 1],
+			Some(_) => RenderMode::Solid,
+			None => Self::DEBUG[0],
+		}
+	}
+}
@@ -0,0 +1,227 @@
+use cgmath::{InnerSpace, Vector3};
+use winit::event::VirtualKeyCode;
+
+use crate::bounds::BoundingBox;
+use crate::camera::Camera;
+use crate::lines::{LineVertex, Lines};
+use crate::settings::Setting;
+
+pub const MAX_PLANES: usize = 3;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SectionUniform {
+	/// Normal in xyz, offset in w.
+	planes: [[f32; 4]; MAX_PLANES],
+	count: u32,
+	caps: u32,
+	_padding: [u32; 2],
+	cap_color: [f32; 4],
+}
+
+/// Cuts away everything on the side `normal` points to, where `dot(normal, p) > offset`.
+#[derive(Copy, Clone, Debug)]
+pub struct SectionPlane {
+	pub normal: Vector3<f32>,
+	pub offset: f32,
+}
+
+impl SectionPlane {
+	pub fn through(point: Vector3<f32>, normal: Vector3<f32>) -> Self {
+		let normal = normal.normalize();
+		Self { normal, offset: normal.dot(point) }
+	}
+
+	/// Keeps the other side instead.
+	pub fn flip(&mut self) {
+		self.normal = -self.normal;
+		self.offset = -self.offset;
+	}
+}
+
+/// Planes cutting the model open for section views. The buffer is bound next to the camera, and
+/// the passes drawing the model discard what the planes cut away. Where a cut opens up a closed
+/// mesh its back faces show through; with a cap color set they are drawn flat in it, so the cut
+/// looks solid without needing a stencil buffer next to the depth that SSAO and eye-dome lighting
+/// sample. Caps stay at the depth of those back faces rather than on the plane.
+pub struct Section {
+	pub planes: Vec<SectionPlane>,
+	pub selected: usize,
+	/// Index into `CAP_COLORS`, `None` to leave cuts open.
+	pub cap: Option<usize>,
+	pub buffer: wgpu::Buffer,
+	/// Outlines of the planes, rebuilt whenever they change.
+	plane_lines: Lines,
+}
+
+impl Section {
+	pub const CAP_COLORS: [(&'static str, [f32; 4]); 4] = [
+		("red", [0.6, 0.04, 0.03, 1.0]),
+		("gray", [0.25, 0.25, 0.25, 1.0]),
+		("yellow", [0.8, 0.55, 0.05, 1.0]),
+		("blue", [0.05, 0.2, 0.6, 1.0]),
+	];
+	const SELECTED_COLOR: [f32; 3] = [1.0, 0.8, 0.1];
+	const COLOR: [f32; 3] = [0.5, 0.5, 0.5];
+
+	pub fn new(device: &wgpu::Device) -> Self {
+		// written every frame by `update`
+		let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Section Buffer"),
+			size: std::mem::size_of::<SectionUniform>() as wgpu::BufferAddress,
+			usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		Self {
+			planes: vec![],
+			selected: 0,
+			cap: Some(0),
+			buffer,
+			plane_lines: Lines::new(device, &[], "Section Lines"),
+		}
+	}
+
+	fn selected_mut(&mut self) -> Option<&mut SectionPlane> {
+		self.planes.get_mut(self.selected)
+	}
+
+	/// Adds a plane if there's room for it, and selects it.
+	fn add(&mut self, plane: SectionPlane) -> bool {
+		if self.planes.len() >= MAX_PLANES {
+			return false;
+		}
+		self.planes.push(plane);
+		self.selected = self.planes.len() - 1;
+		true
+	}
+
+	fn remove_selected(&mut self) {
+		if self.selected < self.planes.len() {
+			self.planes.remove(self.selected);
+			self.selected = self.selected.min(self.planes.len().saturating_sub(1));
+		}
+	}
+
+	fn select_next(&mut self) {
+		if !self.planes.is_empty() {
+			self.selected = (self.selected + 1) % self.planes.len();
+		}
+	}
+
+	/// P adds a plane through the middle of `scene`, across the axis closest to the view direction,
+	/// cutting away the half towards the camera. End removes the selected plane, `.` selects the
+	/// next one, `,` flips it and Page Up and Page Down move it along its normal by 2% of the
+	/// scene's diagonal. Returns the status to show, `None` for keys that aren't the planes'.
+	pub fn input(&mut self, keycode: VirtualKeyCode, device: &wgpu::Device, camera: &Camera, scene: &BoundingBox) -> Option<String> {
+		let step = scene.diag() * 0.02;
+		match keycode {
+			VirtualKeyCode::P => {
+				let back = camera.transform.z.truncate();
+				let axis = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()].into_iter()
+					.max_by(|a, b| a.dot(back).abs().total_cmp(&b.dot(back).abs()))
+					.unwrap();
+				if !self.add(SectionPlane::through(scene.center(), axis * axis.dot(back).signum())) {
+					return Some(format!("at most {} section planes", MAX_PLANES));
+				}
+			}
+			VirtualKeyCode::End => self.remove_selected(),
+			VirtualKeyCode::Period => self.select_next(),
+			VirtualKeyCode::Comma => if let Some(plane) = self.selected_mut() {
+				plane.flip();
+			},
+			VirtualKeyCode::PageUp => if let Some(plane) = self.selected_mut() {
+				plane.offset += step;
+			},
+			VirtualKeyCode::PageDown => if let Some(plane) = self.selected_mut() {
+				plane.offset -= step;
+			},
+			_ => return None,
+		}
+		self.plane_lines = Lines::new(device, &self.lines(scene), "Section Lines");
+		Some(self.status())
+	}
+
+	fn status(&self) -> String {
+		match self.planes.get(self.selected) {
+			Some(plane) => format!(
+				"section plane {}/{}: normal ({}, {}, {}), offset {}",
+				self.selected + 1, self.planes.len(), plane.normal.x, plane.normal.y, plane.normal.z, plane.offset,
+			),
+			None => "no section planes".into(),
+		}
+	}
+
+	/// The cap color, while there's a cut to cap.
+	pub fn settings(&self) -> &'static [Setting] {
+		if self.planes.is_empty() { &[] } else { &[Setting::SectionCap] }
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		if setting != Setting::SectionCap {
+			return false;
+		}
+		self.step_cap(steps as i32);
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		(setting == Setting::SectionCap).then(|| self.cap_name().into())
+	}
+
+	/// Steps through the cap colors and back to open cuts.
+	fn step_cap(&mut self, steps: i32) {
+		let count = Self::CAP_COLORS.len() as i32 + 1;
+		let next = (self.cap.map_or(0, |c| c as i32 + 1) + steps).rem_euclid(count);
+		self.cap = (next > 0).then(|| next as usize - 1);
+	}
+
+	fn cap_name(&self) -> &'static str {
+		self.cap.map_or("off", |c| Self::CAP_COLORS[c].0)
+	}
+
+	/// Writes the planes, with caps only when the cap setting and the render mode (`caps`) both
+	/// call for them.
+	pub fn update(&self, queue: &wgpu::Queue, caps: bool) {
+		queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform(caps)]));
+	}
+
+	fn uniform(&self, caps: bool) -> SectionUniform {
+		let mut planes = [[0.0; 4]; MAX_PLANES];
+		for (raw, plane) in planes.iter_mut().zip(&self.planes) {
+			*raw = [plane.normal.x, plane.normal.y, plane.normal.z, plane.offset];
+		}
+		SectionUniform {
+			planes,
+			count: self.planes.len() as u32,
+			caps: (caps && self.cap.is_some()) as u32,
+			_padding: [0; 2],
+			cap_color: self.cap.map_or([0.0; 4], |c| Self::CAP_COLORS[c].1),
+		}
+	}
+
+	pub fn draw_planes<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+		self.plane_lines.draw(render_pass);
+	}
+
+	/// A square outline of each plane around the middle of `scene`, the selected one highlighted.
+	fn lines(&self, scene: &BoundingBox) -> Vec<LineVertex> {
+		let center = scene.center();
+		let half = scene.diag() * 0.5;
+		let mut vertices = vec![];
+		for (i, plane) in self.planes.iter().enumerate() {
+			let color = if i == self.selected { Self::SELECTED_COLOR } else { Self::COLOR };
+			// any direction in the plane, then the one across it
+			let helper = if plane.normal.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+			let u = plane.normal.cross(helper).normalize() * half;
+			let v = plane.normal.cross(u);
+			let middle = center - plane.normal * (plane.normal.dot(center) - plane.offset);
+			let corners = [middle - u - v, middle + u - v, middle + u + v, middle - u + v];
+			for j in 0..4 {
+				vertices.push(LineVertex { position: corners[j].into(), color });
+				vertices.push(LineVertex { position: corners[(j + 1) % 4].into(), color });
+			}
+		}
+		vertices
+	}
+}
@@ -0,0 +1,91 @@
+/// Values tuned at runtime: Tab selects one of those that apply to what's shown, `-` and `=`
+/// change it. Each belongs to a feature, which lists it while it has an effect and adjusts it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Setting {
+	PointSize,
+	EdlStrength,
+	EdlRadius,
+	LightIntensity,
+	LightTemperature,
+	SpotAngle,
+	Ambient,
+	ShadowBias,
+	ShadowSoftness,
+	Msaa,
+	WireframeWidth,
+	SsaoRadius,
+	SsaoStrength,
+	EnvironmentRotation,
+	EnvironmentExposure,
+	OutlineWidth,
+	OutlineColor,
+	Exposure,
+	Tonemapper,
+	Bloom,
+	SectionCap,
+	Explode,
+	CreaseAngle,
+	FeatureLineWidth,
+}
+
+impl Setting {
+	pub fn name(self) -> &'static str {
+		match self {
+			Setting::PointSize => "point size",
+			Setting::EdlStrength => "eye-dome lighting strength",
+			Setting::EdlRadius => "eye-dome lighting radius",
+			Setting::LightIntensity => "light intensity",
+			Setting::LightTemperature => "light color temperature",
+			Setting::SpotAngle => "spot angle",
+			Setting::Ambient => "ambient light",
+			Setting::ShadowBias => "shadow bias",
+			Setting::ShadowSoftness => "shadow softness",
+			Setting::Msaa => "anti-aliasing samples",
+			Setting::WireframeWidth => "wireframe width",
+			Setting::SsaoRadius => "ambient occlusion radius",
+			Setting::SsaoStrength => "ambient occlusion strength",
+			Setting::EnvironmentRotation => "environment rotation",
+			Setting::EnvironmentExposure => "environment exposure",
+			Setting::OutlineWidth => "outline width",
+			Setting::OutlineColor => "outline color",
+			Setting::Exposure => "exposure",
+			Setting::Tonemapper => "tonemapper",
+			Setting::Bloom => "bloom",
+			Setting::SectionCap => "section cap",
+			Setting::Explode => "exploded view",
+			Setting::CreaseAngle => "crease angle",
+			Setting::FeatureLineWidth => "feature line width",
+		}
+	}
+
+	/// The setting after this one among those that apply, or the first of them when this one
+	/// doesn't apply any more.
+	pub fn next(self, active: &[Setting]) -> Option<Self> {
+		let next = active.iter().position(|s| *s == self).map_or(0, |i| i + 1);
+		active.get(next % active.len().max(1)).copied()
+	}
+}
+
+/// Title bar text for a feature turned on or off.
+pub fn toggled(name: &str, enabled: bool) -> String {
+	format!("{}: {}", name, if enabled { "on" } else { "off" })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn next_wraps_within_the_active_settings() {
+		let active = [Setting::Exposure, Setting::Tonemapper, Setting::Bloom];
+		assert_eq!(Setting::Exposure.next(&active), Some(Setting::Tonemapper));
+		assert_eq!(Setting::Bloom.next(&active), Some(Setting::Exposure));
+	}
+
+	#[test]
+	fn next_starts_over_when_the_setting_no_longer_applies() {
+		let active = [Setting::OutlineWidth, Setting::OutlineColor];
+		assert_eq!(Setting::PointSize.next(&active), Some(Setting::OutlineWidth));
+		assert_eq!(Setting::PointSize.next(&[]), None);
+	}
+}
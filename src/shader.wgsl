@@ -1,54 +1,653 @@
 // Vertex shader
-struct MatrixUniform {
-    data: mat4x4<f32>,
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
 };
 
+// planes cutting the model open, see section.rs
+struct Section {
+	// normal in xyz, offset in w
+	planes: array<vec4<f32>, 3>,
+	count: u32,
+	// non-zero to fill cuts with cap_color
+	caps: u32,
+	cap_color: vec4<f32>,
+}
+
 struct RenderState {
 	render_mode: i32,
+	// in pixels
+	wireframe_width: f32,
+	viewport: vec2<f32>,
+	// nearest and farthest view depth of the scene
+	depth_range: vec2<f32>,
+	// non-zero to light back faces from their side and tint them
+	two_sided: u32,
+	// non-zero to multiply lighting by ao_texture
+	ssao: u32,
+}
+
+struct Submesh {
+	object: u32,
+	material: u32,
+	// where the exploded view has moved it
+	offset: vec3<f32>,
+}
+
+// position.w is the kind, direction.w the range, color.a the intensity,
+// cone holds the cosines of the spot's inner and outer angles
+struct Light {
+	position: vec4<f32>,
+	direction: vec4<f32>,
+	color: vec4<f32>,
+	cone: vec4<f32>,
+}
+
+struct Lights {
+	ambient: vec4<f32>,
+	count: u32,
+	lights: array<Light, 8>,
+}
+
+struct Shadow {
+	view_proj: mat4x4<f32>,
+	light: i32,
+	bias: f32,
+	texel: f32,
+	softness: f32,
 }
 
+// see skybox.wgsl
+struct Environment {
+	inv_view_proj: mat4x4<f32>,
+	rotation: vec2<f32>,
+	intensity: f32,
+	specular_mips: f32,
+	enabled: u32,
+}
+
+struct Material {
+	diffuse: vec4<f32>,
+	specular: vec4<f32>,
+	roughness_metallic_clearcoat: vec4<f32>,
+	sheen: vec4<f32>,
+	// x is the map_d alpha-test threshold, 0 without map_d
+	cutout: vec4<f32>,
+	emissive: vec4<f32>,
+}
+
+const DIRECTIONAL: f32 = 0.0;
+const SPOT: f32 = 2.0;
+const PI: f32 = 3.14159265;
+
 @group(0) @binding(0) 
-var<uniform> camera: MatrixUniform;
+var<uniform> camera: Camera;
+
+@group(0) @binding(1)
+var<uniform> section: Section;
 
 @group(1) @binding(0)
 var<uniform> render_state: RenderState;
 
+@group(1) @binding(1)
+var<uniform> lights: Lights;
+
+@group(1) @binding(2)
+var<uniform> shadow: Shadow;
+
+@group(1) @binding(3)
+var shadow_map: texture_depth_2d;
+
+@group(1) @binding(4)
+var shadow_sampler: sampler_comparison;
+
+// screen-space ambient occlusion, 1 where unoccluded
+@group(1) @binding(5)
+var ao_texture: texture_2d<f32>;
+
+@group(1) @binding(6)
+var<uniform> environment: Environment;
+
+// cosine-weighted radiance around each direction
+@group(1) @binding(7)
+var irradiance_map: texture_cube<f32>;
+
+// GGX-prefiltered radiance, roughness rising from 0 at the top mip to 1 at the last
+@group(1) @binding(8)
+var specular_map: texture_cube<f32>;
+
+@group(1) @binding(9)
+var environment_sampler: sampler;
+
+// a lit sphere, looked up by view-space normal in the matcap mode
+@group(1) @binding(10)
+var matcap_map: texture_2d<f32>;
+
+@group(2) @binding(0)
+var<uniform> material: Material;
+
+@group(2) @binding(1)
+var roughness_map: texture_2d<f32>;
+
+@group(2) @binding(2)
+var metallic_map: texture_2d<f32>;
+
+@group(2) @binding(3)
+var material_sampler: sampler;
+
+@group(2) @binding(4)
+var dissolve_map: texture_2d<f32>;
+
+@group(2) @binding(5)
+var emissive_map: texture_2d<f32>;
+
+// the mesh's vertex buffer, 8 floats per vertex: position, normal, tex_coords
+@group(3) @binding(0)
+var<storage, read> vertices: array<f32>;
+
+@group(3) @binding(1)
+var<storage, read> indices: array<u32>;
+
+// per triangle, bit k is set when the edge opposite corner k is an edge of the source polygon
+@group(3) @binding(2)
+var<storage, read> edges: array<u32>;
+
+@group(3) @binding(3)
+var<uniform> submesh: Submesh;
+
 struct VertexIn {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+}
+
+struct InstanceIn {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) normal_0: vec3<f32>,
+    @location(10) normal_1: vec3<f32>,
+    @location(11) normal_2: vec3<f32>,
+    @location(12) color: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) normal: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) tex_coords: vec2<f32>,
+    // only set by vs_triangles
+    @location(4) barycentric: vec3<f32>,
+    @location(5) @interpolate(flat) edges: u32,
+    // the triangle's area on screen in pixels
+    @location(6) @interpolate(flat) area: f32,
 };
 
 @vertex
 fn vs_main(
     model: VertexIn,
+    instance: InstanceIn,
 ) -> VertexOutput {
+    return transform(model, instance);
+}
+
+// draws non-indexed: each vertex_index is a position in the index buffer, so i % 3 is the corner
+@vertex
+fn vs_triangles(
+    @builtin(vertex_index) i: u32,
+    instance: InstanceIn,
+) -> VertexOutput {
+    let corner = i % 3u;
+    var out = transform(fetch_vertex(i), instance);
+    out.barycentric = vec3<f32>(f32(corner == 0u), f32(corner == 1u), f32(corner == 2u));
+    out.edges = edges[i / 3u];
+
+    let first = i - corner;
+    let a = screen_position(fetch_vertex(first).position, instance);
+    let b = screen_position(fetch_vertex(first + 1u).position, instance);
+    let c = screen_position(fetch_vertex(first + 2u).position, instance);
+    let ab = b - a;
+    let ac = c - a;
+    out.area = abs(ab.x * ac.y - ab.y * ac.x) * 0.5;
+    return out;
+}
+
+fn fetch_vertex(i: u32) -> VertexIn {
+    let v = indices[i] * 8u;
+    var model: VertexIn;
+    model.position = vec3<f32>(vertices[v], vertices[v + 1u], vertices[v + 2u]);
+    model.normal = vec3<f32>(vertices[v + 3u], vertices[v + 4u], vertices[v + 5u]);
+    model.tex_coords = vec2<f32>(vertices[v + 6u], vertices[v + 7u]);
+    return model;
+}
+
+// in pixels
+fn screen_position(position: vec3<f32>, instance: InstanceIn) -> vec2<f32> {
+    let model_matrix = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let clip = camera.view_proj * model_matrix * vec4<f32>(position + submesh.offset, 1.0);
+    return clip.xy / max(abs(clip.w), 1e-6) * render_state.viewport * 0.5;
+}
+
+fn transform(model: VertexIn, instance: InstanceIn) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let normal_matrix = mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+
     var out: VertexOutput;
     
-    out.clip_position = camera.data * vec4<f32>(model.position.xyz, 1.0);
-    out.normal = model.normal;
+    let world_position = model_matrix * vec4<f32>(model.position + submesh.offset, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.tex_coords = model.tex_coords;
+    out.normal = normalize(normal_matrix * model.normal);
+    out.color = instance.color;
     
     return out;
 }
 
+const BACK_FACE_COLOR: vec3<f32> = vec3<f32>(0.9, 0.1, 0.3);
+
+fn cut_away(world_position: vec3<f32>) -> bool {
+	for (var i = 0u; i < section.count; i++) {
+		let plane = section.planes[i];
+		if dot(plane.xyz, world_position) > plane.w {
+			return true;
+		}
+	}
+	return false;
+}
+
 @fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+fn fs_main(vertex: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+	if cut_away(vertex.world_position) {
+		discard;
+	}
+	// a back face seen through a cut is the inside of a closed mesh
+	if !front_facing && section.count > 0u && section.caps != 0u {
+		return section.cap_color;
+	}
+
+	// cut-out foliage and the like
+	if textureSample(dissolve_map, material_sampler, vertex.tex_coords).r < material.cutout.x {
+		discard;
+	}
+
+	if render_state.two_sided == 0u {
+		return shade(vertex);
+	}
+
+	var in = vertex;
+	in.normal = select(-in.normal, in.normal, front_facing);
+	let color = shade(in);
+	return select(vec4<f32>(mix(color.rgb, BACK_FACE_COLOR, 0.5), color.a), color, front_facing);
+}
+
+// the white fill hidden line mode draws its edges over
+@fragment
+fn fs_fill(in: VertexOutput) -> @location(0) vec4<f32> {
+	if cut_away(in.world_position) {
+		discard;
+	}
+	if textureSample(dissolve_map, material_sampler, in.tex_coords).r < material.cutout.x {
+		discard;
+	}
+	return vec4<f32>(1.0);
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
 	switch render_state.render_mode {
 		case 0 { // solid
-			let light = normalize(vec3<f32>(0.3, -1.0, 0.0));
-			let dot = dot(-light, in.normal);
-			let color = vec3<f32>(max(dot, 0.01));
-			return vec4<f32>(color, 1.0);
+			return vec4<f32>(blinn_phong(in) * ambient_occlusion(in.clip_position.xy) + emission(in), surface_alpha(in));
 		}
 		case 1 { // wireframe
-			return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+			let edge = edge_coverage(in.barycentric, in.edges);
+			if edge <= 0.0 {
+				discard;
+			}
+			return vec4<f32>(0.0, 1.0, 0.0, edge);
+		}
+		case 2 { // pbr
+			return vec4<f32>(cook_torrance(in) * ambient_occlusion(in.clip_position.xy) + emission(in), surface_alpha(in));
+		}
+		case 3 { // shaded wireframe
+			let edge = edge_coverage(in.barycentric, in.edges);
+			return vec4<f32>(mix(blinn_phong(in) * ambient_occlusion(in.clip_position.xy) + emission(in), vec3<f32>(0.01), edge), max(surface_alpha(in), edge));
+		}
+		case 4 { // normals
+			return vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+		}
+		case 5 { // depth
+			let depth = -(camera.view * vec4<f32>(in.world_position, 1.0)).z;
+			let range = render_state.depth_range;
+			let t = saturate((depth - range.x) / max(range.y - range.x, 1e-6));
+			return vec4<f32>(vec3<f32>(1.0 - t), 1.0);
+		}
+		case 6 { // uv checker
+			let cell = floor(in.tex_coords * 8.0);
+			let checker = select(0.35, 1.0, (i32(cell.x) + i32(cell.y)) % 2 == 0);
+			let uv = fract(in.tex_coords);
+			return vec4<f32>(vec3<f32>(uv.x, uv.y, 1.0 - uv.x) * checker, 1.0);
+		}
+		case 7 { // object id
+			return vec4<f32>(id_color(submesh.object), 1.0);
+		}
+		case 8 { // material id
+			return vec4<f32>(id_color(submesh.material + 0x9e37u), 1.0);
+		}
+		case 9 { // triangle density
+			// 1 pixel or less is red, 4096 pixels or more blue
+			return vec4<f32>(heatmap(1.0 - saturate(log2(max(in.area, 1.0)) / 12.0)), 1.0);
+		}
+		case 10 { // matcap
+			let matcap = textureSample(matcap_map, environment_sampler, matcap_uv(in)).rgb;
+			return vec4<f32>(matcap * in.color.rgb * ambient_occlusion(in.clip_position.xy) + emission(in), surface_alpha(in));
+		}
+		case 11 { // toon
+			return vec4<f32>(toon(in) * ambient_occlusion(in.clip_position.xy) + emission(in), surface_alpha(in));
+		}
+		case 12 { // gooch
+			return vec4<f32>(gooch(in) * ambient_occlusion(in.clip_position.xy) + emission(in), surface_alpha(in));
+		}
+		case 13 { // hidden line, over fs_fill
+			let edge = edge_coverage(in.barycentric, in.edges);
+			if edge <= 0.0 {
+				discard;
+			}
+			return vec4<f32>(0.0, 0.0, 0.0, edge);
+		}
+		case 14 { // x-ray
+			// faces seen edge-on are the most opaque, so outlines and nested shells stand out
+			let n = normalize(in.normal);
+			let v = normalize(camera.position.xyz - in.world_position);
+			let rim = 1.0 - abs(dot(n, v));
+			let base = material.diffuse.rgb * in.color.rgb;
+			return vec4<f32>(mix(base * 0.3, vec3<f32>(0.6, 0.8, 1.0), rim), mix(0.08, 0.7, rim * rim));
+		}
+		case 15 { // custom, see custom.wgsl
+			return custom(in);
 		}
 		default { // invalid
 			return vec4<f32>(1.0, 0.0, 1.0, 1.0);
 		}
 	}
-}
\ No newline at end of file
+}
+
+// the material's dissolve times the instance's alpha; only the transparent pipelines blend with it
+fn surface_alpha(in: VertexOutput) -> f32 {
+	return material.diffuse.a * in.color.a;
+}
+
+// light given off by the surface itself, unaffected by lights and occlusion
+fn emission(in: VertexOutput) -> vec3<f32> {
+	return material.emissive.rgb * textureSample(emissive_map, material_sampler, in.tex_coords).rgb;
+}
+
+// a bright, well separated color for a small integer
+fn id_color(id: u32) -> vec3<f32> {
+	var h = id * 747796405u + 2891336453u;
+	h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+	h = (h >> 22u) ^ h;
+	let hue = f32(h & 0xffffu) / 65535.0;
+	return saturate(abs(fract(hue + vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0)) * 6.0 - 3.0) - 1.0) * 0.8 + 0.2;
+}
+
+// blue through green and yellow to red as t goes from 0 to 1
+fn heatmap(t: f32) -> vec3<f32> {
+	return saturate(vec3<f32>(
+		1.5 - abs(t * 4.0 - 3.0),
+		1.5 - abs(t * 4.0 - 2.0),
+		1.5 - abs(t * 4.0 - 1.0),
+	));
+}
+
+// how much of the pixel a polygon edge of render_state.wireframe_width covers, with a
+// one pixel falloff; fwidth turns the distance to each edge in barycentrics into pixels
+fn edge_coverage(barycentric: vec3<f32>, edges: u32) -> f32 {
+	let polygon_edge = vec3<u32>(edges) & vec3<u32>(1u, 2u, 4u);
+	let pixels = select(vec3<f32>(1e6), barycentric / max(fwidth(barycentric), vec3<f32>(1e-6)), polygon_edge != vec3<u32>(0u));
+	let distance = min(pixels.x, min(pixels.y, pixels.z));
+	let half_width = render_state.wireframe_width * 0.5;
+	return 1.0 - smoothstep(half_width - 0.5, half_width + 0.5, distance);
+}
+
+fn ambient_occlusion(pixel: vec2<f32>) -> f32 {
+	if render_state.ssao == 0u {
+		return 1.0;
+	}
+	return textureLoad(ao_texture, vec2<i32>(pixel), 0).r;
+}
+
+// fraction of the shadow-casting light reaching `position`, 3x3 PCF over the comparison sampler
+fn shadow_factor(position: vec3<f32>, n: vec3<f32>, l: vec3<f32>) -> f32 {
+	let p = shadow.view_proj * vec4<f32>(position, 1.0);
+	let uv = p.xy * vec2<f32>(0.5, -0.5) + 0.5;
+	if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || p.z > 1.0 {
+		return 1.0;
+	}
+
+	// surfaces facing away from the light need more bias to avoid acne
+	let cos_theta = clamp(dot(n, l), 0.05, 1.0);
+	let bias = shadow.bias * clamp(sqrt(1.0 - cos_theta * cos_theta) / cos_theta, 1.0, 10.0);
+	var lit = 0.0;
+	for (var x = -1; x <= 1; x++) {
+		for (var y = -1; y <= 1; y++) {
+			let offset = vec2<f32>(f32(x), f32(y)) * shadow.texel * shadow.softness;
+			lit += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, p.z - bias);
+		}
+	}
+	return lit / 9.0;
+}
+
+// direction towards light `i` in xyz and its attenuation at `position` in w, shadows included
+fn incident_light(i: u32, position: vec3<f32>, n: vec3<f32>) -> vec4<f32> {
+	let light = lights.lights[i];
+	if light.position.w == DIRECTIONAL {
+		let l = -normalize(light.direction.xyz);
+		if i32(i) == shadow.light {
+			return vec4<f32>(l, shadow_factor(position, n, l));
+		}
+		return vec4<f32>(l, 1.0);
+	}
+
+	let to_light = light.position.xyz - position;
+	let d = length(to_light);
+	let l = to_light / max(d, 1e-5);
+	// smooth window so the light reaches exactly zero at its range
+	let range = light.direction.w;
+	let falloff = saturate(1.0 - pow(d / range, 4.0));
+	var attenuation = falloff * falloff / max(d * d, 1e-4) * range * range * 0.25;
+	if light.position.w == SPOT {
+		attenuation *= smoothstep(light.cone.y, light.cone.x, dot(-l, normalize(light.direction.xyz)));
+	}
+	return vec4<f32>(l, attenuation);
+}
+
+fn blinn_phong(in: VertexOutput) -> vec3<f32> {
+	let n = normalize(in.normal);
+	let v = normalize(camera.position.xyz - in.world_position);
+	let diffuse = material.diffuse.rgb * in.color.rgb;
+	let shininess = max(material.specular.w, 1.0);
+
+	var color = ambient_light(n) * diffuse;
+	for (var i = 0u; i < lights.count; i++) {
+		let light = lights.lights[i];
+		let incident = incident_light(i, in.world_position, n);
+		let l = incident.xyz;
+
+		let n_dot_l = max(dot(n, l), 0.0);
+		let h = normalize(l + v);
+		let specular = material.specular.rgb * pow(max(dot(n, h), 0.0), shininess) * step(0.0, dot(n, l));
+		color += (diffuse * n_dot_l + specular) * light.color.rgb * light.color.a * incident.w;
+	}
+	return color;
+}
+
+// direction `d` in the environment's own frame, turned about the vertical axis
+fn environment_direction(d: vec3<f32>) -> vec3<f32> {
+	let r = environment.rotation;
+	return vec3<f32>(r.x * d.x - r.y * d.z, d.y, r.y * d.x + r.x * d.z);
+}
+
+fn ambient_light(n: vec3<f32>) -> vec3<f32> {
+	if environment.enabled == 0u {
+		return lights.ambient.rgb;
+	}
+	return textureSampleLevel(irradiance_map, environment_sampler, environment_direction(n), 0.0).rgb * environment.intensity;
+}
+
+// split-sum image-based lighting, with Karis' analytic fit standing in for the BRDF lookup table
+fn environment_light(n: vec3<f32>, v: vec3<f32>, base: vec3<f32>, f0: vec3<f32>, roughness: f32, metallic: f32) -> vec3<f32> {
+	let n_dot_v = max(dot(n, v), 1e-4);
+	let c0 = vec4<f32>(-1.0, -0.0275, -0.572, 0.022);
+	let c1 = vec4<f32>(1.0, 0.0425, 1.04, -0.04);
+	let r = roughness * c0 + c1;
+	let a004 = min(r.x * r.x, exp2(-9.28 * n_dot_v)) * r.x + r.y;
+	let ab = vec2<f32>(-1.04, 1.04) * a004 + r.zw;
+	let specular_color = f0 * ab.x + ab.y;
+
+	let lod = roughness * (environment.specular_mips - 1.0);
+	let reflected = textureSampleLevel(specular_map, environment_sampler, environment_direction(reflect(-v, n)), lod).rgb;
+	let irradiance = textureSampleLevel(irradiance_map, environment_sampler, environment_direction(n), 0.0).rgb;
+	let diffuse = (1.0 - specular_color) * (1.0 - metallic) * base * irradiance;
+	return (diffuse + reflected * specular_color) * environment.intensity;
+}
+
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+	let a2 = alpha * alpha;
+	let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+// Smith-GGX height-correlated visibility, G / (4 n.l n.v)
+fn visibility_smith(n_dot_v: f32, n_dot_l: f32, alpha: f32) -> f32 {
+	let a2 = alpha * alpha;
+	let gv = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - a2) + a2);
+	let gl = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - a2) + a2);
+	return 0.5 / max(gv + gl, 1e-5);
+}
+
+fn fresnel_schlick(f0: vec3<f32>, v_dot_h: f32) -> vec3<f32> {
+	return f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+// Charlie sheen distribution with Neubelt's visibility term
+fn sheen_charlie(n_dot_h: f32, n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+	let inv_r = 1.0 / max(roughness, 0.07);
+	let sin2 = max(1.0 - n_dot_h * n_dot_h, 0.0078125);
+	let d = (2.0 + inv_r) * pow(sin2, inv_r * 0.5) / (2.0 * PI);
+	return d / (4.0 * (n_dot_l + n_dot_v - n_dot_l * n_dot_v));
+}
+
+// Cook-Torrance GGX specular over energy-conserving Lambert diffuse, plus optional sheen and clearcoat.
+// Irradiance is scaled by pi so a light's intensity means the same as in the Blinn-Phong mode.
+fn cook_torrance(in: VertexOutput) -> vec3<f32> {
+	let n = normalize(in.normal);
+	let v = normalize(camera.position.xyz - in.world_position);
+	let n_dot_v = max(dot(n, v), 1e-4);
+
+	let params = material.roughness_metallic_clearcoat;
+	let roughness = clamp(params.x * textureSample(roughness_map, material_sampler, in.tex_coords).r, 0.03, 1.0);
+	let metallic = saturate(params.y * textureSample(metallic_map, material_sampler, in.tex_coords).r);
+	let clearcoat = params.z;
+	let clearcoat_roughness = clamp(params.w, 0.03, 1.0);
+
+	let base = material.diffuse.rgb * in.color.rgb;
+	let f0 = mix(vec3<f32>(0.04), base, metallic);
+	let alpha = roughness * roughness;
+
+	var color = lights.ambient.rgb * base;
+	if environment.enabled != 0u {
+		color = environment_light(n, v, base, f0, roughness, metallic);
+	}
+	for (var i = 0u; i < lights.count; i++) {
+		let light = lights.lights[i];
+		let incident = incident_light(i, in.world_position, n);
+		let l = incident.xyz;
+		let n_dot_l = dot(n, l);
+		if n_dot_l <= 0.0 {
+			continue;
+		}
+
+		let h = normalize(l + v);
+		let n_dot_h = max(dot(n, h), 0.0);
+		let v_dot_h = max(dot(v, h), 0.0);
+
+		let f = fresnel_schlick(f0, v_dot_h);
+		let specular = f * distribution_ggx(n_dot_h, alpha) * visibility_smith(n_dot_v, n_dot_l, alpha);
+		let diffuse = (1.0 - f) * (1.0 - metallic) * base / PI;
+		let sheen = material.sheen.rgb * sheen_charlie(n_dot_h, n_dot_v, n_dot_l, roughness);
+
+		let fc = fresnel_schlick(vec3<f32>(0.04), v_dot_h).x * clearcoat;
+		let coat_alpha = clearcoat_roughness * clearcoat_roughness;
+		let coat = fc * distribution_ggx(n_dot_h, coat_alpha) * visibility_smith(n_dot_v, n_dot_l, coat_alpha);
+
+		let radiance = light.color.rgb * light.color.a * incident.w * PI;
+		color += ((diffuse + specular + sheen) * (1.0 - fc) + coat) * radiance * n_dot_l;
+	}
+	return color;
+}
+
+// where the view-space normal points on the matcap sphere. The sphere is turned to face the
+// fragment rather than the view axis, so the lookup doesn't skew towards the edges of the view.
+fn matcap_uv(in: VertexOutput) -> vec2<f32> {
+	let n = normalize((camera.view * vec4<f32>(normalize(in.normal), 0.0)).xyz);
+	let v = normalize(-(camera.view * vec4<f32>(in.world_position, 1.0)).xyz);
+	let x = normalize(vec3<f32>(v.z, 0.0, -v.x));
+	let y = cross(v, x);
+	return vec2<f32>(dot(x, n), -dot(y, n)) * 0.495 + 0.5;
+}
+
+const TOON_BANDS: f32 = 3.0;
+
+// `x` rounded to one of TOON_BANDS + 1 levels, stepping over about a pixel so the bands don't alias
+fn toon_band(x: f32) -> f32 {
+	let scaled = x * TOON_BANDS + 0.5;
+	let blend = smoothstep(1.0 - fwidth(scaled), 1.0, fract(scaled));
+	return min((floor(scaled) + blend) / TOON_BANDS, 1.0);
+}
+
+// cel shading: flat bands of diffuse light, a hard-edged highlight and a rim
+fn toon(in: VertexOutput) -> vec3<f32> {
+	let n = normalize(in.normal);
+	let v = normalize(camera.position.xyz - in.world_position);
+	let base = material.diffuse.rgb * in.color.rgb;
+	let shininess = max(material.specular.w, 8.0);
+
+	var color = ambient_light(n) * base;
+	for (var i = 0u; i < lights.count; i++) {
+		let light = lights.lights[i];
+		let incident = incident_light(i, in.world_position, n);
+		let l = incident.xyz;
+
+		let diffuse = toon_band(saturate(dot(n, l) * incident.w));
+		let h = normalize(l + v);
+		let highlight = pow(max(dot(n, h), 0.0), shininess) * incident.w * step(0.0, dot(n, l));
+		let specular = material.specular.rgb * smoothstep(0.5 - fwidth(highlight), 0.5, highlight);
+		color += (base * diffuse + specular) * light.color.rgb * light.color.a;
+	}
+	let rim = 1.0 - max(dot(n, v), 0.0);
+	return color + base * 0.25 * smoothstep(0.7 - fwidth(rim), 0.7, rim);
+}
+
+// Gooch et al.'s technical illustration shading: a cool to warm ramp around the key light instead
+// of light to dark, leaving black for outlines, and a white highlight
+fn gooch(in: VertexOutput) -> vec3<f32> {
+	let n = normalize(in.normal);
+	let v = normalize(camera.position.xyz - in.world_position);
+	let base = material.diffuse.rgb * in.color.rgb;
+
+	// the first light, or from over the camera without any; the view matrix's rows are the camera's axes
+	let up = vec3<f32>(camera.view[0].y, camera.view[1].y, camera.view[2].y);
+	var l = normalize(v + up);
+	if lights.count > 0u {
+		l = incident_light(0u, in.world_position, n).xyz;
+	}
+
+	let cool = vec3<f32>(0.0, 0.0, 0.55) + 0.25 * base;
+	let warm = vec3<f32>(0.3, 0.3, 0.0) + 0.25 * base;
+	let t = (1.0 + dot(n, l)) * 0.5;
+	let highlight = pow(max(dot(reflect(-l, n), v), 0.0), 32.0);
+	return mix(cool, warm, t) + vec3<f32>(highlight * 0.5);
+}
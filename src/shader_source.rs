@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+const SHADER: &str = include_str!("shader.wgsl");
+const CUSTOM: &str = include_str!("custom.wgsl");
+/// How often the files are looked at for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Where the mesh shader comes from: the custom render mode's shading from `custom.wgsl`
+/// followed by `shader.wgsl`, or files standing in for either. Files are read again when they
+/// change, so shaders can be worked on without restarting. Only the mesh pipelines are built from
+/// it; lines, the grid and the post-processing passes keep their own shaders.
+pub struct ShaderSource {
+	shader: Option<PathBuf>,
+	custom: Option<PathBuf>,
+	/// Latest modification time of the files when they were last read.
+	modified: Option<SystemTime>,
+	/// When the files were last looked at.
+	polled: Option<Instant>,
+}
+
+impl ShaderSource {
+	pub fn new(shader: Option<&str>, custom: Option<&str>) -> Self {
+		Self {
+			shader: shader.map(PathBuf::from),
+			custom: custom.map(PathBuf::from),
+			modified: None,
+			polled: None,
+		}
+	}
+
+	/// The source built into the binary, which is known to compile.
+	pub fn built_in() -> String {
+		format!("{}\n{}", CUSTOM, SHADER)
+	}
+
+	/// Whether any of it is read from disk.
+	pub fn is_external(&self) -> bool {
+		self.shader.is_some() || self.custom.is_some()
+	}
+
+	/// Whether a file has been modified since it was last read, looking at most every
+	/// `POLL_INTERVAL` rather than every frame. Files that can't be looked at, as while an editor
+	/// replaces them, count as unchanged.
+	pub fn changed(&mut self) -> bool {
+		if !self.is_external() || self.polled.is_some_and(|polled| polled.elapsed() < POLL_INTERVAL) {
+			return false;
+		}
+		self.polled = Some(Instant::now());
+		self.latest_modified() > self.modified
+	}
+
+	/// Reads the files, with the built-in source for what isn't replaced.
+	pub fn read(&mut self) -> Result<String, String> {
+		self.modified = self.latest_modified();
+		let read = |path: &Option<PathBuf>, built_in: &str| match path {
+			Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e)),
+			None => Ok(built_in.to_string()),
+		};
+		Ok(format!("{}\n{}", read(&self.custom, CUSTOM)?, read(&self.shader, SHADER)?))
+	}
+
+	fn latest_modified(&self) -> Option<SystemTime> {
+		let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+		self.shader.iter().chain(&self.custom).filter_map(|path| modified(path)).max()
+	}
+}
+
+/// The line of a wgpu error saying what went wrong, and where, when it points into the source.
+/// The full message spans many lines, with the offending source quoted.
+pub fn summary(error: &str) -> String {
+	let lines: Vec<&str> = error.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+	let Some(what) = lines.iter().position(|line| line.to_lowercase().contains("error:")) else {
+		return lines.last().map_or(String::new(), |line| line.to_string());
+	};
+	match lines[what..].iter().find_map(|line| line.split_once("wgsl:")) {
+		Some((_, location)) => format!("{} at line {}", lines[what], location),
+		None => lines[what].to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn built_in_source_validates() {
+		let source = ShaderSource::built_in();
+		let module = naga::front::wgsl::parse_str(&source).unwrap_or_else(|e| panic!("{}", e.emit_to_string(&source)));
+		let mut validator = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::default());
+		if let Err(e) = validator.validate(&module) {
+			panic!("{}", e.emit_to_string(&source));
+		}
+	}
+
+	#[test]
+	fn summary_points_at_the_error() {
+		// the custom shading without the semicolon after its return, as wgpu reports it
+		let source = ShaderSource::built_in().replacen("surface_alpha(in));", "surface_alpha(in))", 1);
+		let error = naga::front::wgsl::parse_str(&source).unwrap_err().emit_to_string(&source);
+		let report = format!("Validation Error\n\nCaused by:\n    In Device::create_shader_module\n      note: label = `Shader`\n    \n{}", error);
+		let summary = summary(&report);
+		assert!(summary.starts_with("error: "), "{}", summary);
+		assert!(summary.ends_with(" at line 34:1"), "{}", summary);
+	}
+
+	#[test]
+	fn summary_without_an_error_line() {
+		assert_eq!(summary("Validation Error\n\nCaused by:\n    something else\n"), "something else");
+		assert_eq!(summary(""), "");
+	}
+}
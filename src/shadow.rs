@@ -0,0 +1,253 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use wgpu::util::DeviceExt;
+use winit::event::VirtualKeyCode;
+
+use crate::bounds::BoundingBox;
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::light::{LightKind, Lights};
+use crate::model::{Mesh, Vertex};
+use crate::settings::{self, Setting};
+use crate::texture::Texture;
+
+/// cgmath's projections map depth to [-1, 1]; wgpu expects [0, 1].
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+	1.0, 0.0, 0.0, 0.0,
+	0.0, 1.0, 0.0, 0.0,
+	0.0, 0.0, 0.5, 0.0,
+	0.0, 0.0, 0.5, 1.0,
+);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadowUniform {
+	view_proj: [[f32; 4]; 4],
+	/// Index of the shadowed light in the lights uniform, or -1 for none.
+	light: i32,
+	bias: f32,
+	texel: f32,
+	softness: f32,
+}
+
+/// Shadow map for the first directional light, with its orthographic frustum fitted around the scene.
+pub struct ShadowMap {
+	pipeline: wgpu::RenderPipeline,
+	pass_bind_group: wgpu::BindGroup,
+	pub buffer: wgpu::Buffer,
+	pub map: Texture,
+	/// Whether a light was found to cast shadows at the last `update`.
+	active: bool,
+	pub enabled: bool,
+	/// Depth offset in shadow map depth units, grown on surfaces at grazing angles.
+	pub bias: f32,
+	/// PCF kernel spacing in texels.
+	pub softness: f32,
+}
+
+impl ShadowMap {
+	pub const SIZE: u32 = 2048;
+
+	/// What `section_buffer` cuts away casts no shadows.
+	pub fn new(device: &wgpu::Device, section_buffer: &wgpu::Buffer, geometry_layout: &wgpu::BindGroupLayout) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Shadow Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+		});
+
+		let map = Texture::new_depth_texture_sized(device, Self::SIZE, Self::SIZE, "shadow_map");
+
+		let buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("Shadow Buffer"),
+				contents: bytemuck::cast_slice(&[ShadowUniform {
+					view_proj: Matrix4::identity().into(),
+					light: -1,
+					bias: 0.0,
+					texel: 1.0 / Self::SIZE as f32,
+					softness: 0.0,
+				}]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::VERTEX,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				}
+			],
+			label: Some("shadow_pass_bind_group_layout"),
+		});
+
+		let pass_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: section_buffer.as_entire_binding(),
+				}
+			],
+			label: Some("shadow_pass_bind_group"),
+		});
+
+		let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Shadow Pipeline Layout"),
+			bind_group_layouts: &[&bind_group_layout, geometry_layout],
+			push_constant_ranges: &[],
+		});
+
+		let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Shadow Pipeline"),
+			layout: Some(&pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_main",
+				buffers: &[
+					Vertex::desc(),
+					InstanceRaw::desc(),
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_main",
+				targets: &[],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				// open meshes and single-sided leaves cast shadows from both sides
+				cull_mode: None,
+				..Default::default()
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::LessEqual,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState {
+					constant: 2,
+					slope_scale: 2.0,
+					clamp: 0.0,
+				},
+			}),
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+
+		Self {
+			pipeline,
+			pass_bind_group,
+			buffer,
+			map,
+			active: false,
+			enabled: true,
+			bias: 0.001,
+			softness: 1.0,
+		}
+	}
+
+	/// Points the shadow frustum along the first directional light, tight around `scene`.
+	/// J turns shadows on and off.
+	pub fn input(&mut self, keycode: VirtualKeyCode) -> Option<String> {
+		if keycode != VirtualKeyCode::J {
+			return None;
+		}
+		self.enabled = !self.enabled;
+		Some(settings::toggled("shadows", self.enabled))
+	}
+
+	pub fn settings(&self) -> &'static [Setting] {
+		if self.enabled { &[Setting::ShadowBias, Setting::ShadowSoftness] } else { &[] }
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::ShadowBias => self.bias = (self.bias * 1.25_f32.powf(steps)).clamp(1e-6, 0.1),
+			Setting::ShadowSoftness => self.softness = (self.softness + steps * 0.5).clamp(0.0, 8.0),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::ShadowBias => Some(format!("{}", self.bias)),
+			Setting::ShadowSoftness => Some(format!("{} texels", self.softness)),
+			_ => None,
+		}
+	}
+
+	pub fn update(&mut self, queue: &wgpu::Queue, lights: &Lights, scene: &BoundingBox) {
+		let light = lights.lights.iter().position(|l| l.kind == LightKind::Directional);
+		let (light, view_proj) = match light {
+			Some(i) if self.enabled && !scene.is_empty() => (i as i32, Self::fit(lights.lights[i].direction, scene)),
+			_ => (-1, Matrix4::identity()),
+		};
+		self.active = light >= 0;
+
+		queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[ShadowUniform {
+			view_proj: view_proj.into(),
+			light,
+			bias: self.bias,
+			texel: 1.0 / Self::SIZE as f32,
+			softness: self.softness,
+		}]));
+	}
+
+	fn fit(direction: Vector3<f32>, scene: &BoundingBox) -> Matrix4<f32> {
+		let direction = direction.normalize();
+		let up = if direction.y.abs() > 0.99 { Vector3::unit_x() } else { Vector3::unit_y() };
+		let center = scene.center();
+		let eye = Point3::from_vec(center - direction * scene.diag().max(1e-3));
+		let view = Matrix4::look_to_rh(eye, direction, up);
+
+		let light_box = scene.transform(&view);
+		// view space looks down -z, so the near plane is the largest z
+		let proj = cgmath::ortho(light_box.min.x, light_box.max.x, light_box.min.y, light_box.max.y, -light_box.max.z, -light_box.min.z);
+		OPENGL_TO_WGPU_MATRIX * proj * view
+	}
+
+	pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, model: &Mesh, instances: &InstanceBuffer) {
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some("Shadow Pass"),
+			color_attachments: &[],
+			depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+				view: &self.map.view,
+				depth_ops: Some(wgpu::Operations {
+					load: wgpu::LoadOp::Clear(1.0),
+					store: wgpu::StoreOp::Store,
+				}),
+				stencil_ops: None,
+			}),
+			occlusion_query_set: None,
+			timestamp_writes: None,
+		});
+		if !self.active {
+			return;
+		}
+
+		render_pass.set_pipeline(&self.pipeline);
+		render_pass.set_bind_group(0, &self.pass_bind_group, &[]);
+		model.draw_geometry(&mut render_pass, instances, &model.all_draws(instances.len()));
+	}
+}
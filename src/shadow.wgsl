@@ -0,0 +1,69 @@
+// Depth-only pass rendering the scene from the shadow-casting light
+struct Shadow {
+    view_proj: mat4x4<f32>,
+    light: i32,
+    bias: f32,
+    texel: f32,
+    softness: f32,
+};
+
+struct Section {
+	planes: array<vec4<f32>, 3>,
+	count: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> shadow: Shadow;
+
+@group(0) @binding(1)
+var<uniform> section: Section;
+
+// where the exploded view has moved the submesh being drawn, see `Mesh::draw_geometry`
+struct Submesh {
+	object: u32,
+	material: u32,
+	offset: vec3<f32>,
+}
+
+@group(1) @binding(3)
+var<uniform> submesh: Submesh;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+}
+
+struct InstanceIn {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexIn,
+    instance: InstanceIn,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world_position = model_matrix * vec4<f32>(model.position + submesh.offset, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = shadow.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+// only discards what the section planes cut away
+@fragment
+fn fs_main(in: VertexOutput) {
+	for (var i = 0u; i < section.count; i++) {
+		if dot(section.planes[i].xyz, in.world_position) > section.planes[i].w {
+			discard;
+		}
+	}
+}
@@ -0,0 +1,44 @@
+// Draws the environment cube behind everything else
+struct Environment {
+	inv_view_proj: mat4x4<f32>,
+	// cosine and sine of the environment's rotation about the vertical axis
+	rotation: vec2<f32>,
+	intensity: f32,
+	specular_mips: f32,
+	enabled: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> environment: Environment;
+
+@group(0) @binding(1)
+var environment_map: texture_cube<f32>;
+
+@group(0) @binding(2)
+var environment_sampler: sampler;
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) ndc: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+	// one triangle covering the screen on the far plane
+	let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+	var out: VertexOutput;
+	out.ndc = uv * 2.0 - 1.0;
+	out.clip_position = vec4<f32>(out.ndc, 1.0, 1.0);
+	return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let far = environment.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+	let near = environment.inv_view_proj * vec4<f32>(in.ndc, 0.0, 1.0);
+	let d = normalize(far.xyz / far.w - near.xyz / near.w);
+	let r = environment.rotation;
+	let rotated = vec3<f32>(r.x * d.x - r.y * d.z, d.y, r.y * d.x + r.x * d.z);
+	let color = textureSampleLevel(environment_map, environment_sampler, rotated, 0.0).rgb;
+	return vec4<f32>(color * environment.intensity, 1.0);
+}
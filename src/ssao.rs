@@ -0,0 +1,310 @@
+use cgmath::{Matrix4, SquareMatrix};
+use wgpu::util::DeviceExt;
+use winit::event::VirtualKeyCode;
+
+use crate::camera::Camera;
+use crate::instance::{InstanceBuffer, InstanceRaw};
+use crate::model::{Draw, Mesh, Vertex};
+use crate::settings::{self, Setting};
+use crate::texture::Texture;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+	proj: [[f32; 4]; 4],
+	inv_proj: [[f32; 4]; 4],
+	radius: f32,
+	strength: f32,
+	_padding: [f32; 2],
+}
+
+/// Screen-space ambient occlusion. A prepass draws depth and view-space normals, occlusion is
+/// estimated from them by sampling a hemisphere around each pixel, then blurred into `ao`,
+/// which the mesh shader multiplies into its lighting.
+pub struct Ssao {
+	normal_pipeline: wgpu::RenderPipeline,
+	occlusion_pipeline: wgpu::RenderPipeline,
+	blur_pipeline: wgpu::RenderPipeline,
+	bind_group_layout: wgpu::BindGroupLayout,
+	occlusion_bind_group: wgpu::BindGroup,
+	blur_bind_group: wgpu::BindGroup,
+	uniform_buffer: wgpu::Buffer,
+	normals: Texture,
+	occlusion: Texture,
+	/// Blurred occlusion, 1 where unoccluded.
+	pub ao: Texture,
+	pub enabled: bool,
+	/// Sampling radius in world units.
+	pub radius: f32,
+	/// Exponent applied to the occlusion, higher is darker.
+	pub strength: f32,
+}
+
+impl Ssao {
+	const NORMAL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+	const AO_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+	pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, camera_layout: &wgpu::BindGroupLayout, geometry_layout: &wgpu::BindGroupLayout, depth_texture: &Texture, radius: f32) -> Self {
+		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("SSAO Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("ssao.wgsl").into()),
+		});
+
+		let uniform_buffer = device.create_buffer_init(
+			&wgpu::util::BufferInitDescriptor {
+				label: Some("SSAO Buffer"),
+				contents: bytemuck::cast_slice(&[SsaoUniform {
+					proj: Matrix4::identity().into(),
+					inv_proj: Matrix4::identity().into(),
+					radius,
+					strength: 1.0,
+					_padding: [0.0; 2],
+				}]),
+				usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+			}
+		);
+
+		let texture = |binding| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Texture {
+				sample_type: wgpu::TextureSampleType::Float { filterable: false },
+				view_dimension: wgpu::TextureViewDimension::D2,
+				multisampled: false,
+			},
+			count: None,
+		};
+
+		// the occlusion and blur passes share a layout: uniform, depth, and the texture they read
+		let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Buffer {
+						ty: wgpu::BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+				texture(1),
+				texture(2),
+			],
+			label: Some("ssao_bind_group_layout"),
+		});
+
+		let normal_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("SSAO Normal Pipeline Layout"),
+			bind_group_layouts: &[camera_layout, geometry_layout],
+			push_constant_ranges: &[],
+		});
+
+		let normal_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("SSAO Normal Pipeline"),
+			layout: Some(&normal_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_normals",
+				buffers: &[
+					Vertex::desc(),
+					InstanceRaw::desc(),
+				],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point: "fs_normals",
+				targets: &[Some(Self::NORMAL_FORMAT.into())],
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				// back faces are turned towards the camera in the shader
+				cull_mode: None,
+				..Default::default()
+			},
+			depth_stencil: Some(wgpu::DepthStencilState {
+				format: Texture::DEPTH_FORMAT,
+				depth_write_enabled: true,
+				depth_compare: wgpu::CompareFunction::Less,
+				stencil: wgpu::StencilState::default(),
+				bias: wgpu::DepthBiasState::default(),
+			}),
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+
+		let pass_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("SSAO Pipeline Layout"),
+			bind_group_layouts: &[&bind_group_layout],
+			push_constant_ranges: &[],
+		});
+
+		let fullscreen = |label, entry_point| device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some(label),
+			layout: Some(&pass_layout),
+			vertex: wgpu::VertexState {
+				module: &shader,
+				entry_point: "vs_fullscreen",
+				buffers: &[],
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &shader,
+				entry_point,
+				targets: &[Some(Self::AO_FORMAT.into())],
+			}),
+			primitive: wgpu::PrimitiveState::default(),
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState::default(),
+			multiview: None,
+		});
+		let occlusion_pipeline = fullscreen("SSAO Occlusion Pipeline", "fs_occlusion");
+		let blur_pipeline = fullscreen("SSAO Blur Pipeline", "fs_blur");
+
+		let normals = Texture::new_render_target(device, config, Self::NORMAL_FORMAT, "ssao_normals");
+		let occlusion = Texture::new_render_target(device, config, Self::AO_FORMAT, "ssao_occlusion");
+		let ao = Texture::new_render_target(device, config, Self::AO_FORMAT, "ssao_ao");
+		let occlusion_bind_group = Self::create_bind_group(device, &bind_group_layout, &uniform_buffer, depth_texture, &normals);
+		let blur_bind_group = Self::create_bind_group(device, &bind_group_layout, &uniform_buffer, depth_texture, &occlusion);
+
+		Self {
+			normal_pipeline,
+			occlusion_pipeline,
+			blur_pipeline,
+			bind_group_layout,
+			occlusion_bind_group,
+			blur_bind_group,
+			uniform_buffer,
+			normals,
+			occlusion,
+			ao,
+			enabled: false,
+			radius,
+			strength: 1.0,
+		}
+	}
+
+	pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, depth_texture: &Texture) {
+		self.normals = Texture::new_render_target(device, config, Self::NORMAL_FORMAT, "ssao_normals");
+		self.occlusion = Texture::new_render_target(device, config, Self::AO_FORMAT, "ssao_occlusion");
+		self.ao = Texture::new_render_target(device, config, Self::AO_FORMAT, "ssao_ao");
+		self.occlusion_bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniform_buffer, depth_texture, &self.normals);
+		self.blur_bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.uniform_buffer, depth_texture, &self.occlusion);
+	}
+
+	/// I turns ambient occlusion on and off.
+	pub fn input(&mut self, keycode: VirtualKeyCode) -> Option<String> {
+		if keycode != VirtualKeyCode::I {
+			return None;
+		}
+		self.enabled = !self.enabled;
+		Some(settings::toggled("ambient occlusion", self.enabled))
+	}
+
+	pub fn settings(&self) -> &'static [Setting] {
+		if self.enabled { &[Setting::SsaoRadius, Setting::SsaoStrength] } else { &[] }
+	}
+
+	pub fn adjust(&mut self, setting: Setting, steps: f32) -> bool {
+		match setting {
+			Setting::SsaoRadius => self.radius = (self.radius * 1.25_f32.powf(steps)).max(1e-4),
+			Setting::SsaoStrength => self.strength = (self.strength + steps * 0.25).clamp(0.25, 4.0),
+			_ => return false,
+		}
+		true
+	}
+
+	pub fn value(&self, setting: Setting) -> Option<String> {
+		match setting {
+			Setting::SsaoRadius => Some(format!("{} units", self.radius)),
+			Setting::SsaoStrength => Some(format!("{}", self.strength)),
+			_ => None,
+		}
+	}
+
+	pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+		let proj = camera.proj();
+		let uniform = SsaoUniform {
+			proj: proj.into(),
+			inv_proj: proj.invert().unwrap_or(Matrix4::identity()).into(),
+			radius: self.radius,
+			strength: self.strength,
+			_padding: [0.0; 2],
+		};
+		queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+	}
+
+	/// Draws the normal prepass of `draws` into `depth_texture` and computes `ao` from it.
+	#[allow(clippy::too_many_arguments)]
+	pub fn draw(&self, encoder: &mut wgpu::CommandEncoder, camera_bind_group: &wgpu::BindGroup, depth_texture: &Texture, model: &Mesh, instances: &InstanceBuffer, draws: &[Draw]) {
+		{
+			let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+				label: Some("SSAO Normal Pass"),
+				color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+					view: &self.normals.view,
+					resolve_target: None,
+					ops: wgpu::Operations {
+						load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+						store: wgpu::StoreOp::Store,
+					},
+				})],
+				depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+					view: &depth_texture.view,
+					depth_ops: Some(wgpu::Operations {
+						load: wgpu::LoadOp::Clear(1.0),
+						store: wgpu::StoreOp::Store,
+					}),
+					stencil_ops: None,
+				}),
+				occlusion_query_set: None,
+				timestamp_writes: None,
+			});
+			render_pass.set_pipeline(&self.normal_pipeline);
+			render_pass.set_bind_group(0, camera_bind_group, &[]);
+			model.draw_geometry(&mut render_pass, instances, draws);
+		}
+
+		Self::fullscreen_pass(encoder, "SSAO Occlusion Pass", &self.occlusion, &self.occlusion_pipeline, &self.occlusion_bind_group);
+		Self::fullscreen_pass(encoder, "SSAO Blur Pass", &self.ao, &self.blur_pipeline, &self.blur_bind_group);
+	}
+
+	fn fullscreen_pass(encoder: &mut wgpu::CommandEncoder, label: &str, target: &Texture, pipeline: &wgpu::RenderPipeline, bind_group: &wgpu::BindGroup) {
+		let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+			label: Some(label),
+			color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+				view: &target.view,
+				resolve_target: None,
+				ops: wgpu::Operations {
+					load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+					store: wgpu::StoreOp::Store,
+				},
+			})],
+			depth_stencil_attachment: None,
+			occlusion_query_set: None,
+			timestamp_writes: None,
+		});
+		render_pass.set_pipeline(pipeline);
+		render_pass.set_bind_group(0, bind_group, &[]);
+		render_pass.draw(0..3, 0..1);
+	}
+
+	fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, uniform_buffer: &wgpu::Buffer, depth_texture: &Texture, input: &Texture) -> wgpu::BindGroup {
+		device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: uniform_buffer.as_entire_binding(),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::TextureView(&depth_texture.view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 2,
+					resource: wgpu::BindingResource::TextureView(&input.view),
+				},
+			],
+			label: Some("ssao_bind_group"),
+		})
+	}
+}
@@ -0,0 +1,184 @@
+// Screen-space ambient occlusion: a normal prepass, hemisphere sampling against the depth buffer
+// and a depth-aware blur
+struct Camera {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct Section {
+	planes: array<vec4<f32>, 3>,
+	count: u32,
+}
+
+struct Ssao {
+	proj: mat4x4<f32>,
+	inv_proj: mat4x4<f32>,
+	radius: f32,
+	strength: f32,
+}
+
+const SAMPLES: i32 = 16;
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(0) @binding(1)
+var<uniform> section: Section;
+
+// where the exploded view has moved the submesh being drawn, see `Mesh::draw_geometry`
+struct Submesh {
+	object: u32,
+	material: u32,
+	offset: vec3<f32>,
+}
+
+@group(1) @binding(3)
+var<uniform> submesh: Submesh;
+
+@group(0) @binding(0)
+var<uniform> ssao: Ssao;
+
+// bound as unfilterable float so it can be loaded like a color texture on every backend
+@group(0) @binding(1)
+var depth_texture: texture_2d<f32>;
+
+// view-space normals for the occlusion pass, raw occlusion for the blur
+@group(0) @binding(2)
+var input_texture: texture_2d<f32>;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct InstanceIn {
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+    @location(9) normal_0: vec3<f32>,
+    @location(10) normal_1: vec3<f32>,
+    @location(11) normal_2: vec3<f32>,
+}
+
+struct NormalOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_normals(model: VertexIn, instance: InstanceIn) -> NormalOutput {
+    let model_matrix = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let normal_matrix = mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+
+    let world_position = model_matrix * vec4<f32>(model.position + submesh.offset, 1.0);
+
+    var out: NormalOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = (camera.view * vec4<f32>(normal_matrix * model.normal, 0.0)).xyz;
+    return out;
+}
+
+@fragment
+fn fs_normals(in: NormalOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+	for (var i = 0u; i < section.count; i++) {
+		if dot(section.planes[i].xyz, in.world_position) > section.planes[i].w {
+			discard;
+		}
+	}
+	let n = normalize(in.normal);
+	return vec4<f32>(select(-n, n, front_facing), 1.0);
+}
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // one triangle covering the screen
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+fn load_depth(coords: vec2<i32>) -> f32 {
+	let size = vec2<i32>(textureDimensions(depth_texture));
+	return textureLoad(depth_texture, clamp(coords, vec2<i32>(0), size - 1), 0).r;
+}
+
+// view-space position of the surface at pixel `coords`
+fn view_position(coords: vec2<f32>, depth: f32) -> vec3<f32> {
+	let uv = coords / vec2<f32>(textureDimensions(depth_texture));
+	let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+	let p = ssao.inv_proj * ndc;
+	return p.xyz / p.w;
+}
+
+// repeats every 4x4 pixels, which the blur averages out
+fn noise(coords: vec2<i32>) -> vec3<f32> {
+	let cell = vec2<f32>(coords % 4);
+	let angle = fract(52.9829189 * fract(dot(cell, vec2<f32>(0.06711056, 0.00583715)))) * 6.2831853;
+	return vec3<f32>(cos(angle), sin(angle), 0.0);
+}
+
+@fragment
+fn fs_occlusion(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let coords = vec2<i32>(position.xy);
+	let depth = load_depth(coords);
+	if depth >= 1.0 {
+		return vec4<f32>(1.0);
+	}
+
+	let p = view_position(position.xy, depth);
+	let n = normalize(textureLoad(input_texture, coords, 0).xyz);
+	let r = noise(coords);
+	let t = normalize(r - n * dot(r, n));
+	let b = cross(n, t);
+	let size = vec2<f32>(textureDimensions(depth_texture));
+
+	var occlusion = 0.0;
+	for (var i = 0; i < SAMPLES; i++) {
+		// spiral over the hemisphere, denser close to the center
+		let f = (f32(i) + 0.5) / f32(SAMPLES);
+		let angle = f32(i) * 2.39996323;
+		let z = sqrt(1.0 - f);
+		let xy = sqrt(f) * vec2<f32>(cos(angle), sin(angle));
+		let scale = mix(0.1, 1.0, f * f);
+		let sample_position = p + (t * xy.x + b * xy.y + n * z) * ssao.radius * scale;
+
+		let clip = ssao.proj * vec4<f32>(sample_position, 1.0);
+		let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+		let sample_coords = uv * size;
+		let sample_depth = load_depth(vec2<i32>(sample_coords));
+		if sample_depth >= 1.0 {
+			continue;
+		}
+		let surface = view_position(sample_coords, sample_depth);
+		// surfaces far outside the radius don't occlude
+		let range = smoothstep(0.0, 1.0, ssao.radius / abs(p.z - surface.z));
+		occlusion += select(0.0, range, surface.z >= sample_position.z + ssao.radius * 0.02);
+	}
+
+	let ao = 1.0 - occlusion / f32(SAMPLES);
+	return vec4<f32>(pow(ao, ssao.strength));
+}
+
+@fragment
+fn fs_blur(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+	let coords = vec2<i32>(position.xy);
+	let size = vec2<i32>(textureDimensions(input_texture));
+	let center = view_position(position.xy, load_depth(coords)).z;
+
+	// 4x4 box matching the noise tile, skipping pixels at very different depths
+	var sum = 0.0;
+	var weight = 0.0;
+	for (var x = -2; x < 2; x++) {
+		for (var y = -2; y < 2; y++) {
+			let c = clamp(coords + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+			let z = view_position(vec2<f32>(c) + 0.5, load_depth(c)).z;
+			let w = select(0.0, 1.0, abs(z - center) < ssao.radius);
+			sum += textureLoad(input_texture, c, 0).r * w;
+			weight += w;
+		}
+	}
+	return vec4<f32>(select(1.0, sum / weight, weight > 0.0));
+}
@@ -8,9 +8,14 @@ impl Texture {
 	pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
 	pub fn new_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        Self::new_depth_texture_sized(device, config.width, config.height, label)
+    }
+
+    /// Depth texture with a `LessEqual` comparison sampler, so it can also be read as a shadow map.
+    pub fn new_depth_texture_sized(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
         let size = wgpu::Extent3d { 
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
@@ -51,4 +56,197 @@ impl Texture {
 			sampler 
 		}
     }
+
+    /// Offscreen color texture the size of the surface, rendered to and then read by a later pass.
+    pub fn new_render_target(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, format: wgpu::TextureFormat, label: &str) -> Self {
+        Self::new_render_target_sized(device, config.width, config.height, format, label)
+    }
+
+    pub fn new_render_target_sized(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }
+        );
+
+        Self {
+			texture,
+			view,
+			sampler
+		}
+    }
+
+    /// Cube map rendered to one face and mip at a time, viewed as a cube with a trilinear sampler.
+    pub fn new_cube(device: &wgpu::Device, size: u32, mip_level_count: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            }
+        );
+
+        Self {
+			texture,
+			view,
+			sampler
+		}
+    }
+
+    /// Multisampled color or depth target the size of the surface. Depth targets can also be bound for loading.
+    pub fn new_multisampled(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, format: wgpu::TextureFormat, sample_count: u32, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: if format.is_depth_stencil_format() {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            },
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // multisampled textures can't be sampled, only loaded
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Self {
+			texture,
+			view,
+			sampler
+		}
+    }
+
+    /// Linear (non-sRGB) texture from an image, for maps that hold data rather than color.
+    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, img: &image::DynamicImage, label: &str) -> Self {
+        let rgba = img.to_rgba8();
+        Self::from_rgba(device, queue, &rgba, rgba.width(), rgba.height(), wgpu::TextureFormat::Rgba8Unorm, label)
+    }
+
+    /// sRGB texture from an image, for maps that hold color.
+    pub fn from_image_srgb(device: &wgpu::Device, queue: &wgpu::Queue, img: &image::DynamicImage, label: &str) -> Self {
+        let rgba = img.to_rgba8();
+        Self::from_rgba(device, queue, &rgba, rgba.width(), rgba.height(), wgpu::TextureFormat::Rgba8UnormSrgb, label)
+    }
+
+    /// 1x1 texture of a single color, bound in place of a missing map.
+    pub fn from_color(device: &wgpu::Device, queue: &wgpu::Queue, color: [u8; 4], label: &str) -> Self {
+        Self::from_rgba(device, queue, &color, 1, 1, wgpu::TextureFormat::Rgba8Unorm, label)
+    }
+
+    fn from_rgba(device: &wgpu::Device, queue: &wgpu::Queue, rgba: &[u8], width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::Repeat,
+                address_mode_v: wgpu::AddressMode::Repeat,
+                address_mode_w: wgpu::AddressMode::Repeat,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Self {
+			texture,
+			view,
+			sampler
+		}
+    }
 }
@@ -0,0 +1,45 @@
+/// Curve mapping the HDR scene into the displayable range. The discriminant is what
+/// `fs_tonemap` switches on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Tonemapper {
+	/// Stephen Hill's fit of the ACES reference rendering and output transforms.
+	Aces = 0,
+	/// Troy Sobotka's AgX, as fitted by Benjamin Wrensch; desaturates bright colors towards white.
+	Agx = 1,
+	Reinhard = 2,
+	/// Clips at 1, as before rendering in HDR.
+	None = 3,
+}
+
+impl Tonemapper {
+	const ALL: [Tonemapper; 4] = [
+		Tonemapper::Aces,
+		Tonemapper::Agx,
+		Tonemapper::Reinhard,
+		Tonemapper::None,
+	];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Tonemapper::Aces => "aces",
+			Tonemapper::Agx => "agx",
+			Tonemapper::Reinhard => "reinhard",
+			Tonemapper::None => "none",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|t| t.name().eq_ignore_ascii_case(name))
+	}
+
+	/// Names of every tonemapper, for usage messages.
+	pub fn names() -> Vec<&'static str> {
+		Self::ALL.into_iter().map(Self::name).collect()
+	}
+
+	/// `steps` tonemappers further along, wrapping around.
+	pub fn step(self, steps: i32) -> Self {
+		let i = Self::ALL.iter().position(|t| *t == self).unwrap() as i32;
+		Self::ALL[(i + steps).rem_euclid(Self::ALL.len() as i32) as usize]
+	}
+}
@@ -0,0 +1,135 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::instance::Instance;
+use crate::model::{Draw, Submesh};
+
+/// Splits every submesh of every instance into opaque draws and blended ones. A submesh is
+/// blended when `transparent_material` holds for its material index or its instance's alpha is
+/// below 1. Opaque instances of a submesh are drawn together in runs; blended ones are drawn one
+/// at a time, farthest from `eye` first, by the centers of their bounding boxes as exploded.
+pub fn split_draws(submeshes: &[Submesh], transparent_material: impl Fn(usize) -> bool, instances: &[Instance], eye: Vector3<f32>) -> (Vec<Draw>, Vec<Draw>) {
+	let mut opaque = Vec::new();
+	let mut transparent = Vec::new();
+
+	for (submesh_index, submesh) in submeshes.iter().enumerate() {
+		let material_transparent = transparent_material(submesh.material);
+		let center = submesh.bounds.aabb.center() + submesh.offset;
+		let mut run: Option<Draw> = None;
+
+		for (i, instance) in instances.iter().enumerate() {
+			let i = i as u32;
+			if material_transparent || instance.color[3] < 1.0 {
+				let world = (instance.transform * center.extend(1.0)).truncate();
+				transparent.push(((world - eye).magnitude2(), Draw { submesh: submesh_index, instances: i..i + 1 }));
+				opaque.extend(run.take());
+				continue;
+			}
+			match &mut run {
+				Some(draw) => draw.instances.end = i + 1,
+				None => run = Some(Draw { submesh: submesh_index, instances: i..i + 1 }),
+			}
+		}
+		opaque.extend(run);
+	}
+
+	transparent.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+	(opaque, transparent.into_iter().map(|(_, draw)| draw).collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use cgmath::{Matrix4, Zero};
+
+	use super::*;
+	use crate::bounds::Bounds;
+	use crate::material::{MaterialUniform, Maps};
+
+	fn submesh(material: usize, center: [f32; 3]) -> Submesh {
+		let center = Vector3::from(center);
+		Submesh {
+			name: String::new(),
+			indices: 0..0,
+			bounds: Bounds::from_points(&[center - Vector3::new(0.5, 0.5, 0.5), center + Vector3::new(0.5, 0.5, 0.5)]),
+			material,
+			vertices: 0..0,
+			offset: Vector3::zero(),
+		}
+	}
+
+	fn instance(x: f32, alpha: f32) -> Instance {
+		Instance {
+			transform: Matrix4::from_translation(Vector3::new(x, 0.0, 0.0)),
+			color: [1.0, 1.0, 1.0, alpha],
+		}
+	}
+
+	fn draw(submesh: usize, instances: std::ops::Range<u32>) -> Draw {
+		Draw { submesh, instances }
+	}
+
+	const EYE: Vector3<f32> = Vector3::new(0.0, 0.0, 10.0);
+
+	#[test]
+	fn opaque_draws_keep_their_order() {
+		let submeshes = [submesh(0, [0.0; 3]), submesh(0, [0.0, 1.0, 0.0])];
+		let instances = [instance(0.0, 1.0), instance(1.0, 1.0), instance(2.0, 0.5), instance(3.0, 1.0)];
+		let (opaque, transparent) = split_draws(&submeshes, |_| false, &instances, EYE);
+		// a blended instance breaks the run it falls in
+		assert_eq!(opaque, vec![draw(0, 0..2), draw(0, 3..4), draw(1, 0..2), draw(1, 3..4)]);
+		assert_eq!(transparent.len(), 2);
+		assert!(transparent.iter().all(|d| d.instances == (2..3)));
+	}
+
+	#[test]
+	fn blended_draws_come_back_to_front() {
+		let submeshes = [submesh(1, [0.0, 0.0, -5.0]), submesh(0, [0.0; 3]), submesh(1, [0.0, 0.0, 5.0]), submesh(1, [0.0, 0.0, -10.0])];
+		let (opaque, transparent) = split_draws(&submeshes, |m| m == 1, &[Instance::default()], EYE);
+		assert_eq!(opaque, vec![draw(1, 0..1)]);
+		assert_eq!(transparent, vec![draw(3, 0..1), draw(0, 0..1), draw(2, 0..1)]);
+	}
+
+	#[test]
+	fn instances_are_sorted_by_where_they_are() {
+		let instances = [instance(0.0, 1.0), instance(-30.0, 1.0), instance(5.0, 1.0)];
+		let (_, transparent) = split_draws(&[submesh(0, [0.0; 3])], |_| true, &instances, EYE);
+		assert_eq!(transparent, vec![draw(0, 1..2), draw(0, 2..3), draw(0, 0..1)]);
+	}
+
+	#[test]
+	fn exploded_offsets_count() {
+		let mut submeshes = [submesh(0, [0.0, 0.0, 0.0]), submesh(0, [0.0, 0.0, -5.0])];
+		let (_, transparent) = split_draws(&submeshes, |_| true, &[Instance::default()], EYE);
+		assert_eq!(transparent, vec![draw(1, 0..1), draw(0, 0..1)]);
+
+		submeshes[0].offset = Vector3::new(0.0, 0.0, -20.0);
+		let (_, transparent) = split_draws(&submeshes, |_| true, &[Instance::default()], EYE);
+		assert_eq!(transparent, vec![draw(0, 0..1), draw(1, 0..1)]);
+	}
+
+	#[test]
+	fn cutouts_stay_opaque() {
+		let cutout = tobj::Material {
+			dissolve_texture: Some("leaf.png".into()),
+			..Default::default()
+		};
+		let glass = tobj::Material {
+			dissolve: Some(0.3),
+			..Default::default()
+		};
+		let mut tinted = tobj::Material::default();
+		tinted.unknown_param.insert("Tr".into(), "0.25".into());
+
+		let materials = [
+			MaterialUniform::from_mtl(&cutout, &Maps { dissolve: true, ..Default::default() }),
+			MaterialUniform::from_mtl(&glass, &Maps::default()),
+			MaterialUniform::from_mtl(&tinted, &Maps::default()),
+		];
+		assert_eq!(materials[0].cutout[0], 0.5);
+		assert_eq!(materials[2].diffuse[3], 0.75);
+
+		let submeshes = [submesh(0, [0.0; 3]), submesh(1, [0.0; 3]), submesh(2, [0.0, 0.0, -1.0])];
+		let (opaque, transparent) = split_draws(&submeshes, |m| materials[m].is_transparent(), &[Instance::default()], EYE);
+		assert_eq!(opaque, vec![draw(0, 0..1)]);
+		assert_eq!(transparent, vec![draw(2, 0..1), draw(1, 0..1)]);
+	}
+}